@@ -1,28 +1,267 @@
-pub fn ends_with_newline(bytes: &[u8]) -> bool {
-    bytes.ends_with(b"\n")
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+pub mod patch;
+#[cfg(feature = "std")]
+pub mod pattern;
+
+pub fn ends_with_newline(bytes: impl AsRef<[u8]>) -> bool {
+    bytes.as_ref().ends_with(b"\n")
 }
 
+/// Like `ends_with_newline`, but a lone trailing `\r` (old Mac line ending)
+/// also counts as a line terminator.
+pub fn ends_with_line_terminator(bytes: impl AsRef<[u8]>) -> bool {
+    let bytes = bytes.as_ref();
+    ends_with_newline(bytes) || bytes.ends_with(b"\r")
+}
+
+#[cfg(feature = "alloc")]
 pub fn strip_one_trailing_newline(bytes: &mut Vec<u8>) -> bool {
+    strip_one_trailing_newline_kind(bytes) != Stripped::None
+}
+
+/// `bytes` without one trailing `\r\n` or `\n`; needs neither `std` nor
+/// `alloc`.
+pub fn strip_one_trailing_newline_slice(bytes: &[u8]) -> &[u8] {
+    bytes
+        .strip_suffix(b"\r\n")
+        .or_else(|| bytes.strip_suffix(b"\n"))
+        .unwrap_or(bytes)
+}
+
+/// Which terminator `strip_one_trailing_newline_kind` removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stripped {
+    None,
+    Lf,
+    CrLf,
+}
+
+/// Like `strip_one_trailing_newline`, but reports whether a `\n` or a
+/// `\r\n` was removed so callers can write the same terminator back.
+#[cfg(feature = "alloc")]
+pub fn strip_one_trailing_newline_kind(bytes: &mut Vec<u8>) -> Stripped {
     if bytes.ends_with(b"\r\n") {
         let new_len = bytes.len() - 2;
         bytes.truncate(new_len);
-        return true;
+        return Stripped::CrLf;
     }
     if bytes.ends_with(b"\n") {
         let new_len = bytes.len() - 1;
         bytes.truncate(new_len);
-        return true;
+        return Stripped::Lf;
     }
-    false
+    Stripped::None
 }
 
-pub fn added_eof_newline(old_bytes: &[u8], new_bytes: &[u8]) -> bool {
-    !ends_with_newline(old_bytes) && ends_with_newline(new_bytes)
+/// Removes every trailing line terminator (`\r\n`, `\n` or a lone `\r`) and
+/// returns how many were removed.
+#[cfg(feature = "alloc")]
+pub fn strip_all_trailing_newlines(bytes: &mut Vec<u8>) -> usize {
+    let mut count = 0;
+    while let Some(&last) = bytes.last() {
+        match last {
+            b'\n' if bytes.ends_with(b"\r\n") => bytes.truncate(bytes.len() - 2),
+            b'\n' | b'\r' => bytes.truncate(bytes.len() - 1),
+            _ => break,
+        }
+        count += 1;
+    }
+    count
+}
+
+/// A line terminator style that callers can force instead of relying on
+/// autodetection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eol {
+    Lf,
+    CrLf,
+}
+
+/// Removes one trailing terminator of exactly the given style.
+///
+/// `Eol::Lf` removes only the final `\n` byte, keeping any `\r` before it;
+/// `Eol::CrLf` removes a trailing `\r\n` and leaves a bare `\n` alone.
+#[cfg(feature = "alloc")]
+pub fn strip_one_trailing_eol(bytes: &mut Vec<u8>, eol: Eol) -> bool {
+    let terminator: &[u8] = match eol {
+        Eol::Lf => b"\n",
+        Eol::CrLf => b"\r\n",
+    };
+    if !bytes.ends_with(terminator) {
+        return false;
+    }
+    let new_len = bytes.len() - terminator.len();
+    bytes.truncate(new_len);
+    true
+}
+
+/// The line terminators a blob uses throughout (not just at EOF).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewlineStyle {
+    Lf,
+    CrLf,
+    Cr,
+    /// More than one of `\n`, `\r\n` and lone `\r` appear.
+    Mixed,
+    /// No line terminator at all.
+    None,
+}
+
+pub fn detect_newline_style(bytes: &[u8]) -> NewlineStyle {
+    let (mut lf, mut crlf, mut cr) = (false, false, false);
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                crlf = true;
+                i += 1;
+            }
+            b'\r' => cr = true,
+            b'\n' => lf = true,
+            _ => {}
+        }
+        i += 1;
+    }
+    match (lf, crlf, cr) {
+        (false, false, false) => NewlineStyle::None,
+        (true, false, false) => NewlineStyle::Lf,
+        (false, true, false) => NewlineStyle::CrLf,
+        (false, false, true) => NewlineStyle::Cr,
+        _ => NewlineStyle::Mixed,
+    }
+}
+
+/// Replaces however many terminators end `bytes` (none included) with
+/// exactly one in `style`, returning whether the content changed.
+///
+/// `Mixed` reuses the last terminator found in `bytes` (`\n` if there is
+/// none) and `None` just strips them all. Empty input stays empty.
+#[cfg(feature = "alloc")]
+pub fn normalize_trailing_newline(bytes: &mut Vec<u8>, style: NewlineStyle) -> bool {
+    if bytes.is_empty() {
+        return false;
+    }
+    let terminator = terminator_for(style, bytes);
+    let mut body_len = bytes.len();
+    while body_len > 0 && matches!(bytes[body_len - 1], b'\n' | b'\r') {
+        body_len -= 1;
+    }
+    if &bytes[body_len..] == terminator {
+        return false;
+    }
+    bytes.truncate(body_len);
+    bytes.extend_from_slice(terminator);
+    true
+}
+
+/// The inverse policy: appends one terminator in `style` when `bytes` does
+/// not already end with one, returning whether it appended.
+///
+/// Empty input stays empty. A lone trailing `\r` already counts as a
+/// terminator, except that `CrLf` completes it to `\r\n`. `Mixed` and
+/// `None` pick the terminator as in `normalize_trailing_newline`, so `None`
+/// never appends.
+#[cfg(feature = "alloc")]
+pub fn ensure_trailing_newline(bytes: &mut Vec<u8>, style: NewlineStyle) -> bool {
+    if bytes.is_empty() || ends_with_newline(&bytes[..]) {
+        return false;
+    }
+    let terminator: &[u8] = if bytes.ends_with(b"\r") {
+        if style != NewlineStyle::CrLf {
+            return false;
+        }
+        b"\n"
+    } else {
+        terminator_for(style, bytes)
+    };
+    bytes.extend_from_slice(terminator);
+    !terminator.is_empty()
+}
+
+/// The bytes of one terminator in `style`; see `normalize_trailing_newline`
+/// for `Mixed` and `None`.
+#[cfg(feature = "alloc")]
+fn terminator_for(style: NewlineStyle, bytes: &[u8]) -> &'static [u8] {
+    match style {
+        NewlineStyle::Lf => b"\n",
+        NewlineStyle::CrLf => b"\r\n",
+        NewlineStyle::Cr => b"\r",
+        NewlineStyle::None => b"",
+        NewlineStyle::Mixed => match bytes.iter().rposition(|b| matches!(b, b'\n' | b'\r')) {
+            Some(i) if bytes[i] == b'\r' => b"\r",
+            Some(i) if i > 0 && bytes[i - 1] == b'\r' => b"\r\n",
+            _ => b"\n",
+        },
+    }
+}
+
+/// How many trailing bytes decide every EOF predicate in this crate
+/// (`\r\n` is the longest terminator).
+pub const EOF_TAIL_LEN: usize = 2;
+
+/// The last `EOF_TAIL_LEN` bytes of `bytes`; enough to answer
+/// `ends_with_newline` and `added_eof_newline` without the full content.
+pub fn eof_tail(bytes: &[u8]) -> &[u8] {
+    &bytes[bytes.len().saturating_sub(EOF_TAIL_LEN)..]
+}
+
+/// Whether the change gives a file without a final line terminator a
+/// trailing newline. An old side ending in a lone `\r` already had a
+/// terminator, so `"a\r"` -> `"a\r\n"` is not an addition.
+pub fn added_eof_newline(old_bytes: impl AsRef<[u8]>, new_bytes: impl AsRef<[u8]>) -> bool {
+    !ends_with_line_terminator(old_bytes) && ends_with_newline(new_bytes)
+}
+
+/// The mirror of `added_eof_newline`: the old content ended with a newline
+/// and the new content does not.
+pub fn removed_eof_newline(old_bytes: impl AsRef<[u8]>, new_bytes: impl AsRef<[u8]>) -> bool {
+    ends_with_newline(old_bytes) && !ends_with_newline(new_bytes)
+}
+
+/// How many leading bytes `looks_binary` inspects (the same as git).
+pub const BINARY_CHECK_LEN: usize = 8000;
+
+/// Whether `bytes` looks like binary data rather than text: like git, a NUL
+/// byte within the first `BINARY_CHECK_LEN` bytes.
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_CHECK_LEN)].contains(&0)
+}
+
+/// The last line of `bytes` that is not empty (ignoring a trailing `\r`),
+/// or an empty slice if every line is empty.
+pub fn last_non_empty_line(bytes: &[u8]) -> &[u8] {
+    bytes
+        .split(|b| *b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .rfind(|line| !line.is_empty())
+        .unwrap_or(b"")
+}
+
+/// How many line terminators end `bytes`, counting `\r\n` and `\n` each as
+/// one. A lone trailing `\r` is not counted and ends the run.
+pub fn count_trailing_newlines(bytes: &[u8]) -> usize {
+    let mut rest = bytes;
+    let mut count = 0;
+    while let Some(stripped) = rest.strip_suffix(b"\n") {
+        rest = stripped.strip_suffix(b"\r").unwrap_or(stripped);
+        count += 1;
+    }
+    count
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "alloc")]
+    use alloc::string::String;
 
     #[test]
     fn ends_with_newline_cases() {
@@ -34,6 +273,17 @@ mod tests {
         assert!(ends_with_newline(b"a\r\n"));
     }
 
+    #[test]
+    fn ends_with_line_terminator_cases() {
+        assert!(!ends_with_line_terminator(b""));
+        assert!(!ends_with_line_terminator(b"a"));
+        assert!(ends_with_line_terminator(b"a\r"));
+        assert!(ends_with_line_terminator(b"a\n"));
+        assert!(ends_with_line_terminator(b"a\r\n"));
+        assert!(ends_with_line_terminator(b"\r"));
+    }
+
+    #[cfg(feature = "alloc")]
     #[test]
     fn strip_one_trailing_newline_cases() {
         let mut v = b"".to_vec();
@@ -53,6 +303,182 @@ mod tests {
         assert_eq!(v, b"a");
     }
 
+    #[test]
+    fn strip_one_trailing_newline_slice_cases() {
+        assert_eq!(strip_one_trailing_newline_slice(b""), b"");
+        assert_eq!(strip_one_trailing_newline_slice(b"a"), b"a");
+        assert_eq!(strip_one_trailing_newline_slice(b"a\n"), b"a");
+        assert_eq!(strip_one_trailing_newline_slice(b"a\r\n"), b"a");
+        assert_eq!(strip_one_trailing_newline_slice(b"a\n\n"), b"a\n");
+        assert_eq!(strip_one_trailing_newline_slice(b"a\r"), b"a\r");
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn strip_one_trailing_newline_kind_cases() {
+        let mut v = b"a\r\n".to_vec();
+        assert_eq!(strip_one_trailing_newline_kind(&mut v), Stripped::CrLf);
+        assert_eq!(v, b"a");
+
+        let mut v = b"a\n".to_vec();
+        assert_eq!(strip_one_trailing_newline_kind(&mut v), Stripped::Lf);
+        assert_eq!(v, b"a");
+
+        let mut v = b"a\r".to_vec();
+        assert_eq!(strip_one_trailing_newline_kind(&mut v), Stripped::None);
+        assert_eq!(v, b"a\r");
+
+        let mut v = b"".to_vec();
+        assert_eq!(strip_one_trailing_newline_kind(&mut v), Stripped::None);
+        assert_eq!(v, b"");
+
+        let mut v = b"a\n\r\n".to_vec();
+        assert_eq!(strip_one_trailing_newline_kind(&mut v), Stripped::CrLf);
+        assert_eq!(v, b"a\n");
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn strip_all_trailing_newlines_cases() {
+        let mut v = b"a\n\n\n".to_vec();
+        assert_eq!(strip_all_trailing_newlines(&mut v), 3);
+        assert_eq!(v, b"a");
+
+        let mut v = b"a".to_vec();
+        assert_eq!(strip_all_trailing_newlines(&mut v), 0);
+        assert_eq!(v, b"a");
+
+        let mut v = b"a\r\n\n\r".to_vec();
+        assert_eq!(strip_all_trailing_newlines(&mut v), 3);
+        assert_eq!(v, b"a");
+
+        let mut v = b"a\nb\r\n\r\n".to_vec();
+        assert_eq!(strip_all_trailing_newlines(&mut v), 2);
+        assert_eq!(v, b"a\nb");
+
+        let mut v = b"\n\n".to_vec();
+        assert_eq!(strip_all_trailing_newlines(&mut v), 2);
+        assert_eq!(v, b"");
+    }
+
+    #[test]
+    fn detect_newline_style_cases() {
+        assert_eq!(detect_newline_style(b""), NewlineStyle::None);
+        assert_eq!(detect_newline_style(b"no terminator"), NewlineStyle::None);
+        assert_eq!(detect_newline_style(b"a\nb\nc\n"), NewlineStyle::Lf);
+        assert_eq!(detect_newline_style(b"a\nb"), NewlineStyle::Lf);
+        assert_eq!(detect_newline_style(b"a\r\nb\r\n"), NewlineStyle::CrLf);
+        assert_eq!(detect_newline_style(b"a\rb\r"), NewlineStyle::Cr);
+        assert_eq!(
+            detect_newline_style(b"a\r\nb\nc\nd\ne\n"),
+            NewlineStyle::Mixed
+        );
+        assert_eq!(detect_newline_style(b"a\rb\n"), NewlineStyle::Mixed);
+        assert_eq!(detect_newline_style(b"a\r\nb\r"), NewlineStyle::Mixed);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn normalize_trailing_newline_cases() {
+        let cases: &[(&[u8], NewlineStyle, &[u8], bool)] = &[
+            (b"a", NewlineStyle::Lf, b"a\n", true),
+            (b"a\n", NewlineStyle::Lf, b"a\n", false),
+            (b"a\n\n\n", NewlineStyle::Lf, b"a\n", true),
+            (b"a\r\n\n", NewlineStyle::CrLf, b"a\r\n", true),
+            (b"a\r\n", NewlineStyle::CrLf, b"a\r\n", false),
+            (b"a\r\n", NewlineStyle::Lf, b"a\n", true),
+            (b"a\n", NewlineStyle::Cr, b"a\r", true),
+            (b"a\n\n", NewlineStyle::None, b"a", true),
+            (b"a", NewlineStyle::None, b"a", false),
+            (b"x\r\ny", NewlineStyle::Mixed, b"x\r\ny\r\n", true),
+            (b"x\ny\r\n\r\n", NewlineStyle::Mixed, b"x\ny\r\n", true),
+            (b"x", NewlineStyle::Mixed, b"x\n", true),
+            (b"", NewlineStyle::Lf, b"", false),
+            (b"\n\n", NewlineStyle::Lf, b"\n", true),
+        ];
+        for (input, style, expected, changed) in cases {
+            let mut v = input.to_vec();
+            assert_eq!(
+                normalize_trailing_newline(&mut v, *style),
+                *changed,
+                "input={input:?} style={style:?}"
+            );
+            assert_eq!(v, *expected, "input={input:?} style={style:?}");
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn ensure_trailing_newline_cases() {
+        let cases: &[(&[u8], NewlineStyle, &[u8], bool)] = &[
+            (b"a", NewlineStyle::Lf, b"a\n", true),
+            (b"a", NewlineStyle::CrLf, b"a\r\n", true),
+            (b"a", NewlineStyle::Cr, b"a\r", true),
+            (b"a\n", NewlineStyle::CrLf, b"a\n", false),
+            (b"a\r\n", NewlineStyle::Lf, b"a\r\n", false),
+            (b"", NewlineStyle::Lf, b"", false),
+            (b"a", NewlineStyle::None, b"a", false),
+            (b"x\r\ny", NewlineStyle::Mixed, b"x\r\ny\r\n", true),
+        ];
+        for (input, style, expected, appended) in cases {
+            let mut v = input.to_vec();
+            assert_eq!(
+                ensure_trailing_newline(&mut v, *style),
+                *appended,
+                "input={input:?} style={style:?}"
+            );
+            assert_eq!(v, *expected, "input={input:?} style={style:?}");
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn ensure_trailing_newline_lone_cr() {
+        let mut v = b"a\r".to_vec();
+        assert!(!ensure_trailing_newline(&mut v, NewlineStyle::Lf));
+        assert_eq!(v, b"a\r");
+
+        let mut v = b"a\r".to_vec();
+        assert!(!ensure_trailing_newline(&mut v, NewlineStyle::Cr));
+        assert_eq!(v, b"a\r");
+
+        let mut v = b"a\r".to_vec();
+        assert!(ensure_trailing_newline(&mut v, NewlineStyle::CrLf));
+        assert_eq!(v, b"a\r\n");
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn strip_one_trailing_eol_lf() {
+        let mut v = b"a\n".to_vec();
+        assert!(strip_one_trailing_eol(&mut v, Eol::Lf));
+        assert_eq!(v, b"a");
+
+        let mut v = b"a\r\n".to_vec();
+        assert!(strip_one_trailing_eol(&mut v, Eol::Lf));
+        assert_eq!(v, b"a\r");
+
+        let mut v = b"a".to_vec();
+        assert!(!strip_one_trailing_eol(&mut v, Eol::Lf));
+        assert_eq!(v, b"a");
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn strip_one_trailing_eol_crlf() {
+        let mut v = b"a\r\n".to_vec();
+        assert!(strip_one_trailing_eol(&mut v, Eol::CrLf));
+        assert_eq!(v, b"a");
+
+        let mut v = b"a\n".to_vec();
+        assert!(!strip_one_trailing_eol(&mut v, Eol::CrLf));
+        assert_eq!(v, b"a\n");
+
+        let mut v = b"a\r\n\r\n".to_vec();
+        assert!(strip_one_trailing_eol(&mut v, Eol::CrLf));
+        assert_eq!(v, b"a\r\n");
+    }
+
     #[test]
     fn added_eof_newline_cases() {
         assert!(added_eof_newline(b"a", b"a\n"));
@@ -61,4 +487,106 @@ mod tests {
         assert!(!added_eof_newline(b"a\n", b"a"));
         assert!(added_eof_newline(b"", b"\n"));
     }
+
+    #[test]
+    fn added_eof_newline_with_lone_cr_old_side() {
+        assert!(!added_eof_newline(b"a\r", b"a\r\n"));
+        assert!(!added_eof_newline(b"a\r", b"a\n"));
+        assert!(!added_eof_newline(b"\r", b"\r\n"));
+        // A lone CR on the new side is not a newline being added.
+        assert!(!added_eof_newline(b"a", b"a\r"));
+        assert!(!added_eof_newline(b"a\r", b"a\r"));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn predicates_accept_owned_and_string_inputs() {
+        let old: Vec<u8> = b"a".to_vec();
+        let new: Vec<u8> = b"a\r\n".to_vec();
+        assert!(ends_with_newline(&new));
+        assert!(added_eof_newline(&old, &new));
+        assert!(added_eof_newline(old, new));
+
+        assert!(ends_with_newline(String::from("a\n")));
+        assert!(ends_with_line_terminator("a\r"));
+        assert!(added_eof_newline("a", String::from("a\n")));
+        assert!(removed_eof_newline(String::from("a\n"), "a"));
+    }
+
+    #[test]
+    fn removed_eof_newline_cases() {
+        assert!(removed_eof_newline(b"a\n", b"a"));
+        assert!(removed_eof_newline(b"a\r\n", b"a"));
+        assert!(!removed_eof_newline(b"a", b"a"));
+        assert!(!removed_eof_newline(b"a\n", b"a\n"));
+        assert!(!removed_eof_newline(b"a", b"a\n"));
+        assert!(removed_eof_newline(b"\n", b""));
+    }
+
+    #[test]
+    fn eof_tail_agrees_with_full_content() {
+        let samples: &[&[u8]] = &[
+            b"", b"a", b"\n", b"\r", b"\r\n", b"ab", b"a\n", b"a\r", b"a\r\n", b"a\n\n",
+            b"abc\r\n", b"abc\n\r",
+        ];
+        for old in samples {
+            assert_eq!(ends_with_newline(eof_tail(old)), ends_with_newline(old));
+            for new in samples {
+                assert_eq!(
+                    added_eof_newline(eof_tail(old), eof_tail(new)),
+                    added_eof_newline(old, new),
+                    "old={old:?} new={new:?}"
+                );
+                assert_eq!(
+                    removed_eof_newline(eof_tail(old), eof_tail(new)),
+                    removed_eof_newline(old, new),
+                    "old={old:?} new={new:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn looks_binary_cases() {
+        assert!(!looks_binary(b""));
+        assert!(!looks_binary(b"plain text\n"));
+        assert!(looks_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\n"));
+        let mut late_nul = [b'a'; BINARY_CHECK_LEN + 1];
+        late_nul[BINARY_CHECK_LEN] = 0;
+        assert!(!looks_binary(&late_nul));
+        late_nul[BINARY_CHECK_LEN - 1] = 0;
+        assert!(looks_binary(&late_nul));
+    }
+
+    #[test]
+    fn last_non_empty_line_cases() {
+        assert_eq!(last_non_empty_line(b""), b"");
+        assert_eq!(last_non_empty_line(b"\n\n"), b"");
+        assert_eq!(last_non_empty_line(b"a"), b"a");
+        assert_eq!(last_non_empty_line(b"a\nb\n"), b"b");
+        assert_eq!(last_non_empty_line(b"a\r\nb\r\n\r\n"), b"b");
+    }
+
+    #[test]
+    fn count_trailing_newlines_cases() {
+        assert_eq!(count_trailing_newlines(b""), 0);
+        assert_eq!(count_trailing_newlines(b"x"), 0);
+        assert_eq!(count_trailing_newlines(b"x\n"), 1);
+        assert_eq!(count_trailing_newlines(b"x\n\n\n"), 3);
+        assert_eq!(count_trailing_newlines(b"x\n\n"), 2);
+        assert_eq!(count_trailing_newlines(b"x\r\n\r\n"), 2);
+    }
+
+    #[test]
+    fn count_trailing_newlines_mixed_tails() {
+        assert_eq!(count_trailing_newlines(b"x\r\n\n"), 2);
+        assert_eq!(count_trailing_newlines(b"x\n\r\n"), 2);
+        assert_eq!(count_trailing_newlines(b"x\r\n\n\r\n\n"), 4);
+        assert_eq!(count_trailing_newlines(b"\r\n\n"), 2);
+        // A `\r` that is not followed by `\n` stops the count.
+        assert_eq!(count_trailing_newlines(b"x\n\r"), 0);
+        assert_eq!(count_trailing_newlines(b"x\n\r\r\n"), 1);
+        // Newlines before the last line do not count.
+        assert_eq!(count_trailing_newlines(b"a\n\nb\r\n"), 1);
+    }
 }
@@ -0,0 +1,121 @@
+//! Object lookups through libgit2 (the `libgit2` feature), so a scan does
+//! not go through `git` for every `<commit>:<path>`, blob and diff.
+//!
+//! Each function returns `None` when libgit2 cannot answer exactly as the
+//! `git` CLI would (the repository does not open, or the lookup involves
+//! state only the CLI sees, such as a temporary index); the caller then
+//! asks the CLI. History rewrites always go through the CLI.
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+use git2::{Delta, DiffFindOptions, DiffOptions, ObjectType, Oid, Repository};
+
+use crate::DiffRecord;
+
+thread_local! {
+    /// This thread's repository handle: `None` until first used, then the
+    /// result of opening it (a handle is not shared between threads).
+    static REPO: RefCell<Option<Option<Repository>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with this thread's repository, opened from the environment like
+/// `git` itself (`GIT_DIR` in the filter-branch child, the current directory
+/// otherwise), or returns `None` if libgit2 cannot open it.
+fn with_repo<T>(f: impl FnOnce(&Repository) -> T) -> Option<T> {
+    REPO.with(|cell| {
+        let mut slot = cell.borrow_mut();
+        let repo = slot.get_or_insert_with(|| Repository::open_from_env().ok());
+        repo.as_ref().map(f)
+    })
+}
+
+/// `git rev-parse <spec>`. Index lookups (`:<path>`) are left to the CLI,
+/// which sees the same `GIT_INDEX_FILE` as the commands that wrote it.
+pub fn rev_parse(spec: &str) -> Option<Result<String, String>> {
+    if spec.starts_with(':') {
+        return None;
+    }
+    with_repo(|repo| {
+        repo.revparse_single(spec)
+            .map(|object| object.id().to_string())
+            .map_err(|e| format!("failed to resolve {spec}: {}", e.message()))
+    })
+}
+
+/// Hands the content of blob `oid` to `f`, refusing blobs over `limit`
+/// bytes with the same message as `git cat-file --batch` reads.
+pub fn with_blob<T>(
+    oid: &str,
+    limit: u64,
+    f: impl FnOnce(&[u8]) -> T,
+) -> Option<Result<T, String>> {
+    let id = Oid::from_str(oid).ok()?;
+    with_repo(|repo| {
+        let odb = repo.odb().map_err(|e| e.message().to_string())?;
+        let (size, kind) = odb
+            .read_header(id)
+            .map_err(|e| format!("failed to read {oid}: {}", e.message()))?;
+        if kind != ObjectType::Blob {
+            return Err(format!("{oid} is a {kind}, not a blob"));
+        }
+        if size as u64 > limit {
+            return Err(format!(
+                "blob {oid} is {size} bytes, over the --max-blob-size limit of {limit}"
+            ));
+        }
+        let blob = repo
+            .find_blob(id)
+            .map_err(|e| format!("failed to read {oid}: {}", e.message()))?;
+        Ok(f(blob.content()))
+    })
+}
+
+/// The records of `git diff-tree -M [-C] --name-status -r <parent> <commit>`.
+pub fn diff_records(
+    parent: &str,
+    commit: &str,
+    find_copies: bool,
+) -> Option<Result<Vec<DiffRecord>, String>> {
+    with_repo(|repo| {
+        let tree = |rev: &str| {
+            repo.revparse_single(rev)
+                .and_then(|object| object.peel_to_tree())
+                .map_err(|e| format!("failed to read the tree of {rev}: {}", e.message()))
+        };
+        let (old, new) = (tree(parent)?, tree(commit)?);
+        let mut options = DiffOptions::new();
+        options.include_typechange(true);
+        let mut diff = repo
+            .diff_tree_to_tree(Some(&old), Some(&new), Some(&mut options))
+            .map_err(|e| format!("failed to diff {commit}: {}", e.message()))?;
+        // `diff-tree -M`'s 50% similarity, and git's default rename limit.
+        let mut find = DiffFindOptions::new();
+        find.renames(true).copies(find_copies).rename_limit(1000);
+        diff.find_similar(Some(&mut find))
+            .map_err(|e| format!("failed to find renames in {commit}: {}", e.message()))?;
+
+        let path = |file: git2::DiffFile<'_>| {
+            PathBuf::from(String::from_utf8_lossy(file.path_bytes().unwrap_or_default()).as_ref())
+        };
+        Ok(diff
+            .deltas()
+            .filter_map(|delta| {
+                let status = match delta.status() {
+                    Delta::Added => 'A',
+                    Delta::Deleted => 'D',
+                    Delta::Modified => 'M',
+                    Delta::Renamed => 'R',
+                    Delta::Copied => 'C',
+                    Delta::Typechange => 'T',
+                    _ => return None,
+                };
+                Some(DiffRecord {
+                    status,
+                    old: path(delta.old_file()),
+                    new: path(delta.new_file()),
+                })
+            })
+            .collect())
+    })
+}
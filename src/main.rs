@@ -1,612 +1,5348 @@
-use codex_no_newline::{added_eof_newline, strip_one_trailing_newline};
-use std::collections::BTreeSet;
+use codex_no_newline::patch;
+use codex_no_newline::pattern::{Glob, Regex};
+use codex_no_newline::{
+    BINARY_CHECK_LEN, EOF_TAIL_LEN, Eol, added_eof_newline, count_trailing_newlines,
+    last_non_empty_line, looks_binary, removed_eof_newline, strip_one_trailing_eol,
+    strip_one_trailing_newline,
+};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, ExitCode};
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitCode, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "libgit2")]
+mod libgit2;
 
 #[derive(Debug, Clone)]
 struct Args {
+    help: bool,
     n: usize,
+    range: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
     dry_run: bool,
+    diff: bool,
+    summary: bool,
+    interactive: bool,
+    /// `--backup-ref`: the ref that saves the original HEAD before a rewrite.
+    backup_ref: Option<String>,
+    autostash: bool,
+    yes: bool,
+    check: bool,
     in_rebase: bool,
     in_filter_branch: bool,
+    check_message_eof: bool,
+    index_requires_matching_worktree: bool,
+    list_policies: bool,
+    verify_git_version: bool,
+    print_precommit_hook: bool,
+    partial_stage_strategy: PartialStageStrategy,
+    missing_worktree_strategy: MissingWorktreeStrategy,
+    report_file: Option<PathBuf>,
+    assume_eol: Option<Eol>,
+    force_paths: Vec<PathBuf>,
+    skip_hooks: bool,
+    compare_with_origin: bool,
+    compare_worktree_to_index: bool,
+    fix: bool,
+    format: OutputFormat,
+    color: ColorChoice,
+    strict: bool,
+    log_file: Option<PathBuf>,
+    trailers: Vec<String>,
+    workdir_only: bool,
+    last_line_pattern: Option<Regex>,
+    emit_script: Option<PathBuf>,
+    group_by_author: bool,
+    pathspecs: Vec<String>,
+    /// Positional file arguments; with --n 0 only these paths are considered.
+    files: Vec<PathBuf>,
+    exclude_extensions: BTreeSet<String>,
+    include_globs: Vec<Glob>,
+    exclude_globs: Vec<Glob>,
+    max_total_bytes: Option<u64>,
+    max_blob_size: u64,
+    /// 0 with `--quiet`, 2 with `--verbose`, 1 otherwise.
+    verbosity: u8,
+    full_history: bool,
+    net: bool,
+    fix_intermediate: bool,
+    strip_list: Option<PathBuf>,
+    only_commits: Option<PathBuf>,
+    min_commit_depth: usize,
+    /// Threads scanning commits for `--n > 1`; 0 means one per CPU.
+    jobs: usize,
+    output_relative_to: Option<PathBuf>,
+    require_newline_globs: Vec<Glob>,
+    safe_mode: bool,
+    restore: bool,
+    include_added: bool,
+    include_binary: bool,
+    include_ignored: bool,
+    check_patch: Option<PathBuf>,
+    commit_order: CommitOrder,
+    engine: Engine,
+    install_hook: bool,
+    uninstall_hook: bool,
+    annotate_notes: bool,
+    emit_rebase_todo: Option<PathBuf>,
+    report_bytes_per_commit: bool,
+    resume: bool,
+    abort: bool,
     author_name: Option<String>,
     author_email: Option<String>,
+    author_name_regex: Option<Regex>,
+    author_email_regex: Option<Regex>,
+    committer_name: Option<String>,
+    committer_email: Option<String>,
+}
+
+/// Exit status for invalid arguments or option combinations (sysexits.h
+/// `EX_USAGE`).
+const EXIT_USAGE: u8 = 64;
+/// Exit status for failures while running, e.g. a git command failing
+/// (sysexits.h `EX_SOFTWARE`).
+const EXIT_RUNTIME: u8 = 70;
+/// Exit status for `--check` when at least one file needs fixing.
+const EXIT_FIXES_NEEDED: u8 = 1;
+
+#[derive(Debug)]
+enum Failure {
+    Usage(String),
+    Runtime(String),
+    /// `--check` found this many paths to fix.
+    FixesNeeded(usize),
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::Runtime(message)
+    }
 }
 
 fn main() -> ExitCode {
-    match run() {
-        Ok(()) => ExitCode::SUCCESS,
-        Err(message) => {
+    let code = match run() {
+        Ok(()) => 0,
+        Err(Failure::Usage(message)) => {
+            log(format!("error: {message}"));
             eprintln!("{message}");
-            ExitCode::FAILURE
+            EXIT_USAGE
         }
-    }
+        Err(Failure::Runtime(message)) => {
+            log(format!("error: {message}"));
+            eprintln!("{message}");
+            EXIT_RUNTIME
+        }
+        Err(Failure::FixesNeeded(count)) => {
+            log(format!("check: {count} path(s) need fixing"));
+            eprintln!("{count} path(s) need fixing");
+            EXIT_FIXES_NEEDED
+        }
+    };
+    log(format!("exit {code}"));
+    close_log();
+    ExitCode::from(code)
 }
 
-fn run() -> Result<(), String> {
-    let args = parse_args(std::env::args_os().collect())?;
-
-    ensure_in_git_worktree()?;
-
-    if args.in_filter_branch {
-        return run_filter_branch_step(&args);
-    }
+/// The `--log-file` sink, if one was given.
+static LOG_FILE: Mutex<Option<fs::File>> = Mutex::new(None);
 
-    match (args.n, args.in_rebase) {
-        (0, false) => run_n0(&args),
-        (0, true) => Err("--in-rebase cannot be used with --n 0".to_string()),
-        (1, _) => run_n1(&args),
-        (_, true) => Err("--in-rebase can only be used with --n 1".to_string()),
-        _ => run_n_gt1(&args),
+fn open_log(path: &Path) -> Result<(), String> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("failed to open log file {}: {e}", path.display()))?;
+    if let Ok(mut sink) = LOG_FILE.lock() {
+        *sink = Some(file);
     }
+    Ok(())
 }
 
-fn parse_args(argv: Vec<std::ffi::OsString>) -> Result<Args, String> {
-    let mut args = Args {
-        n: 1,
-        dry_run: false,
-        in_rebase: false,
-        in_filter_branch: false,
-        author_name: None,
-        author_email: None,
+/// Appends one `[<unix seconds>.<millis> pid <pid>] message` line to the
+/// log. Each line is written with a single `write`, so lines from the
+/// filter-branch children (which append to the same file) don't interleave.
+fn log(message: impl AsRef<str>) {
+    let Ok(mut sink) = LOG_FILE.lock() else {
+        return;
     };
+    let Some(file) = sink.as_mut() else {
+        return;
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let line = format!(
+        "[{}.{:03} pid {}] {}\n",
+        now.as_secs(),
+        now.subsec_millis(),
+        std::process::id(),
+        message.as_ref()
+    );
+    // Diagnostics must never break a run.
+    let _ = file.write_all(line.as_bytes());
+}
 
-    let _bin = argv.get(0).cloned();
-    let mut i = 1;
-    while i < argv.len() {
-        let a = argv[i].to_string_lossy().to_string();
-        match a.as_str() {
-            "--n" => {
-                let v = argv
-                    .get(i + 1)
-                    .ok_or_else(|| "--n requires an integer argument".to_string())?
-                    .to_string_lossy()
-                    .to_string();
-                args.n = v
-                    .parse::<usize>()
-                    .map_err(|_| format!("invalid --n value: {v}"))?;
-                i += 2;
-            }
-            "--dry-run" => {
-                args.dry_run = true;
-                i += 1;
-            }
-            "--in-rebase" => {
-                args.in_rebase = true;
-                i += 1;
-            }
-            "--in-filter-branch" => {
-                args.in_filter_branch = true;
-                i += 1;
-            }
-            "--author-name" => {
-                let v = argv
-                    .get(i + 1)
-                    .ok_or_else(|| "--author-name requires a value".to_string())?
-                    .to_string_lossy()
-                    .to_string();
-                args.author_name = Some(v);
-                i += 2;
-            }
-            "--author-email" => {
-                let v = argv
-                    .get(i + 1)
-                    .ok_or_else(|| "--author-email requires a value".to_string())?
-                    .to_string_lossy()
-                    .to_string();
-                args.author_email = Some(v);
-                i += 2;
-            }
-            "--help" | "-h" => {
-                return Err(usage());
-            }
-            other => {
-                return Err(format!("unknown argument: {other}\n\n{}", usage()));
-            }
-        }
+fn close_log() {
+    if let Ok(mut sink) = LOG_FILE.lock()
+        && let Some(mut file) = sink.take()
+    {
+        let _ = file.flush();
+        let _ = file.sync_all();
     }
+}
 
-    Ok(args)
+/// Prints a diagnostic to stderr and records it in the log.
+fn warn(message: String) {
+    log(&message);
+    if PROGRESS_SHOWN.load(Ordering::Relaxed) {
+        // Clear the progress line; the next update redraws it.
+        eprint!("\r\x1b[K");
+    }
+    eprintln!("{}", paint(&message, YELLOW, &COLOR_STDERR));
 }
 
-fn usage() -> String {
-    [
-        "Usage:",
-        "  git-fix-eof-newline [--n <int>] [--dry-run] [--author-name <substr>] [--author-email <substr>]",
-        "",
-        "Options:",
-        "  --n <int>           Check the last n commits (0 = uncommitted diff; default 1)",
-        "  --dry-run           Print what would change without modifying anything",
-        "  --in-filter-branch  Internal: run as git filter-branch tree-filter",
-        "  --author-name <s>   Only process commits whose author name contains s",
-        "  --author-email <s>  Only process commits whose author email contains s",
-    ]
-    .join("\n")
+/// When to color output (`--color`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
 }
 
-fn ensure_in_git_worktree() -> Result<(), String> {
-    let out = git_output(&["rev-parse", "--is-inside-work-tree"])?;
-    if out.trim() != "true" {
-        return Err("not inside a git worktree".to_string());
+impl ColorChoice {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            other => Err(format!(
+                "invalid --color value: {other} (expected auto, always or never)"
+            )),
+        }
+    }
+
+    /// Whether a stream that `is_terminal` (or not) gets colors. `auto`
+    /// follows https://no-color.org and leaves dumb terminals alone.
+    fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                is_terminal
+                    && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && std::env::var_os("TERM").is_none_or(|t| t != "dumb")
+            }
+        }
     }
-    Ok(())
 }
 
-fn git_output(args: &[&str]) -> Result<String, String> {
-    let out = Command::new("git")
-        .args(args)
-        .output()
-        .map_err(|e| format!("failed to run git: {e}"))?;
-    if !out.status.success() {
-        let stderr = String::from_utf8_lossy(&out.stderr);
-        return Err(format!("git {:?} failed: {}", args, stderr.trim()));
+/// Whether stdout/stderr are colored, for the output helpers that don't get
+/// the `Args`.
+static COLOR_STDOUT: AtomicBool = AtomicBool::new(false);
+static COLOR_STDERR: AtomicBool = AtomicBool::new(false);
+
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+
+/// `text` in the ANSI `color`, if `stream` is colored.
+fn paint(text: &str, color: &str, stream: &AtomicBool) -> String {
+    if stream.load(Ordering::Relaxed) {
+        format!("\x1b[{color}m{text}\x1b[0m")
+    } else {
+        text.to_string()
     }
-    String::from_utf8(out.stdout).map_err(|e| format!("git output was not valid UTF-8: {e}"))
 }
 
-fn git_output_bytes(args: &[&str]) -> Result<Vec<u8>, String> {
-    let out = Command::new("git")
-        .args(args)
-        .output()
-        .map_err(|e| format!("failed to run git: {e}"))?;
-    if !out.status.success() {
-        let stderr = String::from_utf8_lossy(&out.stderr);
-        return Err(format!("git {:?} failed: {}", args, stderr.trim()));
+/// `Args::verbosity`, for the output helpers that don't get the `Args`.
+static VERBOSITY: AtomicU8 = AtomicU8::new(1);
+
+/// Records a per-path decision in the log, and prints it to stderr with
+/// `--verbose`.
+fn trace(message: String) {
+    log(&message);
+    if VERBOSITY.load(Ordering::Relaxed) >= 2 {
+        eprintln!("{message}");
     }
-    Ok(out.stdout)
 }
 
-fn paths_from_zbytes(zbytes: &[u8]) -> Vec<PathBuf> {
-    zbytes
-        .split(|b| *b == 0u8)
-        .filter(|s| !s.is_empty())
-        .map(|s| PathBuf::from(String::from_utf8_lossy(s).to_string()))
-        .collect()
+/// Logs a finished git command and how it exited.
+fn log_git(args: &[&str], status: std::process::ExitStatus) {
+    let code = status
+        .code()
+        .map_or_else(|| "signal".to_string(), |c| c.to_string());
+    log(format!("git {}: exit {code}", args.join(" ")));
 }
 
-fn run_n0(args: &Args) -> Result<(), String> {
-    let unstaged = paths_from_zbytes(&git_output_bytes(&["diff", "--name-only", "-z"])?);
-    let staged = paths_from_zbytes(&git_output_bytes(&[
-        "diff",
-        "--cached",
-        "--name-only",
-        "-z",
-    ])?);
+fn run() -> Result<(), Failure> {
+    let argv: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let mut args = parse_args(argv.clone()).map_err(Failure::Usage)?;
 
-    let unstaged_set: BTreeSet<PathBuf> = unstaged.into_iter().collect();
-    let staged_set: BTreeSet<PathBuf> = staged.into_iter().collect();
+    if let Some(path) = &args.log_file {
+        open_log(path)?;
+        // The filter-branch children run elsewhere; hand them an absolute path.
+        if let Ok(cwd) = std::env::current_dir() {
+            args.log_file = Some(cwd.join(path));
+        }
+        let argv: Vec<String> = argv
+            .iter()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect();
+        log(format!("args: {}", argv.join(" ")));
+        match git_version() {
+            Ok((major, minor, patch)) => log(format!("git version {major}.{minor}.{patch}")),
+            Err(e) => log(format!("git version unknown: {e}")),
+        }
+    }
 
-    let partial: Vec<PathBuf> = unstaged_set.intersection(&staged_set).cloned().collect();
-    for p in partial {
-        eprintln!(
-            "skipping partially-staged file: {}",
-            p.as_os_str().to_string_lossy()
-        );
+    if args.help {
+        println!("{}", usage());
+        return Ok(());
     }
 
-    let mut handled_any = false;
+    MAX_BLOB_SIZE.store(args.max_blob_size, Ordering::Relaxed);
+    VERBOSITY.store(args.verbosity, Ordering::Relaxed);
+    {
+        use std::io::IsTerminal;
+        let stdout = args.color.enabled(std::io::stdout().is_terminal());
+        let stderr = args.color.enabled(std::io::stderr().is_terminal());
+        COLOR_STDOUT.store(stdout, Ordering::Relaxed);
+        COLOR_STDERR.store(stderr, Ordering::Relaxed);
+    }
 
-    for p in unstaged_set.difference(&staged_set) {
-        if fix_path_against_head(p, FixTarget::Worktree, args.dry_run)? {
-            handled_any = true;
-        }
+    if args.list_policies {
+        print!("{}", list_policies());
+        return Ok(());
     }
 
-    for p in staged_set.difference(&unstaged_set) {
-        if fix_path_against_head(p, FixTarget::Index, args.dry_run)? {
-            handled_any = true;
-        }
+    if args.verify_git_version {
+        return Ok(verify_git_version()?);
     }
 
-    if args.dry_run && handled_any {
+    if args.print_precommit_hook {
+        print!("{}", precommit_hook_entry());
         return Ok(());
     }
 
-    Ok(())
-}
+    if let Some(file) = &args.check_patch {
+        return Ok(run_check_patch(file)?);
+    }
 
-enum FixTarget {
-    Worktree,
-    Index,
-}
+    check_mode_combinations(&args).map_err(Failure::Usage)?;
 
-fn fix_path_against_head(path: &Path, target: FixTarget, dry_run: bool) -> Result<bool, String> {
-    let head_oid = rev_parse_oid(&format!("HEAD:{}", path.as_os_str().to_string_lossy()))?;
-    let old_bytes = blob_bytes_limited(&head_oid)?;
+    ensure_in_git_worktree()?;
 
-    let new_bytes = match target {
-        FixTarget::Worktree => match fs::read(path) {
-            Ok(b) => b,
-            Err(_) => return Ok(false),
-        },
-        FixTarget::Index => {
-            let idx_oid = rev_parse_oid(&format!(":{}", path.display()))?;
-            blob_bytes_limited(&idx_oid)?
+    if let Some(dir) = &args.output_relative_to {
+        let dir = fs::canonicalize(dir).map_err(|e| {
+            Failure::Usage(format!(
+                "invalid --output-relative-to directory {}: {e}",
+                dir.display()
+            ))
+        })?;
+        args.output_relative_to = Some(dir);
+    }
+
+    if !args.in_filter_branch {
+        enter_worktree_top(&mut args)?;
+    }
+
+    if args.install_hook {
+        return Ok(install_hook()?);
+    }
+    if args.uninstall_hook {
+        return Ok(uninstall_hook()?);
+    }
+
+    if args.resume || args.abort {
+        restore_interrupted_rewrite()?;
+        if args.abort {
+            return Ok(());
         }
-    };
+    }
 
-    if !added_eof_newline(&old_bytes, &new_bytes) {
-        return Ok(false);
+    if args.in_filter_branch {
+        return Ok(run_filter_branch_step(&args)?);
     }
 
-    if dry_run {
-        let label = match target {
-            FixTarget::Worktree => "worktree",
-            FixTarget::Index => "index",
-        };
-        println!(
-            "n=0 match ({label}): {}",
-            path.as_os_str().to_string_lossy()
-        );
-        return Ok(true);
+    // Every mode but `--n 0` reads history.
+    if (args.n >= 1 || args.net || args.check_message_eof || args.compare_with_origin)
+        && !args.in_rebase
+    {
+        require_git("scanning history", MIN_GIT_VERSION)?;
     }
 
-    match target {
-        FixTarget::Worktree => strip_worktree_file(path),
-        FixTarget::Index => {
-            strip_worktree_file(path)?;
-            git_add_path(path)?;
-            Ok(())
+    if args.check_message_eof {
+        return Ok(run_check_message_eof(&args)?);
+    }
+
+    if args.compare_with_origin {
+        run_compare_with_origin(&args)?;
+    } else if args.compare_worktree_to_index {
+        run_compare_worktree_to_index(&args)?;
+    } else if args.net {
+        with_autostash(&args, || run_net(&args))?;
+    } else {
+        if let Some(script) = &args.emit_script {
+            fs::write(script, SCRIPT_HEADER)
+                .map_err(|e| format!("failed to write script {}: {e}", script.display()))?;
         }
-    }?;
 
-    Ok(true)
-}
+        match args.n {
+            0 => run_n0(&args),
+            1 => with_autostash(&args, || run_n1(&args)),
+            _ => with_autostash(&args, || run_n_gt1(&args)),
+        }?;
 
-fn strip_worktree_file(path: &Path) -> Result<(), String> {
-    let mut bytes =
-        fs::read(path).map_err(|e| format!("failed to read file {}: {e}", path.display()))?;
-    if !strip_one_trailing_newline(&mut bytes) {
-        return Ok(());
+        if args.summary && args.verbosity > 0 {
+            let line = TALLY
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .line(args.dry_run, args.n >= 1);
+            log(&line);
+            eprintln!("{line}");
+        }
     }
-    fs::write(path, bytes).map_err(|e| format!("failed to write file {}: {e}", path.display()))?;
-    Ok(())
-}
 
-fn git_add_path(path: &Path) -> Result<(), String> {
-    let status = Command::new("git")
-        .args(["add", "--"])
-        .arg(path)
-        .status()
-        .map_err(|e| format!("failed to run git: {e}"))?;
-    if !status.success() {
-        return Err(format!("git add failed: {}", path.display()));
+    if args.format == OutputFormat::Json {
+        print_json_summary();
+    }
+    log(format!(
+        "blob reads: {} whole, {} ends only",
+        BLOBS_READ_WHOLE.load(Ordering::Relaxed),
+        BLOBS_READ_ENDS.load(Ordering::Relaxed)
+    ));
+    let matches = MATCHES_REPORTED.load(Ordering::Relaxed);
+    if args.check && matches > 0 {
+        return Err(Failure::FixesNeeded(matches));
     }
     Ok(())
 }
 
-fn run_n1(args: &Args) -> Result<(), String> {
-    if !args.in_rebase {
-        ensure_clean_worktree()?;
-    } else if args.n != 1 {
-        return Err("--in-rebase can only be used with --n 1".to_string());
+/// Rejects option combinations that make no sense for the selected mode.
+fn check_mode_combinations(args: &Args) -> Result<(), String> {
+    if args.resume && args.abort {
+        return Err("--resume cannot be combined with --abort".to_string());
     }
-
-    if !commit_matches_author_filter("HEAD", args)? {
-        return Ok(());
+    if args.resume && args.n <= 1 {
+        return Err("--resume requires --n > 1".to_string());
     }
 
-    let (head, parent) = head_and_first_parent()?;
-    let changed = changed_paths_in_commit(&head)?;
+    if args.restore {
+        let conflicting = [
+            (
+                !args.require_newline_globs.is_empty(),
+                "--require-eof-newline-for",
+            ),
+            (
+                args.last_line_pattern.is_some(),
+                "--strip-only-if-last-line-matches",
+            ),
+            (args.net, "--net"),
+            (args.check_message_eof, "--check-message-eof"),
+            (args.compare_with_origin, "--compare-with-origin"),
+            (
+                args.compare_worktree_to_index,
+                "--compare-working-tree-to-index",
+            ),
+        ];
+        if let Some((_, flag)) = conflicting.iter().find(|(set, _)| *set) {
+            return Err(format!("--restore cannot be combined with {flag}"));
+        }
+    }
 
-    let mut paths_to_fix: Vec<PathBuf> = Vec::new();
-    for path in changed {
-        let old_oid = match rev_parse_oid(&format!("{parent}:{}", path.display())) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-        let new_oid = match rev_parse_oid(&format!("{head}:{}", path.display())) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
+    if args.check {
+        let conflicting = [
+            (args.emit_script.is_some(), "--emit-script"),
+            (args.emit_rebase_todo.is_some(), "--emit-rebase-todo"),
+            (args.annotate_notes, "--annotate-notes"),
+            (args.fix, "--fix"),
+            (args.check_message_eof, "--check-message-eof"),
+            (args.report_bytes_per_commit, "--report-bytes-per-commit"),
+            (args.group_by_author, "--group-by-author"),
+            (args.in_filter_branch, "--in-filter-branch"),
+        ];
+        if let Some((_, flag)) = conflicting.iter().find(|(set, _)| *set) {
+            return Err(format!("--check cannot be combined with {flag}"));
+        }
+    }
 
-        let old_bytes = match blob_bytes_limited(&old_oid) {
-            Ok(b) => b,
-            Err(_) => continue,
-        };
-        let new_bytes = match blob_bytes_limited(&new_oid) {
-            Ok(b) => b,
-            Err(_) => continue,
-        };
+    if args.format == OutputFormat::Json {
+        let conflicting = [
+            (args.emit_script.is_some(), "--emit-script"),
+            (args.emit_rebase_todo.is_some(), "--emit-rebase-todo"),
+            (args.check_message_eof, "--check-message-eof"),
+        ];
+        if let Some((_, flag)) = conflicting.iter().find(|(set, _)| *set) {
+            return Err(format!("--format json cannot be combined with {flag}"));
+        }
+    }
 
-        if added_eof_newline(&old_bytes, &new_bytes) {
-            paths_to_fix.push(path);
+    if args.yes && !args.interactive {
+        return Err("--yes can only be used with --interactive".to_string());
+    }
+    if args.autostash && (args.n == 0 || args.in_rebase) {
+        return Err(
+            "--autostash requires --n >= 1 (and cannot be used with --in-rebase)".to_string(),
+        );
+    }
+    if args.backup_ref.is_some() && args.n == 0 {
+        return Err("--backup-ref requires --n >= 1".to_string());
+    }
+    if args.interactive {
+        if args.n == 0 {
+            return Err("--interactive requires --n >= 1".to_string());
+        }
+        if args.net {
+            return Err("--interactive cannot be combined with --net".to_string());
         }
     }
 
-    if paths_to_fix.is_empty() {
-        return Ok(());
+    if args.diff {
+        if !args.dry_run {
+            return Err("--diff can only be used with --dry-run".to_string());
+        }
+        let conflicting = [
+            (args.check, "--check"),
+            (args.format == OutputFormat::Github, "--format github"),
+            (args.format == OutputFormat::Json, "--format json"),
+            (args.emit_script.is_some(), "--emit-script"),
+            (args.emit_rebase_todo.is_some(), "--emit-rebase-todo"),
+            (args.net, "--net"),
+            (args.check_message_eof, "--check-message-eof"),
+            (args.compare_with_origin, "--compare-with-origin"),
+            (
+                args.compare_worktree_to_index,
+                "--compare-working-tree-to-index",
+            ),
+        ];
+        if let Some((_, flag)) = conflicting.iter().find(|(set, _)| *set) {
+            return Err(format!("--diff cannot be combined with {flag}"));
+        }
     }
 
-    for path in &paths_to_fix {
-        if args.dry_run {
-            println!("n=1 match: {}", path.display());
-            continue;
+    if args.engine != Engine::FilterBranch {
+        let engine = if args.engine == Engine::Rebase {
+            "rebase"
+        } else {
+            "filter-repo"
+        };
+        if args.n <= 1 {
+            return Err(format!("--engine {engine} requires --n > 1"));
+        }
+        let conflicting = [
+            (args.full_history, "--full-history"),
+            (args.annotate_notes, "--annotate-notes"),
+            (args.safe_mode, "--safe-mode"),
+            (args.net, "--net"),
+        ];
+        if let Some((_, flag)) = conflicting.iter().find(|(set, _)| *set) {
+            return Err(format!("--engine {engine} cannot be combined with {flag}"));
         }
-        strip_worktree_file(path)?;
-        git_add_path(path)?;
     }
 
-    if args.dry_run {
+    if args.in_filter_branch {
+        if args.n != 1 {
+            return Err("--in-filter-branch can only be used with --n 1".to_string());
+        }
         return Ok(());
     }
 
-    let status = Command::new("git")
-        .args(["commit", "--amend", "--no-edit", "--allow-empty"])
-        .status()
-        .map_err(|e| format!("failed to run git: {e}"))?;
-    if !status.success() {
-        return Err("git commit --amend failed".to_string());
+    if args.check_message_eof {
+        if args.n == 0 {
+            return Err("--check-message-eof requires --n >= 1".to_string());
+        }
+        return Ok(());
     }
 
-    Ok(())
-}
-
-fn run_filter_branch_step(args: &Args) -> Result<(), String> {
-    if args.n != 1 {
-        return Err("--in-filter-branch can only be used with --n 1".to_string());
+    if args.fix && !args.compare_worktree_to_index {
+        return Err("--fix can only be used with --compare-working-tree-to-index".to_string());
     }
-    let commit = filter_branch_commit();
 
-    if !commit_matches_author_filter(&commit, args)? {
+    if args.compare_with_origin || args.compare_worktree_to_index {
         return Ok(());
     }
-    let parent = first_parent_of_commit(&commit)?;
-    let changed = changed_paths_in_commit(&commit)?;
 
-    let mut changed_any = false;
-    for path in changed {
-        let old_oid = match rev_parse_oid(&format!("{parent}:{}", path.display())) {
-            Ok(v) => v,
+    if args.fix_intermediate && !args.net {
+        return Err("--fix-intermediate requires --net".to_string());
+    }
+
+    if args.net {
+        if args.n <= 1 {
+            return Err("--net requires --n > 1".to_string());
+        }
+        if args.fix_intermediate && args.full_history {
+            return Err("--fix-intermediate cannot be combined with --full-history".to_string());
+        }
+        if args.min_commit_depth > 0 {
+            return Err("--min-commit-depth cannot be combined with --net".to_string());
+        }
+        return Ok(());
+    }
+
+    if !args.trailers.is_empty() && args.n != 1 {
+        return Err("--add-trailer can only be used with --n 1".to_string());
+    }
+
+    if !args.force_paths.is_empty() && args.n != 0 {
+        return Err("--force-path can only be used with --n 0".to_string());
+    }
+    if args.include_ignored && args.n != 0 {
+        return Err("--include-ignored can only be used with --n 0".to_string());
+    }
+    if !args.files.is_empty() && args.n != 0 {
+        return Err(format!(
+            "file arguments can only be used with --n 0 (got {}; use --pathspec or -- to limit other modes)",
+            args.files[0].display()
+        ));
+    }
+
+    if args.min_commit_depth > 0 && args.n <= 1 {
+        return Err("--min-commit-depth can only be used with --n > 1".to_string());
+    }
+    if args.jobs > 0 && args.n <= 1 {
+        return Err("--jobs can only be used with --n > 1".to_string());
+    }
+
+    if (args.since.is_some() || args.until.is_some()) && args.n <= 1 {
+        return Err("--since/--until require --n > 1 or --range".to_string());
+    }
+
+    if args.include_added && args.n == 0 {
+        return Err("--include-added requires --n >= 1".to_string());
+    }
+
+    if args.report_bytes_per_commit && args.n == 0 {
+        return Err("--report-bytes-per-commit requires --n >= 1".to_string());
+    }
+
+    if args.group_by_author && args.n <= 1 {
+        return Err("--group-by-author can only be used with --n > 1".to_string());
+    }
+
+    if args.emit_rebase_todo.is_some() {
+        if args.n <= 1 {
+            return Err("--emit-rebase-todo can only be used with --n > 1".to_string());
+        }
+        if args.full_history {
+            return Err("--emit-rebase-todo cannot be combined with --full-history".to_string());
+        }
+    }
+
+    if args.annotate_notes {
+        if args.n == 0 {
+            return Err("--annotate-notes requires --n >= 1".to_string());
+        }
+        if args.emit_script.is_some() {
+            return Err("--annotate-notes cannot be combined with --emit-script".to_string());
+        }
+    }
+
+    if args.emit_script.is_some() {
+        if args.n > 1 {
+            return Err("--emit-script can only be used with --n 0 or --n 1".to_string());
+        }
+        if !args.trailers.is_empty() {
+            return Err("--emit-script cannot be combined with --add-trailer".to_string());
+        }
+    }
+
+    match (args.n, args.in_rebase) {
+        (0, true) => Err("--in-rebase cannot be used with --n 0".to_string()),
+        (n, true) if n > 1 => Err("--in-rebase can only be used with --n 1".to_string()),
+        _ => Ok(()),
+    }
+}
+
+fn parse_args(argv: Vec<std::ffi::OsString>) -> Result<Args, String> {
+    parse_args_with_config(argv, git_config_get)
+}
+
+/// `parse_args`, with `config` looking up the `fixeof.*` defaults (so tests
+/// do not depend on the git config of whoever runs them).
+fn parse_args_with_config(
+    argv: Vec<std::ffi::OsString>,
+    config: impl Fn(&str) -> Option<String>,
+) -> Result<Args, String> {
+    let mut args = Args {
+        help: false,
+        n: 1,
+        range: None,
+        since: None,
+        until: None,
+        dry_run: false,
+        diff: false,
+        summary: false,
+        interactive: false,
+        yes: false,
+        backup_ref: None,
+        autostash: false,
+        check: false,
+        in_rebase: false,
+        in_filter_branch: false,
+        check_message_eof: false,
+        index_requires_matching_worktree: false,
+        list_policies: false,
+        verify_git_version: false,
+        print_precommit_hook: false,
+        partial_stage_strategy: PartialStageStrategy::Skip,
+        missing_worktree_strategy: MissingWorktreeStrategy::Index,
+        report_file: None,
+        assume_eol: None,
+        force_paths: Vec::new(),
+        skip_hooks: false,
+        compare_with_origin: false,
+        compare_worktree_to_index: false,
+        fix: false,
+        format: OutputFormat::Text,
+        color: ColorChoice::Auto,
+        strict: false,
+        log_file: None,
+        trailers: Vec::new(),
+        workdir_only: false,
+        last_line_pattern: None,
+        emit_script: None,
+        group_by_author: false,
+        pathspecs: Vec::new(),
+        files: Vec::new(),
+        exclude_extensions: BTreeSet::new(),
+        include_globs: Vec::new(),
+        exclude_globs: Vec::new(),
+        max_total_bytes: None,
+        max_blob_size: DEFAULT_MAX_BLOB_SIZE,
+        verbosity: 1,
+        full_history: false,
+        net: false,
+        fix_intermediate: false,
+        strip_list: None,
+        only_commits: None,
+        min_commit_depth: 0,
+        jobs: 0,
+        output_relative_to: None,
+        require_newline_globs: Vec::new(),
+        safe_mode: false,
+        restore: false,
+        include_added: false,
+        include_binary: false,
+        include_ignored: false,
+        check_patch: None,
+        commit_order: CommitOrder::NewestFirst,
+        engine: Engine::FilterBranch,
+        install_hook: false,
+        uninstall_hook: false,
+        annotate_notes: false,
+        emit_rebase_todo: None,
+        report_bytes_per_commit: false,
+        resume: false,
+        abort: false,
+        author_name: None,
+        author_email: None,
+        author_name_regex: None,
+        author_email_regex: None,
+        committer_name: None,
+        committer_email: None,
+    };
+
+    let _bin = argv.first().cloned();
+    let mut n_explicit = false;
+    let mut max_blob_size_explicit = false;
+    let mut i = 1;
+    while i < argv.len() {
+        let a = argv[i].to_string_lossy().to_string();
+        match a.as_str() {
+            "--n" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--n requires an integer argument".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.n = v
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid --n value: {v}"))?;
+                n_explicit = true;
+                i += 2;
+            }
+            "--range" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--range requires a revision range".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.range = Some(v);
+                i += 2;
+            }
+            "--since" | "--until" => {
+                let flag = argv[i].to_string_lossy().to_string();
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| format!("{flag} requires a date"))?
+                    .to_string_lossy()
+                    .to_string();
+                if flag == "--since" {
+                    args.since = Some(v);
+                } else {
+                    args.until = Some(v);
+                }
+                i += 2;
+            }
+            "--workdir-only" => {
+                args.workdir_only = true;
+                i += 1;
+            }
+            "--dry-run" => {
+                args.dry_run = true;
+                i += 1;
+            }
+            "--diff" => {
+                args.diff = true;
+                i += 1;
+            }
+            "--summary" => {
+                args.summary = true;
+                i += 1;
+            }
+            "--interactive" => {
+                args.interactive = true;
+                i += 1;
+            }
+            "--yes" => {
+                args.yes = true;
+                i += 1;
+            }
+            "--autostash" => {
+                args.autostash = true;
+                i += 1;
+            }
+            "--backup-ref" => {
+                // The name is optional, so only a next argument that is not
+                // an option is taken as one.
+                let name = argv
+                    .get(i + 1)
+                    .map(|v| v.to_string_lossy().to_string())
+                    .filter(|v| !v.starts_with('-'));
+                i += if name.is_some() { 2 } else { 1 };
+                args.backup_ref = Some(backup_ref_name(name));
+            }
+            "--check" => {
+                args.check = true;
+                args.dry_run = true;
+                i += 1;
+            }
+            "--in-rebase" => {
+                args.in_rebase = true;
+                i += 1;
+            }
+            "--in-filter-branch" => {
+                args.in_filter_branch = true;
+                i += 1;
+            }
+            "--check-message-eof" => {
+                args.check_message_eof = true;
+                i += 1;
+            }
+            "--apply-to-index-only-when-matching-worktree" => {
+                args.index_requires_matching_worktree = true;
+                i += 1;
+            }
+            "--list-policies" => {
+                args.list_policies = true;
+                i += 1;
+            }
+            "--verify-git-version" => {
+                args.verify_git_version = true;
+                i += 1;
+            }
+            "--report-file" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--report-file requires a path".to_string())?;
+                args.report_file = Some(PathBuf::from(v));
+                i += 2;
+            }
+            "--assume-lf" | "--assume-crlf" => {
+                let eol = if a == "--assume-lf" {
+                    Eol::Lf
+                } else {
+                    Eol::CrLf
+                };
+                if args.assume_eol.is_some_and(|prev| prev != eol) {
+                    return Err("--assume-lf and --assume-crlf are mutually exclusive".to_string());
+                }
+                args.assume_eol = Some(eol);
+                i += 1;
+            }
+            "--force-path" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--force-path requires a path".to_string())?;
+                args.force_paths.push(PathBuf::from(v));
+                i += 2;
+            }
+            "--compare-working-tree-to-index" => {
+                args.compare_worktree_to_index = true;
+                i += 1;
+            }
+            "--fix" => {
+                args.fix = true;
+                i += 1;
+            }
+            "--compare-with-origin" => {
+                args.compare_with_origin = true;
+                i += 1;
+            }
+            "--add-trailer" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--add-trailer requires a <key>=<value> argument".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                match v.split_once('=') {
+                    Some((key, _)) if !key.trim().is_empty() => args.trailers.push(v),
+                    _ => {
+                        return Err(format!(
+                            "invalid --add-trailer value (expected <key>=<value>): {v}"
+                        ));
+                    }
+                }
+                i += 2;
+            }
+            "--strip-only-if-last-line-matches" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| {
+                        "--strip-only-if-last-line-matches requires a regex".to_string()
+                    })?
+                    .to_string_lossy()
+                    .to_string();
+                args.last_line_pattern = Some(
+                    Regex::new(&v)
+                        .map_err(|e| format!("--strip-only-if-last-line-matches: {e}"))?,
+                );
+                i += 2;
+            }
+            "--emit-script" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--emit-script requires a path".to_string())?;
+                args.emit_script = Some(PathBuf::from(v));
+                i += 2;
+            }
+            "--pathspec" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--pathspec requires a pathspec".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.pathspecs.push(v);
+                i += 2;
+            }
+            "--exclude-extension" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--exclude-extension requires a list of extensions".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                for ext in v.split(',') {
+                    let ext = ext.trim().trim_start_matches('.');
+                    if ext.is_empty() {
+                        return Err(format!("invalid --exclude-extension value: {v}"));
+                    }
+                    args.exclude_extensions.insert(ext.to_ascii_lowercase());
+                }
+                i += 2;
+            }
+            "--max-blob-size" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--max-blob-size requires a size".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.max_blob_size =
+                    parse_size(&v).ok_or_else(|| format!("invalid --max-blob-size value: {v}"))?;
+                max_blob_size_explicit = true;
+                i += 2;
+            }
+            "--max-total-bytes" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--max-total-bytes requires a size".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.max_total_bytes = Some(
+                    parse_size(&v)
+                        .ok_or_else(|| format!("invalid --max-total-bytes value: {v}"))?,
+                );
+                i += 2;
+            }
+            "--full-history" => {
+                args.full_history = true;
+                i += 1;
+            }
+            "--net" => {
+                args.net = true;
+                i += 1;
+            }
+            "--fix-intermediate" => {
+                args.fix_intermediate = true;
+                i += 1;
+            }
+            "--strip-list" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--strip-list requires a path".to_string())?;
+                args.strip_list = Some(PathBuf::from(v));
+                i += 2;
+            }
+            "--only-commits" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--only-commits requires a path".to_string())?;
+                args.only_commits = Some(PathBuf::from(v));
+                i += 2;
+            }
+            "--min-commit-depth" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--min-commit-depth requires an integer argument".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.min_commit_depth = v
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid --min-commit-depth value: {v}"))?;
+                i += 2;
+            }
+            "--jobs" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--jobs requires an integer argument".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.jobs = v
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("invalid --jobs value: {v}"))?;
+                i += 2;
+            }
+            "--output-relative-to" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--output-relative-to requires a directory".to_string())?;
+                args.output_relative_to = Some(PathBuf::from(v));
+                i += 2;
+            }
+            "--include" | "--exclude" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| format!("{} requires a glob", argv[i].to_string_lossy()))?
+                    .to_string_lossy()
+                    .to_string();
+                let glob = Glob::new(&v)?;
+                if argv[i] == "--include" {
+                    args.include_globs.push(glob);
+                } else {
+                    args.exclude_globs.push(glob);
+                }
+                i += 2;
+            }
+            "--require-eof-newline-for" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--require-eof-newline-for requires a glob".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.require_newline_globs.push(Glob::new(&v)?);
+                i += 2;
+            }
+            "--safe-mode" => {
+                args.safe_mode = true;
+                i += 1;
+            }
+            "--restore" => {
+                args.restore = true;
+                i += 1;
+            }
+            "--include-added" => {
+                args.include_added = true;
+                i += 1;
+            }
+            "--include-binary" => {
+                args.include_binary = true;
+                i += 1;
+            }
+            "--include-ignored" => {
+                args.include_ignored = true;
+                i += 1;
+            }
+            "--emit-rebase-todo" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--emit-rebase-todo requires a path".to_string())?;
+                args.emit_rebase_todo = Some(PathBuf::from(v));
+                i += 2;
+            }
+            "--resume" => {
+                args.resume = true;
+                i += 1;
+            }
+            "--abort" => {
+                args.abort = true;
+                i += 1;
+            }
+            "--annotate-notes" => {
+                args.annotate_notes = true;
+                i += 1;
+            }
+            "--install-hook" => {
+                args.install_hook = true;
+                i += 1;
+            }
+            // `install-hook`/`uninstall-hook` are subcommands, so they are
+            // only recognized as the first argument.
+            "install-hook" if i == 1 => {
+                args.install_hook = true;
+                i += 1;
+            }
+            "uninstall-hook" if i == 1 => {
+                args.uninstall_hook = true;
+                i += 1;
+            }
+            "print-precommit-hook" if i == 1 => {
+                args.print_precommit_hook = true;
+                i += 1;
+            }
+            "--check-patch" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--check-patch requires a file (or - for stdin)".to_string())?;
+                args.check_patch = Some(PathBuf::from(v));
+                i += 2;
+            }
+            "--report-bytes-per-commit" => {
+                args.report_bytes_per_commit = true;
+                i += 1;
+            }
+            "--group-by-author" => {
+                args.group_by_author = true;
+                i += 1;
+            }
+            "--skip-hooks" => {
+                args.skip_hooks = true;
+                i += 1;
+            }
+            "--format" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--format requires a value".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.format = OutputFormat::parse(&v)?;
+                i += 2;
+            }
+            "--color" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--color requires a value".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.color = ColorChoice::parse(&v)?;
+                i += 2;
+            }
+            "--json" => {
+                args.format = OutputFormat::Json;
+                i += 1;
+            }
+            "--quiet" => {
+                args.verbosity = 0;
+                i += 1;
+            }
+            "--verbose" => {
+                args.verbosity = 2;
+                i += 1;
+            }
+            "--log-file" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--log-file requires a path".to_string())?;
+                args.log_file = Some(PathBuf::from(v));
+                i += 2;
+            }
+            "--strict" => {
+                args.strict = true;
+                i += 1;
+            }
+            "--engine" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--engine requires a value".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.engine = Engine::parse(&v)?;
+                i += 2;
+            }
+            "--commit-order" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--commit-order requires a value".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.commit_order = CommitOrder::parse(&v)?;
+                i += 2;
+            }
+            "--missing-worktree-strategy" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--missing-worktree-strategy requires a value".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.missing_worktree_strategy = MissingWorktreeStrategy::parse(&v)?;
+                i += 2;
+            }
+            "--partial-stage-strategy" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--partial-stage-strategy requires a value".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.partial_stage_strategy = PartialStageStrategy::parse(&v)?;
+                i += 2;
+            }
+            "--include-partial" => {
+                args.partial_stage_strategy = PartialStageStrategy::Worktree;
+                i += 1;
+            }
+            "--author-name" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--author-name requires a value".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.author_name = Some(v);
+                i += 2;
+            }
+            "--author-email" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--author-email requires a value".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.author_email = Some(v);
+                i += 2;
+            }
+            "--author-name-regex" | "--author-email-regex" => {
+                let flag = argv[i].to_string_lossy().to_string();
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| format!("{flag} requires a regex"))?
+                    .to_string_lossy()
+                    .to_string();
+                let re = Some(Regex::new(&v).map_err(|e| format!("{flag}: {e}"))?);
+                if flag == "--author-name-regex" {
+                    args.author_name_regex = re;
+                } else {
+                    args.author_email_regex = re;
+                }
+                i += 2;
+            }
+            "--committer-name" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--committer-name requires a value".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.committer_name = Some(v);
+                i += 2;
+            }
+            "--committer-email" => {
+                let v = argv
+                    .get(i + 1)
+                    .ok_or_else(|| "--committer-email requires a value".to_string())?
+                    .to_string_lossy()
+                    .to_string();
+                args.committer_email = Some(v);
+                i += 2;
+            }
+            "--help" | "-h" => {
+                args.help = true;
+                return Ok(args);
+            }
+            "--" => {
+                // Like `git add -- <pathspec>...`: everything after `--` is a
+                // pathspec, even if it looks like an option.
+                args.pathspecs.extend(
+                    argv[i + 1..]
+                        .iter()
+                        .map(|v| v.to_string_lossy().to_string()),
+                );
+                i = argv.len();
+            }
+            // Anything else that is not an option is a file, as passed by
+            // the pre-commit framework's `{files}`.
+            other if !other.starts_with('-') => {
+                let file = other.strip_prefix("./").unwrap_or(other);
+                args.files.push(PathBuf::from(file));
+                i += 1;
+            }
+            other => {
+                return Err(format!("unknown argument: {other}\n\n{}", usage()));
+            }
+        }
+    }
+
+    if args.range.is_some() {
+        if n_explicit {
+            return Err("--range cannot be combined with --n".to_string());
+        }
+        if args.workdir_only {
+            return Err("--workdir-only cannot be combined with --range".to_string());
+        }
+        // History mode; the range decides which commits are checked.
+        args.n = usize::MAX;
+    }
+
+    // `git config fixeof.*` values fill in flags that were not given.
+    if !n_explicit
+        && !args.workdir_only
+        && args.range.is_none()
+        && let Some(v) = config("fixeof.n")
+    {
+        args.n = v
+            .parse::<usize>()
+            .map_err(|_| format!("invalid fixeof.n value in git config: {v}"))?;
+    }
+    if !max_blob_size_explicit && let Some(v) = config("fixeof.maxBlobSize") {
+        args.max_blob_size = parse_size(&v)
+            .ok_or_else(|| format!("invalid fixeof.maxBlobSize value in git config: {v}"))?;
+    }
+    if args.author_name.is_none() {
+        args.author_name = config("fixeof.authorName");
+    }
+    if args.author_email.is_none() {
+        args.author_email = config("fixeof.authorEmail");
+    }
+
+    if args.workdir_only {
+        if n_explicit && args.n >= 1 {
+            return Err(format!(
+                "--workdir-only cannot be combined with --n {} (it never rewrites commits)",
+                args.n
+            ));
+        }
+        args.n = 0;
+    }
+
+    Ok(args)
+}
+
+fn usage() -> String {
+    [
+        "Usage:",
+        "  git-fix-eof-newline [--n <int>] [--dry-run] [--author-name <substr>] [--author-email <substr>] [-- <pathspec>...]",
+        "  git-fix-eof-newline --check-message-eof [--n <int>] [--author-name <substr>] [--author-email <substr>]",
+        "  git-fix-eof-newline --n 0 [<option>...] <file>...",
+        "  git-fix-eof-newline install-hook | uninstall-hook | print-precommit-hook",
+        "",
+        "Options:",
+        "  --n <int>           Check the last n commits (0 = uncommitted diff; default 1)",
+        "  --range <a..b>      Check the commits in a git revision range instead of --n; like",
+        "                      --n > 1, matching commits are rewritten with filter-branch",
+        "  --since <date>      With --n > 1 or --range, skip commits older than date",
+        "  --until <date>      With --n > 1 or --range, skip commits newer than date",
+        "  --dry-run           Print what would change without modifying anything",
+        "  --diff              With --dry-run, also print each fix as a unified diff",
+        "  --summary           End with a line saying how many files were fixed and skipped",
+        "  --interactive       With --n >= 1, list the commits to rewrite and ask before doing it",
+        "  --yes               With --interactive, go ahead without asking",
+        "  --backup-ref [name] Before rewriting, save the original HEAD as refs/fix-eof-backup/<name>",
+        "                      (default: the current Unix time)",
+        "  --autostash         With --n >= 1, stash local changes before rewriting and restore",
+        "                      them afterwards (like git rebase --autostash)",
+        "  --check             Like --dry-run, but print only the matching paths (one per line)",
+        "                      and exit 1 if there are any",
+        "  --workdir-only      Only ever touch the worktree/index (implies --n 0; --n >= 1 is an error)",
+        "  --in-rebase         With --n 1, run from a git rebase exec step (skips the clean",
+        "                      worktree check)",
+        "  --in-filter-branch  Internal: run as git filter-branch tree-filter",
+        "  --report-file <p>   Internal: where --in-filter-branch records what it changed",
+        "  --strip-list <p>    Internal: commit/path pairs --in-filter-branch fixes unconditionally",
+        "  --only-commits <p>  Internal: the commits --in-filter-branch may modify",
+        "  --check-message-eof Report commits whose message ends with blank lines (no changes)",
+        "  --check-patch <file>",
+        "                      Report hunks of a unified diff (- for stdin) that add an EOF",
+        "                      newline (no changes; no repository needed)",
+        "  --compare-with-origin",
+        "                      Report files whose worktree copy added an EOF newline relative to",
+        "                      @{upstream} (no changes)",
+        "  --compare-working-tree-to-index",
+        "                      Report files whose worktree copy added an EOF newline relative to",
+        "                      the staged version (no changes unless --fix)",
+        "  --fix               With --compare-working-tree-to-index, strip the newline from the",
+        "                      worktree copy",
+        "  --apply-to-index-only-when-matching-worktree",
+        "                      With --n 0, only fix a staged file if the worktree still matches it",
+        "  --list-policies     List the available EOF transformations and exit",
+        "  --install-hook      Same as the install-hook subcommand",
+        "  --verify-git-version",
+        "                      Check that the installed git is new enough and exit",
+        "  --partial-stage-strategy <skip|worktree|index|both>",
+        "                      With --n 0, how to treat files with staged and unstaged changes",
+        "                      (default skip; worktree/index fix only that side; both fixes each)",
+        "  --include-partial   Same as --partial-stage-strategy worktree",
+        "  --missing-worktree-strategy <index|skip>",
+        "                      With --n 0, how to treat staged files deleted from the worktree",
+        "                      (default index: fix the staged blob only)",
+        "  --force-path <p>    With --n 0, check p against HEAD even if git reports no change",
+        "                      (repeatable; replaces the diff-derived path set)",
+        "  --add-trailer <k>=<v>",
+        "                      With --n 1, append a trailer to the amended commit message (repeatable)",
+        "  --emit-script <p>   With --n 0 or 1, write a shell script performing the fixes to p",
+        "                      instead of applying them",
+        "  --annotate-notes    With --n >= 1, record the fixed paths of each affected commit in",
+        "                      a git note under refs/notes/fix-eof",
+        "  --emit-rebase-todo <p>",
+        "                      With --n > 1, write a git rebase -i todo list to p that stops",
+        "                      at each matching commit instead of rewriting",
+        "  --engine <filter-branch|rebase|filter-repo>",
+        "                      How --n > 1 rewrites history: git filter-branch (default),",
+        "                      git rebase amending only the matching commits, or",
+        "                      git filter-repo (must be installed)",
+        "  --resume            Undo an interrupted --n > 1 rewrite, then run it again",
+        "  --abort             Undo an interrupted --n > 1 rewrite and exit",
+        "  --safe-mode         After fixing, check that exactly the fixed files changed; roll",
+        "                      back and fail otherwise",
+        "  --skip-hooks        Pass --no-verify when amending, skipping pre-commit/commit-msg hooks",
+        "  --strip-only-if-last-line-matches <regex>",
+        "                      Only strip when the last non-empty line matches regex",
+        "  --restore           Undo removed EOF newlines instead of stripping added ones:",
+        "                      re-append the terminator the old version ended with",
+        "  --include-added     With --n >= 1, also check files a commit added, as if their",
+        "                      previous content were empty (strips any final newline)",
+        "  --include-binary    Also fix files that look binary (a NUL byte in the first",
+        "                      8000 bytes); paths marked -text in .gitattributes stay skipped",
+        "  --include-ignored   With --n 0, also fix tracked files that .gitignore matches",
+        "  --require-eof-newline-for <glob>",
+        "                      Paths matching glob must keep their EOF newline: restore it",
+        "                      when a change removed it instead of stripping (repeatable)",
+        "  --assume-lf         Only strip a final \\n, keeping any \\r before it",
+        "  --assume-crlf       Only strip a final \\r\\n, leaving a bare \\n alone",
+        "  --pathspec <spec>   Only consider paths matching a git pathspec (repeatable; the",
+        "                      same as listing them after --)",
+        "  --exclude-extension <list>",
+        "                      Never touch files with these comma-separated extensions",
+        "                      (case-insensitive; repeatable; wins over every other filter)",
+        "  --include <glob>    Only touch paths matching glob (repeatable)",
+        "  --exclude <glob>    Never touch paths matching glob (repeatable; wins over --include)",
+        "  --max-blob-size <size>",
+        "                      Skip files whose blob is larger than size (suffixes K, M, G;",
+        "                      default 10000000)",
+        "  --max-total-bytes <size>",
+        "                      Stop once more than size bytes of blobs have been read",
+        "                      (suffixes K, M, G; --n >= 1 then changes nothing)",
+        "  --full-history      With --n > 1, scan the last n non-merge commits reachable",
+        "                      from HEAD instead of only the first-parent chain",
+        "  --net               With --n > 1, report files that gain an EOF newline and later lose",
+        "                      it again within the range (no changes)",
+        "  --fix-intermediate  With --net, rewrite the commits in between so the newline",
+        "                      never appears",
+        "  --min-commit-depth <k>",
+        "                      With --n > 1, leave the newest k commits' trees untouched and",
+        "                      only fix older ones",
+        "  --jobs <k>          With --n > 1, scan commits on k threads (default: one per CPU)",
+        "  --group-by-author   With --n > 1, summarize matches per author email",
+        "  --report-bytes-per-commit",
+        "                      With --n >= 1, print how many bytes each fixed commit loses",
+        "  --commit-order <newest-first|oldest-first>",
+        "                      Order of per-commit output (default newest-first, like git log)",
+        "  --format <text|github|json>",
+        "                      How matched paths are printed: plain lines (default), GitHub",
+        "                      Actions ::warning annotations, or one JSON object per path",
+        "  --json              Same as --format json",
+        "  --color <auto|always|never>",
+        "                      Show matches in green and warnings in yellow (default auto: only",
+        "                      on a terminal, and not if NO_COLOR is set)",
+        "  --quiet             Don't print skip warnings or plain-text match lines",
+        "  --verbose           Also print to stderr what was decided for every path considered",
+        "  --strict            With --format github, emit ::error instead of ::warning",
+        "  --output-relative-to <dir>",
+        "                      Print paths relative to dir (absolute if outside it)",
+        "  --log-file <p>      Append timestamped diagnostics (arguments, git commands and",
+        "                      their status, decisions) to p",
+        "  --author-name <s>   Only process commits whose author name contains s",
+        "  --author-email <s>  Only process commits whose author email contains s",
+        "  --author-name-regex <re>",
+        "                      Only process commits whose author name matches re (anchor",
+        "                      with ^ and $ to match the whole name)",
+        "  --author-email-regex <re>",
+        "                      Only process commits whose author email matches re",
+        "  --committer-name <s>",
+        "                      Only process commits whose committer name contains s",
+        "  --committer-email <s>",
+        "                      Only process commits whose committer email contains s",
+        "",
+        "Defaults for flags that are not given are read from git config: fixeof.n,",
+        "fixeof.maxBlobSize, fixeof.authorName and fixeof.authorEmail.",
+        "",
+        "Exit status:",
+        "  0   success",
+        "  1   --check found files that need fixing",
+        "  64  usage error (unknown option, bad value or invalid combination)",
+        "  70  runtime error (e.g. a git command failed)",
+    ]
+    .join("\n")
+}
+
+/// An EOF transformation the tool knows how to apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Policy {
+    StripOne,
+    RequireOne,
+}
+
+impl Policy {
+    const ALL: &'static [Policy] = &[Policy::StripOne, Policy::RequireOne];
+
+    fn name(self) -> &'static str {
+        match self {
+            Policy::StripOne => "strip-one",
+            Policy::RequireOne => "require-one",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Policy::StripOne => "remove one line terminator that a change added at EOF",
+            Policy::RequireOne => "restore the line terminator that a change removed at EOF",
+        }
+    }
+
+    /// Input used to demonstrate the policy in `--list-policies`.
+    fn example_input(self) -> &'static [u8] {
+        match self {
+            Policy::StripOne => b"a\n",
+            Policy::RequireOne => b"a",
+        }
+    }
+
+    /// Whether changing a file from `old` to `new` breaks this policy. Only
+    /// the last `EOF_TAIL_LEN` bytes of each side matter.
+    fn needs_fix(self, old: &[u8], new: &[u8]) -> bool {
+        match self {
+            Policy::StripOne => added_eof_newline(old, new),
+            Policy::RequireOne => removed_eof_newline(old, new),
+        }
+    }
+
+    /// The terminator style to fix with: `--assume-lf`/`--assume-crlf` if
+    /// given, otherwise `require-one` restores the one `old` ended with.
+    fn eol(self, args: &Args, old: &[u8]) -> Option<Eol> {
+        match self {
+            Policy::RequireOne if args.assume_eol.is_none() && old.ends_with(b"\r\n") => {
+                Some(Eol::CrLf)
+            }
+            _ => args.assume_eol,
+        }
+    }
+
+    /// The terminator fixing `new` with `eol` removes or restores, as
+    /// `--format json` names it.
+    fn terminator(self, eol: Option<Eol>, new: &[u8]) -> &'static str {
+        match (self, eol) {
+            (_, Some(Eol::CrLf)) => "crlf",
+            (Policy::StripOne, None) if new.ends_with(b"\r\n") => "crlf",
+            _ => "lf",
+        }
+    }
+
+    /// Fixes `bytes` in place; `eol` forces the terminator style
+    /// (`--assume-lf`/`--assume-crlf`).
+    fn apply(self, bytes: &mut Vec<u8>, eol: Option<Eol>) -> bool {
+        match (self, eol) {
+            (Policy::StripOne, Some(eol)) => strip_one_trailing_eol(bytes, eol),
+            (Policy::StripOne, None) => strip_one_trailing_newline(bytes),
+            (Policy::RequireOne, Some(Eol::CrLf)) => {
+                bytes.extend_from_slice(b"\r\n");
+                true
+            }
+            (Policy::RequireOne, _) => {
+                bytes.push(b'\n');
+                true
+            }
+        }
+    }
+}
+
+/// The policy `--restore`/`--require-eof-newline-for` assigns to `path`.
+fn policy_for(path: &Path, args: &Args) -> Policy {
+    let path = path.as_os_str().to_string_lossy();
+    if args.restore || args.require_newline_globs.iter().any(|g| g.is_match(&path)) {
+        Policy::RequireOne
+    } else {
+        Policy::StripOne
+    }
+}
+
+fn list_policies() -> String {
+    let mut out = String::new();
+    for policy in Policy::ALL {
+        let before = policy.example_input();
+        let mut after = before.to_vec();
+        policy.apply(&mut after, None);
+        out.push_str(&format!(
+            "{}: \"{}\" -> \"{}\"  ({})\n",
+            policy.name(),
+            escape_bytes(before),
+            escape_bytes(&after),
+            policy.description()
+        ));
+    }
+    out
+}
+
+/// `print-precommit-hook`: an entry for a `.pre-commit-hooks.yaml` (or a
+/// `repo: local` block), which hands the staged files to `--n 0`.
+fn precommit_hook_entry() -> String {
+    [
+        "- id: fix-eof-newline",
+        "  name: strip added EOF newlines",
+        "  entry: git-fix-eof-newline --n 0",
+        "  language: system",
+        "  types: [text]",
+        "  pass_filenames: true",
+        "",
+    ]
+    .join("\n")
+}
+
+fn escape_bytes(bytes: &[u8]) -> String {
+    bytes.escape_ascii().to_string()
+}
+
+/// Oldest git the history scans and rewrites work with: they keep their
+/// state under `rev-parse --git-path`, which arrived in 2.5.
+const MIN_GIT_VERSION: (u32, u32, u32) = (2, 5, 0);
+
+/// Features that need a newer git than `MIN_GIT_VERSION`, with the oldest
+/// version each works with. They are refused up front on an older git.
+const AUTOSTASH_GIT_VERSION: (u32, u32, u32) = (2, 13, 0);
+const FILTER_REPO_GIT_VERSION: (u32, u32, u32) = (2, 22, 0);
+const GIT_FEATURES: [(&str, (u32, u32, u32)); 2] = [
+    ("--autostash (git stash push)", AUTOSTASH_GIT_VERSION),
+    ("--engine filter-repo", FILTER_REPO_GIT_VERSION),
+];
+
+fn git_version() -> Result<(u32, u32, u32), String> {
+    let out = git_output(&["--version"])?;
+    parse_git_version(&out).ok_or_else(|| format!("failed to parse git version: {}", out.trim()))
+}
+
+/// Fails if the installed git is older than `min`, which `feature` needs.
+/// An unparseable version is let through: the git commands themselves
+/// still fail if they are missing.
+fn require_git(feature: &str, min: (u32, u32, u32)) -> Result<(), String> {
+    static VERSION: OnceLock<Option<(u32, u32, u32)>> = OnceLock::new();
+    let version = VERSION.get_or_init(|| match git_version() {
+        Ok(version) => Some(version),
+        Err(e) => {
+            trace(format!("not checking the git version: {e}"));
+            None
+        }
+    });
+    match version {
+        Some(version) => git_version_supports(*version, feature, min),
+        None => Ok(()),
+    }
+}
+
+fn git_version_supports(
+    version: (u32, u32, u32),
+    feature: &str,
+    min: (u32, u32, u32),
+) -> Result<(), String> {
+    if version < min {
+        return Err(format!(
+            "{feature} needs git {} or newer; found git {}",
+            format_git_version(min),
+            format_git_version(version)
+        ));
+    }
+    Ok(())
+}
+
+fn format_git_version((major, minor, patch): (u32, u32, u32)) -> String {
+    format!("{major}.{minor}.{patch}")
+}
+
+/// Parses `git --version` output such as `git version 2.39.2 (Apple Git-143)`
+/// or `git version 2.41.0.windows.1`. Missing components default to 0.
+fn parse_git_version(s: &str) -> Option<(u32, u32, u32)> {
+    let version = s.trim().strip_prefix("git version ")?;
+    let version = version.split_whitespace().next()?;
+    let mut parts = version.split(['.', '-']).map(|p| {
+        let digits: String = p.chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse::<u32>().ok()
+    });
+    let major = parts.next()??;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+fn verify_git_version() -> Result<(), String> {
+    let version = git_version()?;
+    let (found, min) = (
+        format_git_version(version),
+        format_git_version(MIN_GIT_VERSION),
+    );
+    if version < MIN_GIT_VERSION {
+        return Err(format!(
+            "git {found} is too old; {min} or newer is required"
+        ));
+    }
+    println!("git {found} (ok, requires >= {min})");
+    for (feature, min) in GIT_FEATURES {
+        let status = if version < min { "unavailable" } else { "ok" };
+        println!(
+            "  {feature}: {status} (requires >= {})",
+            format_git_version(min)
+        );
+    }
+    Ok(())
+}
+
+fn ensure_in_git_worktree() -> Result<(), String> {
+    let out = git_output(&["rev-parse", "--is-inside-work-tree"])?;
+    if out.trim() != "true" {
+        return Err("not inside a git worktree".to_string());
+    }
+    Ok(())
+}
+
+/// Moves to the top of the worktree, as git lists paths relative to it and
+/// the fixes read and write them from there. Paths given on the command
+/// line stay relative to the directory the tool was started in: files are
+/// made absolute, while repository paths and pathspecs get the
+/// subdirectory prefixed.
+fn enter_worktree_top(args: &mut Args) -> Result<(), String> {
+    let prefix = git_output(&["rev-parse", "--show-prefix"])?;
+    let prefix = prefix.trim_end_matches('\n');
+    if prefix.is_empty() {
+        return Ok(());
+    }
+    let cwd = std::env::current_dir()
+        .map_err(|e| format!("failed to read the current directory: {e}"))?;
+    for file in [
+        &mut args.emit_script,
+        &mut args.emit_rebase_todo,
+        &mut args.report_file,
+        &mut args.strip_list,
+        &mut args.only_commits,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if file.is_relative() {
+            *file = cwd.join(&*file);
+        }
+    }
+    for path in args.force_paths.iter_mut().chain(args.files.iter_mut()) {
+        if path.is_relative() {
+            *path = normalize_repo_path(&Path::new(prefix).join(&*path));
+        }
+    }
+    for spec in &mut args.pathspecs {
+        *spec = pathspec_under(prefix, spec);
+    }
+
+    let top = git_output(&["rev-parse", "--show-toplevel"])?;
+    let top = top.trim_end_matches('\n');
+    std::env::set_current_dir(top).map_err(|e| format!("failed to change to {top}: {e}"))
+}
+
+/// `path` with its `.` and `..` components resolved, without touching the
+/// filesystem, so it matches the paths git lists.
+fn normalize_repo_path(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// A pathspec given in the subdirectory `prefix` (ending in `/`), as git
+/// would read it there, rewritten to mean the same from the top of the
+/// worktree. Pathspecs with the `top` magic (`:/`) already do.
+fn pathspec_under(prefix: &str, spec: &str) -> String {
+    if let Some(long) = spec.strip_prefix(":(") {
+        let Some((magic, rest)) = long.split_once(')') else {
+            return spec.to_string();
+        };
+        if magic.split(',').any(|m| m == "top") {
+            return spec.to_string();
+        }
+        return format!(":({magic}){prefix}{rest}");
+    }
+    if let Some(short) = spec.strip_prefix(':') {
+        let magic_len = short
+            .find(|c: char| !"/!^".contains(c))
+            .unwrap_or(short.len());
+        let (magic, rest) = short.split_at(magic_len);
+        if magic.contains('/') {
+            return spec.to_string();
+        }
+        let rest = rest.strip_prefix(':').unwrap_or(rest);
+        return format!(":{magic}{prefix}{rest}");
+    }
+    format!("{prefix}{spec}")
+}
+
+/// Resolves `name` inside the repository's git directory, like
+/// `$GIT_DIR/<name>`. Unlike joining onto `.git`, this also works in linked
+/// worktrees and submodules (where `.git` is a file) and honors
+/// `core.hooksPath`. The result is absolute, so it stays valid for
+/// commands run from other directories (such as the filter-branch child).
+fn git_path(name: &str) -> Result<PathBuf, String> {
+    let out = git_output(&["rev-parse", "--git-path", name])?;
+    let path = PathBuf::from(out.trim_end_matches('\n'));
+    if path.is_absolute() {
+        return Ok(path);
+    }
+    let cwd = std::env::current_dir()
+        .map_err(|e| format!("failed to read the current directory: {e}"))?;
+    Ok(cwd.join(path))
+}
+
+/// First line of the hook older versions of `--install-hook` wrote; such a
+/// hook is replaced (or removed) as a whole.
+const HOOK_MARKER: &str = "# Installed by git-fix-eof-newline --install-hook";
+
+/// The lines `install-hook` wraps its invocation in, so it can find and
+/// replace (or remove) it inside a hook that also does other things.
+const HOOK_BLOCK_START: &str = "# >>> git-fix-eof-newline >>>";
+const HOOK_BLOCK_END: &str = "# <<< git-fix-eof-newline <<<";
+
+/// `install-hook`: adds a block that runs `--n 0` to the pre-commit hook,
+/// creating the hook if there is none. Reinstalling replaces the block in
+/// place, and the rest of an existing hook is kept.
+fn install_hook() -> Result<(), String> {
+    let hooks_dir = git_path("hooks")?;
+    let hook = hooks_dir.join("pre-commit");
+    let existing = match fs::read_to_string(&hook) {
+        Ok(existing) => Some(existing),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("failed to read {}: {e}", hook.display())),
+    };
+
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("failed to locate git-fix-eof-newline: {e}"))?;
+    let block = format!(
+        "{HOOK_BLOCK_START}\n{} --n 0 || exit $?\n{HOOK_BLOCK_END}\n",
+        sh_path(&exe)
+    );
+    let script = with_hook_block(existing.as_deref(), &block)
+        .map_err(|e| format!("refusing to change {}: {e}", hook.display()))?;
+    fs::create_dir_all(&hooks_dir)
+        .map_err(|e| format!("failed to create {}: {e}", hooks_dir.display()))?;
+    fs::write(&hook, script).map_err(|e| format!("failed to write {}: {e}", hook.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("failed to make {} executable: {e}", hook.display()))?;
+    }
+    println!("installed pre-commit hook: {}", hook.display());
+    Ok(())
+}
+
+/// `uninstall-hook`: removes the block `install-hook` added, and the hook
+/// itself if nothing but its `#!` line is left.
+fn uninstall_hook() -> Result<(), String> {
+    let hook = git_path("hooks")?.join("pre-commit");
+    let existing = match fs::read_to_string(&hook) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("no pre-commit hook installed: {}", hook.display());
+            return Ok(());
+        }
+        Err(e) => return Err(format!("failed to read {}: {e}", hook.display())),
+    };
+    match without_hook_block(&existing) {
+        None => println!(
+            "pre-commit hook does not run git-fix-eof-newline: {}",
+            hook.display()
+        ),
+        Some(rest)
+            if rest
+                .lines()
+                .all(|l| l.trim().is_empty() || l.starts_with("#!")) =>
+        {
+            fs::remove_file(&hook)
+                .map_err(|e| format!("failed to remove {}: {e}", hook.display()))?;
+            println!("removed pre-commit hook: {}", hook.display());
+        }
+        Some(rest) => {
+            fs::write(&hook, rest)
+                .map_err(|e| format!("failed to write {}: {e}", hook.display()))?;
+            println!(
+                "removed git-fix-eof-newline from pre-commit hook: {}",
+                hook.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The pre-commit hook with `block` in it: `existing` with its block
+/// replaced, or with `block` inserted right after the `#!` line so it runs
+/// before anything that might `exit` early.
+fn with_hook_block(existing: Option<&str>, block: &str) -> Result<String, String> {
+    let Some(existing) = existing.filter(|e| !e.contains(HOOK_MARKER)) else {
+        return Ok(format!("#!/bin/sh\n{block}"));
+    };
+    if let Some((before, after)) = split_hook_block(existing) {
+        return Ok(format!("{before}{block}{after}"));
+    }
+    let (shebang, rest) = match existing.strip_prefix("#!") {
+        Some(_) => existing.split_at(existing.find('\n').map_or(existing.len(), |i| i + 1)),
+        None => ("", existing),
+    };
+    let interpreter = shebang.split_whitespace().last().unwrap_or("sh");
+    if !interpreter.rsplit('/').next().unwrap_or("").ends_with("sh") {
+        return Err(format!(
+            "it is not a shell script ({}); add `git-fix-eof-newline --n 0` to it by hand",
+            shebang.trim_end()
+        ));
+    }
+    let newline = if shebang.is_empty() || shebang.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    Ok(format!("{shebang}{newline}{block}{rest}"))
+}
+
+/// `existing` without the block `install-hook` added, or `None` if it has
+/// none. A hook written by an older version is dropped entirely.
+fn without_hook_block(existing: &str) -> Option<String> {
+    if existing.contains(HOOK_MARKER) {
+        return Some(String::new());
+    }
+    split_hook_block(existing).map(|(before, after)| format!("{before}{after}"))
+}
+
+/// The parts of a hook before and after its `install-hook` block.
+fn split_hook_block(hook: &str) -> Option<(&str, &str)> {
+    let start = hook.find(HOOK_BLOCK_START)?;
+    let end = start + hook[start..].find(HOOK_BLOCK_END)? + HOOK_BLOCK_END.len();
+    let end = if hook[end..].starts_with('\n') {
+        end + 1
+    } else {
+        end
+    };
+    Some((&hook[..start], &hook[end..]))
+}
+
+/// The value of a git config key, or `None` if it is unset (or git fails,
+/// e.g. outside a repository with no global value).
+fn git_config_get(key: &str) -> Option<String> {
+    let value = git_output(&["config", "--get", key]).ok()?;
+    let value = value.trim_end_matches('\n');
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn git_output(args: &[&str]) -> Result<String, String> {
+    let out = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    log_git(args, out.status);
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        return Err(format!("git {:?} failed: {}", args, stderr.trim()));
+    }
+    String::from_utf8(out.stdout).map_err(|e| format!("git output was not valid UTF-8: {e}"))
+}
+
+fn git_output_bytes(args: &[&str]) -> Result<Vec<u8>, String> {
+    let out = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    log_git(args, out.status);
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        return Err(format!("git {:?} failed: {}", args, stderr.trim()));
+    }
+    Ok(out.stdout)
+}
+
+fn git_output_bytes_with_stdin(args: &[&str], input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = Command::new("git")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| "failed to open git stdin".to_string())?
+        .write_all(input)
+        .map_err(|e| format!("failed to write to git {:?}: {e}", args))?;
+    let out = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    log_git(args, out.status);
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        return Err(format!("git {:?} failed: {}", args, stderr.trim()));
+    }
+    Ok(out.stdout)
+}
+
+/// Which of `paths` a `.gitignore` (or `info/exclude`) matches. They are
+/// tracked, which `git check-ignore` would otherwise take as not ignored,
+/// so it runs with `--no-index`.
+fn ignored_paths<'a>(
+    paths: impl Iterator<Item = &'a PathBuf>,
+) -> Result<BTreeSet<PathBuf>, String> {
+    let mut input = Vec::new();
+    for path in paths {
+        input.extend_from_slice(path.as_os_str().as_encoded_bytes());
+        input.push(0);
+    }
+    if input.is_empty() {
+        return Ok(BTreeSet::new());
+    }
+    let args = ["check-ignore", "--no-index", "-z", "--stdin"];
+    let mut child = Command::new("git")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| "failed to open git stdin".to_string())?;
+    // Written from another thread so a long answer cannot block the input.
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let out = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    let written = writer.join().unwrap_or(Ok(()));
+    log_git(&args, out.status);
+    // Exit 1 just means that nothing is ignored.
+    if !matches!(out.status.code(), Some(0 | 1)) {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        return Err(format!("git {:?} failed: {}", args, stderr.trim()));
+    }
+    written.map_err(|e| format!("failed to write to git {:?}: {e}", args))?;
+    Ok(out
+        .stdout
+        .split(|b| *b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| PathBuf::from(String::from_utf8_lossy(p).to_string()))
+        .collect())
+}
+
+/// Appends `-- <pathspec>...` to a git command line so git itself limits
+/// the paths it lists.
+fn with_pathspecs<'a>(mut cmd: Vec<&'a str>, pathspecs: &'a [String]) -> Vec<&'a str> {
+    if !pathspecs.is_empty() {
+        cmd.push("--");
+        cmd.extend(pathspecs.iter().map(String::as_str));
+    }
+    cmd
+}
+
+/// How a repository path is shown to the user, honoring
+/// `--output-relative-to`. Filesystem operations keep using `path` itself.
+fn display_path(path: &Path, args: &Args) -> String {
+    let Some(dir) = &args.output_relative_to else {
+        return path.as_os_str().to_string_lossy().to_string();
+    };
+    let absolute = match std::env::current_dir().and_then(fs::canonicalize) {
+        Ok(cwd) => cwd.join(path),
+        Err(_) => return path.as_os_str().to_string_lossy().to_string(),
+    };
+    match absolute.strip_prefix(dir) {
+        Ok(relative) => relative.as_os_str().to_string_lossy().to_string(),
+        Err(_) => absolute.as_os_str().to_string_lossy().to_string(),
+    }
+}
+
+fn paths_from_zbytes(zbytes: &[u8]) -> Vec<PathBuf> {
+    zbytes
+        .split(|b| *b == 0u8)
+        .filter(|s| !s.is_empty())
+        .map(|s| PathBuf::from(String::from_utf8_lossy(s).to_string()))
+        .collect()
+}
+
+/// One entry of `git diff --name-status -z` output. `old` and `new` are the
+/// same path unless the status is a rename (`R`) or copy (`C`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DiffRecord {
+    status: char,
+    old: PathBuf,
+    new: PathBuf,
+}
+
+fn name_status_records(zbytes: &[u8]) -> Vec<DiffRecord> {
+    let mut fields = zbytes
+        .split(|b| *b == 0u8)
+        .filter(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).to_string());
+    let mut records = Vec::new();
+    while let Some(status) = fields.next() {
+        let Some(status) = status.chars().next() else {
+            continue;
+        };
+        let Some(old) = fields.next() else {
+            break;
+        };
+        let new = if matches!(status, 'R' | 'C') {
+            match fields.next() {
+                Some(new) => new,
+                None => break,
+            }
+        } else {
+            old.clone()
+        };
+        records.push(DiffRecord {
+            status,
+            old: PathBuf::from(old),
+            new: PathBuf::from(new),
+        });
+    }
+    records
+}
+
+/// Maps each path that exists on both sides of a diff to its old name.
+/// Added and deleted paths have nothing to compare and are left out.
+fn fixable_paths(records: &[DiffRecord]) -> BTreeMap<PathBuf, PathBuf> {
+    records
+        .iter()
+        .filter(|r| matches!(r.status, 'M' | 'R' | 'C'))
+        .map(|r| (r.new.clone(), r.old.clone()))
+        .collect()
+}
+
+fn run_n0(args: &Args) -> Result<(), String> {
+    // Drop stale stat info first so files that were only touched don't show
+    // up as modified.
+    git_output(&["update-index", "-q", "--refresh"])?;
+
+    // On an unborn branch every file is new, and new files are left alone.
+    if git_output(&["rev-parse", "--verify", "-q", "HEAD^{commit}"]).is_err() {
+        if args.verbosity > 0 {
+            warn("HEAD has no commits yet; there is nothing to compare against".to_string());
+        }
+        return Ok(());
+    }
+
+    // (path, HEAD-side path, what to fix)
+    let jobs: Vec<(PathBuf, PathBuf, FixTarget)> = if args.force_paths.is_empty() {
+        let jobs = n0_jobs_from_diff(args)?;
+        if args.include_ignored {
+            jobs
+        } else {
+            let ignored = ignored_paths(jobs.iter().map(|(p, _, _)| p))?;
+            jobs.into_iter()
+                .filter(|(p, _, _)| {
+                    let keep = !ignored.contains(p);
+                    if !keep {
+                        skip(
+                            "ignored",
+                            format!(
+                                "skipping ignored file (pass --include-ignored to fix it): {}",
+                                display_path(p, args)
+                            ),
+                        );
+                    }
+                    keep
+                })
+                .collect()
+        }
+    } else {
+        args.force_paths
+            .iter()
+            .map(|p| (p.clone(), p.clone(), FixTarget::Worktree))
+            .collect()
+    };
+    let jobs: Vec<_> = jobs
+        .into_iter()
+        .filter(|(p, _, _)| !excluded_path(p, args))
+        .collect();
+
+    let snapshot = if args.safe_mode && !args.dry_run && args.emit_script.is_none() {
+        Some(Snapshot::take()?)
+    } else {
+        None
+    };
+
+    let mut fixed: Vec<(PathBuf, FixTarget)> = Vec::new();
+    let total = jobs.len();
+    for (done, (p, head_path, target)) in jobs.into_iter().enumerate() {
+        if over_byte_budget(args, done, total, "paths") {
+            break;
+        }
+        if fix_path_against_head(&p, &head_path, target, args)? {
+            fixed.push((p, target));
+        }
+    }
+    // `--partial-stage-strategy both` fixes a path twice.
+    let files: BTreeSet<&PathBuf> = fixed.iter().map(|(p, _)| p).collect();
+    tally(|t| t.files += files.len());
+
+    match snapshot {
+        Some(before) => verify_n0_changes(&before, &fixed),
+        None => Ok(()),
+    }
+}
+
+fn n0_jobs_from_diff(args: &Args) -> Result<Vec<(PathBuf, PathBuf, FixTarget)>, String> {
+    let mut unstaged = fixable_paths(&name_status_records(&git_output_bytes(&with_pathspecs(
+        vec!["diff", "-M", "--name-status", "-z"],
+        &args.pathspecs,
+    ))?));
+    let mut staged = fixable_paths(&name_status_records(&git_output_bytes(&with_pathspecs(
+        vec!["diff", "--cached", "-M", "--name-status", "-z"],
+        &args.pathspecs,
+    ))?));
+    if !args.files.is_empty() {
+        // Narrowed before anything is classified, so the other changed
+        // files don't even show up as skipped.
+        let files = repo_relative_files(&args.files)?;
+        unstaged.retain(|p, _| files.contains(p));
+        staged.retain(|p, _| files.contains(p));
+        for file in &files {
+            if !unstaged.contains_key(file) && !staged.contains_key(file) {
+                trace(format!(
+                    "no uncommitted changes to fix: {}",
+                    display_path(file, args)
+                ));
+            }
+        }
+    }
+
+    let unstaged_set: BTreeSet<PathBuf> = unstaged.keys().cloned().collect();
+    let staged_set: BTreeSet<PathBuf> = staged.keys().cloned().collect();
+
+    // The `HEAD` side of a path: a staged rename names it, otherwise it is
+    // the path itself.
+    let job = |p: &PathBuf, target: FixTarget| {
+        let head_path = staged
+            .get(p)
+            .or_else(|| unstaged.get(p))
+            .cloned()
+            .unwrap_or_else(|| p.clone());
+        (p.clone(), head_path, target)
+    };
+
+    let partial: Vec<PathBuf> = unstaged_set.intersection(&staged_set).cloned().collect();
+
+    let mut jobs = Vec::new();
+    for p in &partial {
+        let strategy = args.partial_stage_strategy;
+        if strategy == PartialStageStrategy::Skip {
+            skip(
+                "partially staged",
+                format!(
+                    "skipping partially-staged file (pass --include-partial to fix its worktree copy): {}",
+                    display_path(p, args)
+                ),
+            );
+            continue;
+        }
+        if matches!(
+            strategy,
+            PartialStageStrategy::Worktree | PartialStageStrategy::Both
+        ) {
+            jobs.push(job(p, FixTarget::Worktree));
+        }
+        if matches!(
+            strategy,
+            PartialStageStrategy::Index | PartialStageStrategy::Both
+        ) {
+            jobs.push(job(p, FixTarget::IndexBlob));
+        }
+    }
+    for p in unstaged_set.difference(&staged_set) {
+        jobs.push(job(p, FixTarget::Worktree));
+    }
+    for p in staged_set.difference(&unstaged_set) {
+        // A deleted worktree file is an unstaged `D`, which `fixable_paths`
+        // drops, so it lands here; there is no worktree copy to fix.
+        if fs::symlink_metadata(p).is_err() {
+            match args.missing_worktree_strategy {
+                MissingWorktreeStrategy::Index => jobs.push(job(p, FixTarget::IndexBlob)),
+                MissingWorktreeStrategy::Skip => skip(
+                    "missing",
+                    format!(
+                        "skipping staged file missing from the worktree: {}",
+                        display_path(p, args)
+                    ),
+                ),
+            }
+            continue;
+        }
+        jobs.push(job(p, FixTarget::Index));
+    }
+    Ok(jobs)
+}
+
+/// File arguments as paths relative to the repository root, which is what
+/// `git diff` lists. Relative ones already are (the tool runs from the
+/// root); absolute ones, as editors pass them, have the root stripped.
+fn repo_relative_files(files: &[PathBuf]) -> Result<BTreeSet<PathBuf>, String> {
+    let mut root: Option<PathBuf> = None;
+    let mut relative = BTreeSet::new();
+    for file in files {
+        if file.is_relative() {
+            relative.insert(file.clone());
+            continue;
+        }
+        if root.is_none() {
+            let top = PathBuf::from(git_output(&["rev-parse", "--show-toplevel"])?.trim_end());
+            root = Some(fs::canonicalize(&top).unwrap_or(top));
+        }
+        let root = root.as_deref().unwrap_or(Path::new(""));
+        // Canonicalize the directory only, so a symlink named on the
+        // command line is still the path git knows it by.
+        let full = match (file.parent(), file.file_name()) {
+            (Some(dir), Some(name)) => fs::canonicalize(dir)
+                .map(|d| d.join(name))
+                .unwrap_or_else(|_| file.clone()),
+            _ => file.clone(),
+        };
+        let path = full.strip_prefix(root).map_err(|_| {
+            format!(
+                "{} is outside the repository ({})",
+                file.display(),
+                root.display()
+            )
+        })?;
+        relative.insert(path.to_path_buf());
+    }
+    Ok(relative)
+}
+
+/// The tracked worktree and index state as two trees, for `--safe-mode`.
+struct Snapshot {
+    worktree: String,
+    index: String,
+}
+
+impl Snapshot {
+    fn take() -> Result<Snapshot, String> {
+        // `git stash create` records both without touching anything, and
+        // prints nothing when there are no local changes.
+        let stash = git_output(&["stash", "create"])?;
+        let stash = stash.trim();
+        if stash.is_empty() {
+            let head = rev_parse_oid("HEAD^{tree}")?;
+            return Ok(Snapshot {
+                worktree: head.clone(),
+                index: head,
+            });
+        }
+        Ok(Snapshot {
+            worktree: rev_parse_oid(&format!("{stash}^{{tree}}"))?,
+            index: rev_parse_oid(&format!("{stash}^2^{{tree}}"))?,
+        })
+    }
+
+    /// Puts the worktree files in `paths` and the whole index back to this
+    /// snapshot.
+    fn restore(&self, paths: &BTreeSet<PathBuf>) -> Result<(), String> {
+        for path in paths {
+            let spec = format!("{}:{}", self.worktree, path.display());
+            if let Ok(bytes) = git_output_bytes(&["cat-file", "blob", &spec]) {
+                fs::write(path, bytes)
+                    .map_err(|e| format!("failed to restore {}: {e}", path.display()))?;
+            }
+        }
+        git_output(&["read-tree", &self.index])?;
+        git_output(&["update-index", "-q", "--refresh"]).map(|_| ())
+    }
+}
+
+/// Paths whose blobs differ between two trees or commits.
+fn changed_between_trees(old: &str, new: &str) -> Result<BTreeSet<PathBuf>, String> {
+    let out = git_output_bytes(&["diff-tree", "-r", "--name-only", "-z", old, new])?;
+    Ok(paths_from_zbytes(&out).into_iter().collect())
+}
+
+fn format_path_set(paths: &BTreeSet<PathBuf>) -> String {
+    let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+    format!("[{}]", paths.join(", "))
+}
+
+/// `--safe-mode` for `--n 0`: the worktree and index must have changed in
+/// exactly the fixed paths, otherwise everything is rolled back.
+fn verify_n0_changes(before: &Snapshot, fixed: &[(PathBuf, FixTarget)]) -> Result<(), String> {
+    let after = Snapshot::take()?;
+    let worktree_changed = changed_between_trees(&before.worktree, &after.worktree)?;
+    let index_changed = changed_between_trees(&before.index, &after.index)?;
+
+    let expected = |wanted: &[FixTarget]| -> BTreeSet<PathBuf> {
+        fixed
+            .iter()
+            .filter(|(_, target)| wanted.contains(target))
+            .map(|(p, _)| p.clone())
+            .collect()
+    };
+    let expected_worktree = expected(&[FixTarget::Worktree, FixTarget::Index]);
+    let expected_index = expected(&[FixTarget::Index, FixTarget::IndexBlob]);
+
+    if worktree_changed == expected_worktree && index_changed == expected_index {
+        return Ok(());
+    }
+    before.restore(&worktree_changed)?;
+    Err(format!(
+        "safe mode: modified files differ from the fixes (worktree: expected {}, got {}; index: expected {}, got {}); rolled back",
+        format_path_set(&expected_worktree),
+        format_path_set(&worktree_changed),
+        format_path_set(&expected_index),
+        format_path_set(&index_changed),
+    ))
+}
+
+/// Pairs each original commit in `base..old_head` (oldest first) with its
+/// rewrite in `base..HEAD`, or `None` if filter-branch pruned it.
+fn rewritten_commits(
+    args: &Args,
+    base: &str,
+    old_head: &str,
+) -> Result<Vec<(String, Option<String>)>, String> {
+    // filter-branch keeps authorship and messages, which pairs each
+    // original commit with its rewrite.
+    let identity =
+        |commit: &str| git_output_bytes(&["log", "-1", "--format=%an%x00%ae%x00%at%x00%B", commit]);
+    let walk = |tip: &str| -> Result<Vec<String>, String> {
+        let range = format!("{base}..{tip}");
+        let mut cmd = vec!["rev-list", "--reverse"];
+        if !args.full_history {
+            cmd.push("--first-parent");
+        }
+        cmd.push(&range);
+        Ok(git_output(&cmd)?.lines().map(str::to_string).collect())
+    };
+    let mut rewritten: BTreeMap<Vec<u8>, String> = BTreeMap::new();
+    for commit in walk("HEAD")? {
+        rewritten.insert(identity(&commit)?, commit);
+    }
+    walk(old_head)?
+        .into_iter()
+        .map(|original| {
+            let new = rewritten.get(&identity(&original)?).cloned();
+            Ok((original, new))
+        })
+        .collect()
+}
+
+/// `--safe-mode` for rewrites: `old_head..HEAD` must differ from the
+/// original commits in exactly the reported paths, otherwise `HEAD` is reset.
+fn verify_rewrite(
+    args: &Args,
+    base: &str,
+    old_head: &str,
+    report: &[(String, Vec<String>)],
+) -> Result<(), String> {
+    let mut mismatches: Vec<String> = Vec::new();
+    let mut previous = base.to_string();
+    for (original, new) in rewritten_commits(args, base, old_head)? {
+        let expected: BTreeSet<PathBuf> = report
+            .iter()
+            .find(|(c, _)| *c == original)
+            .map(|(_, paths)| paths.iter().map(PathBuf::from).collect())
+            .unwrap_or_default();
+        // A pruned commit must leave the tree as its predecessor had it.
+        let new = new.unwrap_or_else(|| previous.clone());
+        let actual = changed_between_trees(&original, &new)?;
+        if actual != expected {
+            mismatches.push(format!(
+                "{original}: expected {}, got {}",
+                format_path_set(&expected),
+                format_path_set(&actual)
+            ));
+        }
+        previous = new;
+    }
+
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    git_output(&["reset", "--hard", "-q", old_head])?;
+    Err(format!(
+        "safe mode: rewritten commits differ from the report ({}); reset HEAD back to {old_head}",
+        mismatches.join("; ")
+    ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PartialStageStrategy {
+    Skip,
+    Worktree,
+    Index,
+    Both,
+}
+
+impl PartialStageStrategy {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "worktree" => Ok(Self::Worktree),
+            "index" => Ok(Self::Index),
+            "both" => Ok(Self::Both),
+            other => Err(format!(
+                "invalid --partial-stage-strategy value: {other} (expected skip, worktree, index or both)"
+            )),
+        }
+    }
+}
+
+/// What `--n 0` does with a staged file whose worktree copy was deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MissingWorktreeStrategy {
+    /// Fix the staged blob without recreating the worktree file.
+    Index,
+    Skip,
+}
+
+impl MissingWorktreeStrategy {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "index" => Ok(Self::Index),
+            "skip" => Ok(Self::Skip),
+            other => Err(format!(
+                "invalid --missing-worktree-strategy value: {other} (expected index or skip)"
+            )),
+        }
+    }
+}
+
+/// How matched paths are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    /// GitHub Actions workflow commands (`::warning file=...::...`).
+    Github,
+    /// One JSON object per line, then a `{"changed":N,"skipped":M}` summary.
+    Json,
+}
+
+impl OutputFormat {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "text" => Ok(Self::Text),
+            "github" => Ok(Self::Github),
+            "json" => Ok(Self::Json),
+            other => Err(format!(
+                "invalid --format value: {other} (expected text, github or json)"
+            )),
+        }
+    }
+}
+
+/// A matched path, as `report_path` prints it.
+struct Finding<'a> {
+    path: &'a Path,
+    policy: Policy,
+    /// What the fix goes to: `worktree`, `index` or `commit`.
+    target: &'static str,
+    /// The terminator the fix removes or restores, when it is known.
+    terminator: Option<&'static str>,
+    /// Whether the fix was applied, rather than only reported.
+    applied: bool,
+}
+
+/// How many matches `report_path` has printed, for `--check` and the
+/// `--format json` summary.
+static MATCHES_REPORTED: AtomicUsize = AtomicUsize::new(0);
+/// How many paths were skipped with a warning, for the `--format json`
+/// summary.
+static PATHS_SKIPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Prints a finding: `line` in text mode, one annotation in the format
+/// GitHub Actions picks up, or one JSON object.
+fn report_path(args: &Args, finding: &Finding, line: String) {
+    let Finding { path, policy, .. } = *finding;
+    log(format!("match ({}): {}", policy.name(), path.display()));
+    MATCHES_REPORTED.fetch_add(1, Ordering::Relaxed);
+    match args.format {
+        // --check prints bare paths so the output can be piped as-is.
+        OutputFormat::Text if args.verbosity == 0 => {}
+        OutputFormat::Text if args.check => {
+            println!("{}", paint(&display_path(path, args), GREEN, &COLOR_STDOUT))
+        }
+        OutputFormat::Text => println!("{}", paint(&line, GREEN, &COLOR_STDOUT)),
+        OutputFormat::Github => {
+            let level = if args.strict { "error" } else { "warning" };
+            let message = match (policy, finding.applied) {
+                (Policy::StripOne, false) => "added EOF newline would be stripped",
+                (Policy::StripOne, true) => "added EOF newline was stripped",
+                (Policy::RequireOne, false) => "removed EOF newline would be restored",
+                (Policy::RequireOne, true) => "removed EOF newline was restored",
+            };
+            println!(
+                "::{level} file={}::{message}",
+                github_escape_property(&display_path(path, args))
+            );
+        }
+        OutputFormat::Json => {
+            let action = match (policy, finding.applied) {
+                (Policy::StripOne, true) => "strip",
+                (Policy::StripOne, false) => "would-strip",
+                (Policy::RequireOne, true) => "restore",
+                (Policy::RequireOne, false) => "would-restore",
+            };
+            println!(
+                "{{\"path\":{},\"target\":\"{}\",\"action\":\"{action}\",\"terminator\":{}}}",
+                json_string(&display_path(path, args)),
+                finding.target,
+                finding
+                    .terminator
+                    .map_or_else(|| "null".to_string(), |t| format!("\"{t}\""))
+            );
+        }
+    }
+}
+
+/// Warns that `message`'s path is skipped (unless `--quiet`) and counts it
+/// for the `--format json` summary and, under `reason`, for `--summary`.
+fn skip(reason: &'static str, message: String) {
+    PATHS_SKIPPED.fetch_add(1, Ordering::Relaxed);
+    tally_skip(reason, &message);
+    if VERBOSITY.load(Ordering::Relaxed) == 0 {
+        log(&message);
+    } else {
+        warn(message);
+    }
+}
+
+/// What a run did, for the `--summary` line.
+#[derive(Debug)]
+struct Tally {
+    /// Files fixed (or, in a dry run, that would be), counted once per
+    /// commit they are fixed in.
+    files: usize,
+    commits: usize,
+    /// Files compared that needed no fix (`--n 0`).
+    unchanged: usize,
+    /// Skip messages by reason. A commit can be scanned more than once, so
+    /// a repeated message is only counted once.
+    skipped: BTreeMap<&'static str, BTreeSet<String>>,
+}
+
+static TALLY: Mutex<Tally> = Mutex::new(Tally {
+    files: 0,
+    commits: 0,
+    unchanged: 0,
+    skipped: BTreeMap::new(),
+});
+
+fn tally(update: impl FnOnce(&mut Tally)) {
+    update(&mut TALLY.lock().unwrap_or_else(|e| e.into_inner()));
+}
+
+/// Counts a skip for `--summary`, for the places that only `trace` it.
+fn tally_skip(reason: &'static str, message: &str) {
+    tally(|t| {
+        t.skipped
+            .entry(reason)
+            .or_default()
+            .insert(message.to_string());
+    });
+}
+
+impl Tally {
+    /// `fixed 3 files across 2 commits (1 skipped: binary)`; `commits` says
+    /// whether the run fixes commits rather than the worktree/index.
+    fn line(&self, dry_run: bool, commits: bool) -> String {
+        let plural = |n: usize, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+        let mut line = format!(
+            "{} {}",
+            if dry_run { "would fix" } else { "fixed" },
+            plural(self.files, "file")
+        );
+        if commits {
+            line.push_str(&format!(" across {}", plural(self.commits, "commit")));
+        }
+        let mut notes = Vec::new();
+        if self.unchanged > 0 {
+            notes.push(format!("{} unchanged", self.unchanged));
+        }
+        let skipped: usize = self.skipped.values().map(BTreeSet::len).sum();
+        if skipped > 0 {
+            let reasons: Vec<String> = if self.skipped.len() == 1 {
+                self.skipped.keys().map(|r| r.to_string()).collect()
+            } else {
+                self.skipped
+                    .iter()
+                    .map(|(reason, paths)| format!("{} {reason}", paths.len()))
+                    .collect()
+            };
+            notes.push(format!("{skipped} skipped: {}", reasons.join(", ")));
+        }
+        if !notes.is_empty() {
+            line.push_str(&format!(" ({})", notes.join(", ")));
+        }
+        line
+    }
+}
+
+/// Prints the `--format json` summary line.
+fn print_json_summary() {
+    println!(
+        "{{\"changed\":{},\"skipped\":{}}}",
+        MATCHES_REPORTED.load(Ordering::Relaxed),
+        PATHS_SKIPPED.load(Ordering::Relaxed)
+    );
+}
+
+/// Quotes `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Escapes a workflow command property value such as `file=`.
+fn github_escape_property(s: &str) -> String {
+    let mut out = String::new();
+    for ch in s.chars() {
+        match ch {
+            '%' => out.push_str("%25"),
+            '\r' => out.push_str("%0D"),
+            '\n' => out.push_str("%0A"),
+            ':' => out.push_str("%3A"),
+            ',' => out.push_str("%2C"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// How `--n > 1` rewrites the matching commits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Engine {
+    /// `git filter-branch --tree-filter` over `base..HEAD`.
+    FilterBranch,
+    /// `git rebase -i` with an `exec` after each matching commit only.
+    Rebase,
+    /// `git filter-repo` with a commit callback that swaps in fixed blobs.
+    FilterRepo,
+}
+
+impl Engine {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "filter-branch" => Ok(Self::FilterBranch),
+            "rebase" => Ok(Self::Rebase),
+            "filter-repo" => Ok(Self::FilterRepo),
+            other => Err(format!(
+                "invalid --engine value: {other} (expected filter-branch, rebase or filter-repo)"
+            )),
+        }
+    }
+}
+
+/// Order in which per-commit results are printed. Commits are always
+/// processed oldest-first; this only affects the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommitOrder {
+    NewestFirst,
+    OldestFirst,
+}
+
+impl CommitOrder {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "newest-first" => Ok(Self::NewestFirst),
+            "oldest-first" => Ok(Self::OldestFirst),
+            other => Err(format!(
+                "invalid --commit-order value: {other} (expected newest-first or oldest-first)"
+            )),
+        }
+    }
+
+    /// Puts `items`, given oldest-first, into display order.
+    fn arrange<T>(self, mut items: Vec<T>) -> Vec<T> {
+        if self == Self::NewestFirst {
+            items.reverse();
+        }
+        items
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FixTarget {
+    /// The worktree file only.
+    Worktree,
+    /// A staged-only file: fix the worktree copy and restage it.
+    Index,
+    /// The staged blob only, leaving the worktree untouched.
+    IndexBlob,
+}
+
+/// Fixes `path` if it added an EOF newline relative to `HEAD:<head_path>`
+/// (`head_path` differs from `path` only for renames and copies).
+fn fix_path_against_head(
+    path: &Path,
+    head_path: &Path,
+    target: FixTarget,
+    args: &Args,
+) -> Result<bool, String> {
+    if is_worktree_symlink(path) {
+        skip(
+            "symlink",
+            format!("skipping symlink: {}", display_path(path, args)),
+        );
+        return Ok(false);
+    }
+    // A `--force-path` may name a file `HEAD` does not have; like a file
+    // missing from the worktree, it is skipped rather than failing the run.
+    let head_spec = format!("HEAD:{}", head_path.as_os_str().to_string_lossy());
+    let Ok(head_oid) = git_output(&["rev-parse", "-q", "--verify", &head_spec]) else {
+        skip(
+            "missing",
+            format!(
+                "skipping file missing from HEAD: {}",
+                display_path(path, args)
+            ),
+        );
+        return Ok(false);
+    };
+    let head_oid = head_oid.trim().to_string();
+    let Some(old_bytes) = blob_or_skip(args, path, "HEAD", blob_tail_limited(&head_oid)) else {
+        return Ok(false);
+    };
+
+    // The worktree copy is compared as-is. With `core.autocrlf` its CRLFs are
+    // LFs in the blob, but that conversion never changes whether the content
+    // ends in a newline, which is all `needs_fix` looks at.
+    //
+    // When nothing is written or shown, only the two ends of the new content
+    // are read (see `abridged`).
+    let detect_only = args.dry_run
+        && !args.diff
+        && args.emit_script.is_none()
+        && args.last_line_pattern.is_none()
+        && !args.index_requires_matching_worktree;
+    let new_bytes = match target {
+        FixTarget::Worktree => match if detect_only {
+            file_abridged(path)
+        } else {
+            fs::read(path)
+        } {
+            Ok(b) => b,
+            Err(_) => return Ok(false),
+        },
+        FixTarget::Index | FixTarget::IndexBlob => {
+            let idx_oid = rev_parse_oid(&format!(":{}", path.display()))?;
+            let read = if detect_only {
+                blob_ends_limited(&idx_oid).map(|(head, tail)| abridged(head, &tail))
+            } else {
+                blob_bytes_limited(&idx_oid)
+            };
+            match blob_or_skip(args, path, "the index", read) {
+                Some(b) => b,
+                None => return Ok(false),
+            }
+        }
+    };
+
+    let policy = policy_for(path, args);
+    if !policy.needs_fix(&old_bytes, &new_bytes) {
+        trace(format!("no change: {}", display_path(path, args)));
+        tally(|t| t.unchanged += 1);
+        return Ok(false);
+    }
+    if !last_line_allows_strip(args, policy, &new_bytes) {
+        trace(format!(
+            "last line does not match: {}",
+            display_path(path, args)
+        ));
+        return Ok(false);
+    }
+    if let Some(reason) = binary_skip_reason(args, path, &new_bytes) {
+        skip(
+            "binary",
+            format!(
+                "skipping binary file ({reason}): {}",
+                display_path(path, args)
+            ),
+        );
+        return Ok(false);
+    }
+    let eol = policy.eol(args, &old_bytes);
+
+    if matches!(target, FixTarget::Index | FixTarget::IndexBlob)
+        && args.index_requires_matching_worktree
+    {
+        let worktree_matches = match fs::read(path) {
+            Ok(b) => b == new_bytes,
+            Err(_) => false,
+        };
+        if !worktree_matches {
+            skip(
+                "worktree differs",
+                format!(
+                    "skipping staged file (worktree differs from index): {}",
+                    display_path(path, args)
+                ),
+            );
+            return Ok(false);
+        }
+    }
+
+    if let Some(script) = &args.emit_script {
+        let bytes = match target {
+            FixTarget::Worktree | FixTarget::IndexBlob => new_bytes,
+            FixTarget::Index => fs::read(path)
+                .map_err(|e| format!("failed to read file {}: {e}", path.display()))?,
+        };
+        append_script_steps(
+            script,
+            &script_fix_steps(path, &target, policy, bytes, eol)?,
+        )?;
+        return Ok(true);
+    }
+
+    let label = match target {
+        FixTarget::Worktree => "worktree",
+        FixTarget::Index | FixTarget::IndexBlob => "index",
+    };
+    trace(format!("matched ({label}): {}", display_path(path, args)));
+    let finding = Finding {
+        path,
+        policy,
+        target: label,
+        terminator: Some(policy.terminator(eol, &new_bytes)),
+        applied: !args.dry_run,
+    };
+    if args.dry_run {
+        report_path(
+            args,
+            &finding,
+            format!("n=0 match ({label}): {}", display_path(path, args)),
+        );
+        if args.diff {
+            print_eof_diff(path, policy, eol, new_bytes);
+        }
+        return Ok(true);
+    }
+
+    match target {
+        FixTarget::Worktree => fix_worktree_file(path, policy, eol),
+        FixTarget::Index => {
+            fix_worktree_file(path, policy, eol)?;
+            git_add_path(path)?;
+            Ok(())
+        }
+        FixTarget::IndexBlob => fix_index_blob(path, policy, new_bytes, eol),
+    }?;
+    if args.format == OutputFormat::Json {
+        report_path(args, &finding, String::new());
+    }
+
+    Ok(true)
+}
+
+fn fix_worktree_file(path: &Path, policy: Policy, eol: Option<Eol>) -> Result<(), String> {
+    // Reading and writing through a symlink would change its target, which
+    // may not even be in the repository.
+    if is_worktree_symlink(path) {
+        log(format!("not following symlink: {}", path.display()));
+        return Ok(());
+    }
+    let mut bytes =
+        fs::read(path).map_err(|e| format!("failed to read file {}: {e}", path.display()))?;
+    if !policy.apply(&mut bytes, eol) {
+        return Ok(());
+    }
+    log(format!(
+        "fix worktree ({}): {}",
+        policy.name(),
+        path.display()
+    ));
+    fs::write(path, bytes).map_err(|e| format!("failed to write file {}: {e}", path.display()))?;
+    Ok(())
+}
+
+/// Writes a fixed copy of the staged blob for `path` back into the index
+/// without touching the worktree.
+fn fix_index_blob(
+    path: &Path,
+    policy: Policy,
+    mut bytes: Vec<u8>,
+    eol: Option<Eol>,
+) -> Result<(), String> {
+    if !policy.apply(&mut bytes, eol) {
+        return Ok(());
+    }
+    log(format!("fix index ({}): {}", policy.name(), path.display()));
+
+    let path_s = path.as_os_str().to_string_lossy().to_string();
+    let staged = git_output(&["ls-files", "-s", "--", &path_s])?;
+    let mode = staged
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| format!("{path_s} is not in the index"))?
+        .to_string();
+
+    let oid = git_hash_object_stdin(&bytes)?;
+    git_output(&[
+        "update-index",
+        "--cacheinfo",
+        &format!("{mode},{oid},{path_s}"),
+    ])?;
+    Ok(())
+}
+
+/// Preamble of an `--emit-script` script; `truncate_to <file> <len>` keeps
+/// the first `len` bytes of `file`.
+const SCRIPT_HEADER: &str = "#!/bin/sh
+# Generated by git-fix-eof-newline --emit-script; run from the repository root.
+set -e
+
+truncate_to() {
+    tmp=$(mktemp)
+    head -c \"$2\" -- \"$1\" > \"$tmp\"
+    cat \"$tmp\" > \"$1\"
+    rm -f \"$tmp\"
+}
+
+";
+
+/// Shell commands that apply the same fix as `fix_path_against_head` would,
+/// given the current content of the side being fixed.
+fn script_fix_steps(
+    path: &Path,
+    target: &FixTarget,
+    policy: Policy,
+    mut bytes: Vec<u8>,
+    eol: Option<Eol>,
+) -> Result<Vec<String>, String> {
+    let original_len = bytes.len();
+    if !policy.apply(&mut bytes, eol) {
+        return Ok(Vec::new());
+    }
+    let len = bytes.len();
+    // A strip truncates; a require appends this (printf-escaped) suffix.
+    let suffix = (len > original_len).then(|| bytes[original_len..].escape_ascii().to_string());
+    let path_s = path.as_os_str().to_string_lossy().to_string();
+    let quoted = sh_quote(&path_s);
+    let fix_file = match &suffix {
+        None => format!("truncate_to {quoted} {len}"),
+        Some(suffix) => format!("printf '{suffix}' >> {quoted}"),
+    };
+    Ok(match target {
+        FixTarget::Worktree => vec![fix_file],
+        FixTarget::Index => vec![fix_file, format!("git add -- {quoted}")],
+        FixTarget::IndexBlob => {
+            let staged = git_output(&["ls-files", "-s", "--", &path_s])?;
+            let mode = staged
+                .split_whitespace()
+                .next()
+                .ok_or_else(|| format!("{path_s} is not in the index"))?
+                .to_string();
+            let spec = sh_quote(&format!(":{path_s}"));
+            let fixed_blob = match &suffix {
+                None => format!("git cat-file blob {spec} | head -c {len}"),
+                Some(suffix) => format!("{{ git cat-file blob {spec}; printf '{suffix}'; }}"),
+            };
+            vec![
+                format!("oid=$({fixed_blob} | git hash-object -w --stdin)"),
+                format!("git update-index --cacheinfo \"{mode},$oid,\"{quoted}"),
+            ]
+        }
+    })
+}
+
+fn append_script_steps(script: &Path, steps: &[String]) -> Result<(), String> {
+    let mut text = String::new();
+    for step in steps {
+        text.push_str(step);
+        text.push('\n');
+    }
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .open(script)
+        .map_err(|e| format!("failed to open script {}: {e}", script.display()))?;
+    file.write_all(text.as_bytes())
+        .map_err(|e| format!("failed to write script {}: {e}", script.display()))
+}
+
+fn git_hash_object_stdin(bytes: &[u8]) -> Result<String, String> {
+    let out = git_output_bytes_with_stdin(&["hash-object", "-w", "--stdin"], bytes)?;
+    Ok(String::from_utf8_lossy(&out).trim().to_string())
+}
+
+fn git_add_path(path: &Path) -> Result<(), String> {
+    let status = Command::new("git")
+        .args(["add", "--"])
+        .arg(path)
+        .status()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    log_git(&["add", "--", &path.to_string_lossy()], status);
+    if !status.success() {
+        return Err(format!("git add failed: {}", path.display()));
+    }
+    Ok(())
+}
+
+fn run_n1(args: &Args) -> Result<(), String> {
+    if !args.in_rebase && !previews_only(args) {
+        ensure_clean_worktree()?;
+    }
+    if git_output(&["rev-parse", "--verify", "-q", "HEAD^{commit}"]).is_err() {
+        return Err("HEAD does not point to a commit yet; there is nothing to amend".to_string());
+    }
+    // A rebase runs on a detached HEAD, so only note it for a plain run.
+    let detached = !args.in_rebase && git_output(&["symbolic-ref", "-q", "HEAD"]).is_err();
+
+    if !commit_matches_filters("HEAD", args)? {
+        return Ok(());
+    }
+
+    let (head, parent) = head_and_first_parent()?;
+    let changed = changed_paths_in_commit(&head, args)?;
+    let oids = OidCache::default();
+
+    let mut paths_to_fix: Vec<PathBuf> = Vec::new();
+    let total = changed.len();
+    for (done, (path, old_path)) in changed.iter().enumerate() {
+        if over_byte_budget(args, done, total, "paths") {
+            warn("not amending HEAD: the scan did not finish".to_string());
+            return Ok(());
+        }
+        if commit_path_needs_fix(args, &oids, &parent, &head, path, old_path) {
+            paths_to_fix.push(path.clone());
+        }
+    }
+
+    if paths_to_fix.is_empty() {
+        return Ok(());
+    }
+    tally(|t| {
+        t.files += paths_to_fix.len();
+        t.commits += 1;
+    });
+
+    if let Some(script) = &args.emit_script {
+        for path in &paths_to_fix {
+            let bytes = fs::read(path)
+                .map_err(|e| format!("failed to read file {}: {e}", path.display()))?;
+            let policy = policy_for(path, args);
+            let old_tail =
+                parent_eof_tail(args, &oids, &parent, &changed[path]).unwrap_or_default();
+            let eol = policy.eol(args, &old_tail);
+            let steps = script_fix_steps(path, &FixTarget::Index, policy, bytes, eol)?;
+            append_script_steps(script, &steps)?;
+        }
+        let mut amend = "GIT_EDITOR=true git commit --amend --allow-empty --no-edit".to_string();
+        if args.skip_hooks {
+            amend.push_str(" --no-verify");
+        }
+        return append_script_steps(script, &[amend]);
+    }
+
+    if !args.dry_run {
+        confirm_rewrite(args, &[(head.clone(), paths_to_fix.clone())])?;
+        save_backup_ref(args, &head)?;
+    }
+
+    for path in &paths_to_fix {
+        let policy = policy_for(path, args);
+        let old_tail = parent_eof_tail(args, &oids, &parent, &changed[path]).unwrap_or_default();
+        let eol = policy.eol(args, &old_tail);
+        if args.dry_run || args.format == OutputFormat::Json {
+            // The committed copy: a dry run may see a dirty worktree.
+            let oid = oids.rev_parse(&format!("{head}:{}", path.display()))?;
+            let bytes = blob_bytes_limited(&oid)?;
+            let finding = Finding {
+                path,
+                policy,
+                target: "commit",
+                terminator: Some(policy.terminator(eol, &bytes)),
+                applied: !args.dry_run,
+            };
+            report_path(
+                args,
+                &finding,
+                format!("n=1 match: {}", display_path(path, args)),
+            );
+            if args.diff {
+                print_eof_diff(path, policy, eol, bytes);
+            }
+        }
+        if args.dry_run {
+            continue;
+        }
+        fix_worktree_file(path, policy, eol)?;
+        git_add_path(path)?;
+    }
+
+    let byte_total = if args.report_bytes_per_commit {
+        let bytes = bytes_removed_from_commit(&head, &paths_to_fix, args, &oids)?;
+        vec![(head.clone(), bytes, paths_to_fix.len())]
+    } else {
+        Vec::new()
+    };
+
+    if args.dry_run {
+        if args.annotate_notes {
+            annotate_commit(&head, &paths_to_fix, args)?;
+        }
+        print_byte_totals(args, byte_total);
+        return Ok(());
+    }
+
+    let message = if args.trailers.is_empty() {
+        None
+    } else {
+        Some(message_with_trailers(&head, &args.trailers)?)
+    };
+    amend_head(args, message.as_deref())?;
+
+    if args.safe_mode {
+        let actual = changed_between_trees(&head, "HEAD")?;
+        let expected: BTreeSet<PathBuf> = paths_to_fix.iter().cloned().collect();
+        if actual != expected {
+            git_output(&["reset", "--hard", "-q", &head])?;
+            return Err(format!(
+                "safe mode: amended HEAD differs from the fixes (expected {}, got {}); reset HEAD back to {head}",
+                format_path_set(&expected),
+                format_path_set(&actual)
+            ));
+        }
+    }
+    if args.annotate_notes {
+        annotate_commit("HEAD", &paths_to_fix, args)?;
+    }
+    if detached && args.verbosity > 0 {
+        warn(format!(
+            "HEAD is detached, so no branch was updated: the fixed commit is {} (was {head}); \
+             run `git switch -c <branch>` to keep it",
+            rev_parse_oid("HEAD")?
+        ));
+    }
+    print_byte_totals(args, byte_total);
+    Ok(())
+}
+
+/// Notes ref `--annotate-notes` writes to.
+const NOTES_REF: &str = "fix-eof";
+
+/// Attaches a note listing the fixed `paths` (one `<policy>: <path>` line
+/// each) to `commit`, replacing an earlier note from this tool.
+fn annotate_commit(commit: &str, paths: &[PathBuf], args: &Args) -> Result<(), String> {
+    let note: String = paths
+        .iter()
+        .map(|path| format!("{}: {}\n", policy_for(path, args).name(), path.display()))
+        .collect();
+    git_output(&[
+        "notes",
+        &format!("--ref={NOTES_REF}"),
+        "add",
+        "-f",
+        "-m",
+        &note,
+        commit,
+    ])?;
+    Ok(())
+}
+
+/// Runs `git interpret-trailers` over `commit`'s message to append `trailers`.
+fn message_with_trailers(commit: &str, trailers: &[String]) -> Result<Vec<u8>, String> {
+    let message = git_output_bytes(&["log", "-1", "--format=format:%B", commit])?;
+    let mut cmd: Vec<&str> = vec!["interpret-trailers"];
+    for trailer in trailers {
+        cmd.push("--trailer");
+        cmd.push(trailer);
+    }
+    git_output_bytes_with_stdin(&cmd, &message)
+}
+
+/// `--interactive`: lists the commits about to be rewritten (and the paths
+/// fixed in each) on stderr, and goes ahead only if the answer is `y`.
+///
+/// If stdin is not a terminal, nothing is asked or read and the rewrite goes
+/// ahead as if `--interactive` had not been passed, so a CI job never waits
+/// for an answer. `--yes` skips the question.
+fn confirm_rewrite(args: &Args, plan: &[(String, Vec<PathBuf>)]) -> Result<(), String> {
+    use std::io::IsTerminal;
+    if !args.interactive || args.yes {
+        return Ok(());
+    }
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        trace("stdin is not a terminal; rewriting without asking".to_string());
+        return Ok(());
+    }
+    let count = plan.len();
+    eprintln!(
+        "about to rewrite {count} commit{}:",
+        if count == 1 { "" } else { "s" }
+    );
+    for (commit, paths) in plan {
+        let paths: Vec<String> = paths.iter().map(|p| display_path(p, args)).collect();
+        eprintln!("  {commit}: {}", paths.join(", "));
+    }
+    eprint!("Proceed? [y/N] ");
+    if read_confirmation(&mut stdin.lock())? {
+        return Ok(());
+    }
+    Err("aborted: nothing was rewritten".to_string())
+}
+
+/// Reads one answer to a `[y/N]` question: `y` or `Y` says yes, anything
+/// else (end of input included) says no.
+fn read_confirmation(input: &mut impl BufRead) -> Result<bool, String> {
+    let mut answer = String::new();
+    let read = input
+        .read_line(&mut answer)
+        .map_err(|e| format!("failed to read the answer: {e}"))?;
+    if read == 0 {
+        eprintln!();
+    }
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+/// The full ref `--backup-ref [name]` saves to. A name that already starts
+/// with `refs/` is used as-is.
+fn backup_ref_name(name: Option<String>) -> String {
+    match name {
+        Some(name) if name.starts_with("refs/") => name,
+        Some(name) => format!("refs/fix-eof-backup/{name}"),
+        None => {
+            let secs = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            format!("refs/fix-eof-backup/{secs}")
+        }
+    }
+}
+
+/// `--backup-ref`: points the backup ref at `head` before a rewrite, so
+/// `git reset --hard <ref>` undoes it. An existing ref is never moved.
+fn save_backup_ref(args: &Args, head: &str) -> Result<(), String> {
+    let Some(name) = &args.backup_ref else {
+        return Ok(());
+    };
+    if git_output(&["check-ref-format", name]).is_err() {
+        return Err(format!("invalid --backup-ref name: {name}"));
+    }
+    if git_output(&["rev-parse", "--verify", "-q", name]).is_ok() {
+        return Err(format!("backup ref {name} already exists"));
+    }
+    // The empty old value makes git refuse if the ref appeared meanwhile.
+    git_output(&[
+        "update-ref",
+        "-m",
+        "git-fix-eof-newline: backup",
+        name,
+        head,
+        "",
+    ])?;
+    println!("saved original HEAD as {name}");
+    Ok(())
+}
+
+/// Amends `HEAD` with the staged tree, replacing the message when one is given.
+fn amend_head(args: &Args, message: Option<&[u8]>) -> Result<(), String> {
+    let mut amend = Command::new("git");
+    amend.args(["commit", "--amend", "--allow-empty"]);
+    match message {
+        Some(_) => amend.args(["-F", "-"]).stdin(Stdio::piped()),
+        None => amend.arg("--no-edit"),
+    };
+    if args.skip_hooks {
+        amend.arg("--no-verify");
+    }
+    // Keep stdout to the JSON objects; git's summary and hook output still
+    // show on stderr.
+    if args.format == OutputFormat::Json {
+        amend.stdout(std::io::stderr());
+    }
+    // Make sure nothing can open an editor and block the amend.
+    let mut child = amend
+        .env("GIT_EDITOR", "true")
+        .spawn()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    if let Some(message) = message {
+        child
+            .stdin
+            .take()
+            .ok_or_else(|| "failed to open git stdin".to_string())?
+            .write_all(message)
+            .map_err(|e| format!("failed to write commit message: {e}"))?;
+    }
+    let status = child
+        .wait()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    log_git(&["commit", "--amend"], status);
+    if !status.success() {
+        return Err("git commit --amend failed".to_string());
+    }
+
+    Ok(())
+}
+
+fn run_filter_branch_step(args: &Args) -> Result<(), String> {
+    let commit = filter_branch_commit();
+
+    if let Some(only_commits) = &args.only_commits
+        && !listed_commits(only_commits)?.contains(&commit)
+    {
+        if let Some(report_file) = &args.report_file {
+            append_filter_branch_report(report_file, &commit, &[])?;
+        }
+        return Ok(());
+    }
+    if !commit_matches_filters(&commit, args)? {
+        return Ok(());
+    }
+    // Merges in the range are compared against their first parent so they
+    // don't bring a stripped newline back.
+    let parent = first_parent_of_commit(&commit)?;
+    let changed = changed_paths_in_commit(&commit, args)?;
+    let oids = OidCache::default();
+
+    let mut stripped: Vec<PathBuf> = Vec::new();
+    let changed = match &args.strip_list {
+        Some(strip_list) => {
+            for path in strip_list_paths(strip_list, &commit)? {
+                if path.is_file() && !is_worktree_symlink(&path) && !args.dry_run {
+                    let policy = policy_for(&path, args);
+                    fix_worktree_file(&path, policy, policy.eol(args, b""))?;
+                    stripped.push(path);
+                }
+            }
+            BTreeMap::new()
+        }
+        None => changed,
+    };
+    for (path, old_path) in changed {
+        if is_worktree_symlink(&path) {
+            continue;
+        }
+        let Some(old_bytes) = parent_eof_tail(args, &oids, &parent, &old_path) else {
+            continue;
+        };
+        let new_bytes = match fs::read(&path) {
+            Ok(b) => b,
             Err(_) => continue,
         };
 
-        let old_bytes = match blob_bytes_limited(&old_oid) {
-            Ok(b) => b,
-            Err(_) => continue,
+        let policy = policy_for(&path, args);
+        if policy.needs_fix(&old_bytes, &new_bytes)
+            && binary_skip_reason(args, &path, &new_bytes).is_none()
+            && last_line_allows_strip(args, policy, &new_bytes)
+            && !args.dry_run
+        {
+            fix_worktree_file(&path, policy, policy.eol(args, &old_bytes))?;
+            stripped.push(path);
+        }
+    }
+
+    if let Some(report_file) = &args.report_file {
+        append_filter_branch_report(report_file, &commit, &stripped)?;
+    }
+
+    if !stripped.is_empty() {
+        let status = Command::new("git")
+            .args(["add", "-A"])
+            .status()
+            .map_err(|e| format!("failed to run git: {e}"))?;
+        log_git(&["add", "-A"], status);
+        if !status.success() {
+            return Err("git add -A failed".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// Appends one `processed` record for `commit` and one `stripped` record per
+/// path to the report file shared with the parent process. Records end in
+/// NUL so that any path can be stored.
+fn append_filter_branch_report(
+    report_file: &Path,
+    commit: &str,
+    stripped: &[PathBuf],
+) -> Result<(), String> {
+    let mut lines = format!("processed\t{commit}\0");
+    for path in stripped {
+        lines.push_str(&format!("stripped\t{commit}\t{}\0", path.display()));
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(report_file)
+        .map_err(|e| format!("failed to open report file {}: {e}", report_file.display()))?;
+    file.write_all(lines.as_bytes())
+        .map_err(|e| format!("failed to write report file {}: {e}", report_file.display()))
+}
+
+/// Commits seen by the tree-filter, in order, with the paths stripped in each.
+fn read_filter_branch_report(report_file: &Path) -> Result<Vec<(String, Vec<String>)>, String> {
+    let text = fs::read_to_string(report_file)
+        .map_err(|e| format!("failed to read report file {}: {e}", report_file.display()))?;
+    let mut commits: Vec<(String, Vec<String>)> = Vec::new();
+    for line in text.split_terminator('\0') {
+        let mut parts = line.splitn(3, '\t');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("processed"), Some(commit), None) => {
+                commits.push((commit.to_string(), Vec::new()));
+            }
+            (Some("stripped"), Some(commit), Some(path)) => {
+                if let Some((_, paths)) = commits.iter_mut().rev().find(|(c, _)| c == commit) {
+                    paths.push(path.to_string());
+                }
+            }
+            _ => return Err(format!("malformed report record: {line:?}")),
+        }
+    }
+    Ok(commits)
+}
+
+/// Paths listed for `commit` in a `--strip-list` file of NUL-terminated
+/// `<commit>\t<path>` records.
+fn strip_list_paths(strip_list: &Path, commit: &str) -> Result<Vec<PathBuf>, String> {
+    let text = fs::read_to_string(strip_list)
+        .map_err(|e| format!("failed to read strip list {}: {e}", strip_list.display()))?;
+    Ok(text
+        .split_terminator('\0')
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(c, _)| *c == commit)
+        .map(|(_, path)| PathBuf::from(path))
+        .collect())
+}
+
+/// Reads an `--only-commits` file: one commit id per line.
+fn listed_commits(list: &Path) -> Result<BTreeSet<String>, String> {
+    let text = fs::read_to_string(list)
+        .map_err(|e| format!("failed to read commit list {}: {e}", list.display()))?;
+    Ok(text.lines().map(|l| l.trim().to_string()).collect())
+}
+
+fn filter_branch_commit() -> String {
+    std::env::var("GIT_COMMIT").unwrap_or_else(|_| "HEAD".to_string())
+}
+
+/// Whether a history-rewriting mode only reports (`--dry-run`, `--check`)
+/// and so reads nothing but commits. It then works on a dirty worktree;
+/// `--emit-script` still needs a clean one, since its script is run on it.
+fn previews_only(args: &Args) -> bool {
+    args.dry_run && args.emit_script.is_none()
+}
+
+/// `--autostash`: stashes local changes (untracked files too) around
+/// `rewrite`, like `git rebase --autostash`, and restores them afterwards,
+/// also when the rewrite fails. Runs that rewrite nothing are left alone.
+fn with_autostash(args: &Args, rewrite: impl FnOnce() -> Result<(), String>) -> Result<(), String> {
+    if !args.autostash || previews_only(args) || (args.net && !args.fix_intermediate) {
+        return rewrite();
+    }
+    require_git("--autostash", AUTOSTASH_GIT_VERSION)?;
+    let stash_top = || git_output(&["rev-parse", "-q", "--verify", "refs/stash"]).ok();
+    let before = stash_top();
+    git_output(&[
+        "stash",
+        "push",
+        "-u",
+        "-m",
+        "git-fix-eof-newline --autostash",
+    ])?;
+    let Some(stash) = stash_top().filter(|top| Some(top) != before.as_ref()) else {
+        // Nothing to stash.
+        return rewrite();
+    };
+    let stash = stash.trim().to_string();
+    println!("stashed local changes as {stash}");
+
+    let result = rewrite();
+    match stash_entry(&stash).and_then(|entry| git_output(&["stash", "pop", "--index", &entry])) {
+        Ok(_) => {
+            println!("restored local changes");
+            result
+        }
+        Err(e) => {
+            let kept = format!(
+                "failed to restore local changes ({e}); they are still stashed as {stash}, \
+                 restore them with `git stash pop --index`"
+            );
+            Err(match result {
+                Ok(()) => kept,
+                Err(rewrite_error) => format!("{rewrite_error}\n{kept}"),
+            })
+        }
+    }
+}
+
+/// The `stash@{n}` entry holding `oid`. The stash is shared by every
+/// worktree of the repository, so one stashed elsewhere during the rewrite
+/// may have been pushed on top of ours.
+fn stash_entry(oid: &str) -> Result<String, String> {
+    let out = git_output(&["reflog", "show", "--format=%H", "refs/stash"])?;
+    out.lines()
+        .position(|line| line == oid)
+        .map(|n| format!("stash@{{{n}}}"))
+        .ok_or_else(|| format!("stash {oid} is no longer in the stash list"))
+}
+
+fn ensure_clean_worktree() -> Result<(), String> {
+    let out = git_output(&["status", "--porcelain"])?;
+    if !out.trim().is_empty() {
+        return Err("working tree is not clean; refusing to amend commits".to_string());
+    }
+    Ok(())
+}
+
+fn head_and_first_parent() -> Result<(String, String), String> {
+    let out = git_output(&["rev-list", "--parents", "-n", "1", "HEAD"])?;
+    let mut parts = out.split_whitespace();
+    let head = parts
+        .next()
+        .ok_or_else(|| "failed to parse HEAD".to_string())?
+        .to_string();
+    let parent = parts
+        .next()
+        .ok_or_else(|| "HEAD has no parent (cannot run --n 1 on an initial commit)".to_string())?
+        .to_string();
+    Ok((head, parent))
+}
+
+/// Maps each path `commit` modified (renamed, copied or, with
+/// `--include-added`, added) to its name in the first parent. A merge is
+/// diffed against its first parent.
+fn changed_paths_in_commit(
+    commit: &str,
+    args: &Args,
+) -> Result<BTreeMap<PathBuf, PathBuf>, String> {
+    // Two trees rather than `--diff-merges=first-parent`, which needs git 2.31.
+    let Ok(parent) = rev_parse_oid(&format!("{commit}^1")) else {
+        // A root commit has no parent to compare against.
+        return Ok(BTreeMap::new());
+    };
+    let parent = parent.as_str();
+    // libgit2 has no pathspec magic; with pathspecs, `diff-tree` filters.
+    #[cfg(feature = "libgit2")]
+    if args.pathspecs.is_empty()
+        && let Some(records) = libgit2::diff_records(parent, commit, find_copies())
+    {
+        return Ok(commit_paths(&records?, args));
+    }
+    let mut diff_tree = vec!["diff-tree", "-M"];
+    if find_copies() {
+        diff_tree.push("-C");
+    }
+    diff_tree.extend(["--name-status", "-z", "-r", parent, commit]);
+    let out = git_output_bytes(&with_pathspecs(diff_tree, &args.pathspecs))?;
+    Ok(commit_paths(&name_status_records(&out), args))
+}
+
+/// Whether `diff.renames` asks for copy detection. `diff-tree` ignores the
+/// setting, so it is passed on as `-C`: a copy is then compared against its
+/// source instead of being treated as a new file.
+fn find_copies() -> bool {
+    static FIND_COPIES: OnceLock<bool> = OnceLock::new();
+    *FIND_COPIES.get_or_init(|| {
+        git_config_get("diff.renames").is_some_and(|v| matches!(v.as_str(), "copies" | "copy"))
+    })
+}
+
+/// Whether `--exclude-extension`, `--include` or `--exclude` rules `path`
+/// out. Excludes are checked after includes, so they win.
+fn excluded_path(path: &Path, args: &Args) -> bool {
+    if path.extension().is_some_and(|ext| {
+        args.exclude_extensions
+            .contains(&ext.to_string_lossy().to_ascii_lowercase())
+    }) {
+        return true;
+    }
+    let path = path.as_os_str().to_string_lossy();
+    if !args.include_globs.is_empty() && !args.include_globs.iter().any(|g| g.is_match(&path)) {
+        return true;
+    }
+    args.exclude_globs.iter().any(|g| g.is_match(&path))
+}
+
+/// Like `fixable_paths`, plus added paths under `--include-added` (mapped
+/// to themselves; the parent has no blob for them), minus excluded ones.
+/// Deleted paths are never included, however the statuses above change:
+/// there is no blob or worktree file left to fix.
+fn commit_paths(records: &[DiffRecord], args: &Args) -> BTreeMap<PathBuf, PathBuf> {
+    records
+        .iter()
+        .filter(|r| r.status != 'D')
+        .filter(|r| matches!(r.status, 'M' | 'R' | 'C') || (r.status == 'A' && args.include_added))
+        .filter(|r| !excluded_path(&r.new, args))
+        .map(|r| (r.new.clone(), r.old.clone()))
+        .collect()
+}
+
+/// Whether `commit` passes both the author and the committer filters.
+fn commit_matches_filters(commit: &str, args: &Args) -> Result<bool, String> {
+    Ok(commit_matches_author_filter(commit, args)?
+        && commit_matches_committer_filter(commit, args)?)
+}
+
+fn commit_matches_author_filter(commit: &str, args: &Args) -> Result<bool, String> {
+    if args.author_name.is_none()
+        && args.author_email.is_none()
+        && args.author_name_regex.is_none()
+        && args.author_email_regex.is_none()
+    {
+        return Ok(true);
+    }
+    let (name, email) = commit_author(commit)?;
+    let regex_matches =
+        |re: &Option<Regex>, field: &str| re.as_ref().is_none_or(|re| re.is_match(field));
+    Ok(
+        ident_matches(&name, &email, &args.author_name, &args.author_email)
+            && regex_matches(&args.author_name_regex, &name)
+            && regex_matches(&args.author_email_regex, &email),
+    )
+}
+
+/// Like `commit_matches_author_filter`, for `--committer-name`/`--committer-email`.
+/// In rebased or cherry-picked history the committer is whoever replayed the
+/// commit, not who wrote it.
+fn commit_matches_committer_filter(commit: &str, args: &Args) -> Result<bool, String> {
+    if args.committer_name.is_none() && args.committer_email.is_none() {
+        return Ok(true);
+    }
+    let (name, email) = commit_ident(commit, "%cn%x00%ce")?;
+    Ok(ident_matches(
+        &name,
+        &email,
+        &args.committer_name,
+        &args.committer_email,
+    ))
+}
+
+/// Case-insensitive substring match of a name and email against the
+/// optional needles; a missing needle matches anything.
+fn ident_matches(
+    name: &str,
+    email: &str,
+    name_needle: &Option<String>,
+    email_needle: &Option<String>,
+) -> bool {
+    let contains = |haystack: &str, needle: &Option<String>| {
+        needle
+            .as_ref()
+            .is_none_or(|n| haystack.to_lowercase().contains(&n.to_lowercase()))
+    };
+    contains(name, name_needle) && contains(email, email_needle)
+}
+
+/// The author name and email of `commit`.
+fn commit_author(commit: &str) -> Result<(String, String), String> {
+    commit_ident(commit, "%an%x00%ae")
+}
+
+/// A name and email of `commit`, from a `--format` of the form `%_n%x00%_e`.
+fn commit_ident(commit: &str, format: &str) -> Result<(String, String), String> {
+    let out = git_output(&["show", "-s", &format!("--format={format}"), commit])?;
+    let mut parts = out.split('\0');
+    let name = parts.next().unwrap_or("").trim().to_string();
+    let email = parts.next().unwrap_or("").trim().to_string();
+    Ok((name, email))
+}
+
+fn rev_parse_oid(spec: &str) -> Result<String, String> {
+    #[cfg(feature = "libgit2")]
+    if let Some(oid) = libgit2::rev_parse(spec) {
+        return oid;
+    }
+    Ok(git_output(&["rev-parse", spec])?.trim().to_string())
+}
+
+/// `rev_parse_oid` results for one run, so the scan and the fix that
+/// follows it do not look the same `<commit>:<path>` up twice. Only specs
+/// that start with a full commit hash are cached: `HEAD:<path>` and
+/// `:<path>` can change during a run.
+#[derive(Default)]
+struct OidCache {
+    oids: Mutex<HashMap<String, Result<String, String>>>,
+}
+
+impl OidCache {
+    fn rev_parse(&self, spec: &str) -> Result<String, String> {
+        self.get_or_lookup(spec, rev_parse_oid)
+    }
+
+    fn get_or_lookup(
+        &self,
+        spec: &str,
+        lookup: impl FnOnce(&str) -> Result<String, String>,
+    ) -> Result<String, String> {
+        let rev = spec.split(':').next().unwrap_or("");
+        let cacheable = rev.len() >= 40 && rev.bytes().all(|b| b.is_ascii_hexdigit());
+        if !cacheable {
+            return lookup(spec);
+        }
+        let cached = self.lock().get(spec).cloned();
+        cached.unwrap_or_else(|| {
+            let oid = lookup(spec);
+            self.lock().insert(spec.to_string(), oid.clone());
+            oid
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Result<String, String>>> {
+        self.oids.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn blob_bytes_limited(oid: &str) -> Result<Vec<u8>, String> {
+    BLOBS_READ_WHOLE.fetch_add(1, Ordering::Relaxed);
+    let mut bytes = Vec::new();
+    read_blob(oid, &mut |chunk| bytes.extend_from_slice(chunk))?;
+    Ok(bytes)
+}
+
+/// Like `blob_bytes_limited`, but keeps only the blob's last `EOF_TAIL_LEN`
+/// bytes, which is all EOF detection needs.
+fn blob_tail_limited(oid: &str) -> Result<Vec<u8>, String> {
+    blob_ends_limited(oid).map(|(_, tail)| tail)
+}
+
+/// Like `blob_tail_limited`, but also returns the first `BINARY_CHECK_LEN`
+/// bytes for `looks_binary`.
+fn blob_ends_limited(oid: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+    BLOBS_READ_ENDS.fetch_add(1, Ordering::Relaxed);
+    let mut head: Vec<u8> = Vec::new();
+    let mut tail: Vec<u8> = Vec::with_capacity(EOF_TAIL_LEN * 2);
+    read_blob(oid, &mut |chunk| {
+        let wanted = BINARY_CHECK_LEN.saturating_sub(head.len()).min(chunk.len());
+        head.extend_from_slice(&chunk[..wanted]);
+        tail.extend_from_slice(&chunk[chunk.len().saturating_sub(EOF_TAIL_LEN)..]);
+        let excess = tail.len().saturating_sub(EOF_TAIL_LEN);
+        tail.drain(..excess);
+    })?;
+    Ok((head, tail))
+}
+
+/// Hands blob `oid` to `sink`, in one piece through libgit2 with the
+/// `libgit2` feature, otherwise streamed by this thread's `CatFileBatch`.
+fn read_blob(oid: &str, sink: &mut dyn FnMut(&[u8])) -> Result<(), String> {
+    #[cfg(feature = "libgit2")]
+    if let Some(result) = libgit2::with_blob(oid, MAX_BLOB_SIZE.load(Ordering::Relaxed), |blob| {
+        BLOB_BYTES_READ.fetch_add(blob.len() as u64, Ordering::Relaxed);
+        sink(blob)
+    }) {
+        return result;
+    }
+    with_cat_file(|batch| batch.read_blob(oid, sink))
+}
+
+/// Stands in for content of which only the `head` and `tail` were read: the
+/// first `BINARY_CHECK_LEN` bytes followed by the last `EOF_TAIL_LEN`, which
+/// is all `looks_binary`, `needs_fix` and `terminator` look at. A head
+/// shorter than `BINARY_CHECK_LEN` is the whole content already.
+fn abridged(mut head: Vec<u8>, tail: &[u8]) -> Vec<u8> {
+    if head.len() == BINARY_CHECK_LEN {
+        head.extend_from_slice(tail);
+    }
+    head
+}
+
+/// `abridged` for a worktree file: reads its first `BINARY_CHECK_LEN` and
+/// last `EOF_TAIL_LEN` bytes only.
+fn file_abridged(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut head = Vec::new();
+    (&mut file)
+        .take(BINARY_CHECK_LEN as u64)
+        .read_to_end(&mut head)?;
+    let mut tail = Vec::new();
+    let rest = len.saturating_sub(head.len() as u64);
+    if rest > 0 {
+        file.seek(SeekFrom::End(-(rest.min(EOF_TAIL_LEN as u64) as i64)))?;
+        file.read_to_end(&mut tail)?;
+    }
+    Ok(abridged(head, &tail))
+}
+
+/// A long-lived `git cat-file --batch`: reading a blob is one round trip
+/// over its pipes instead of a `cat-file -s` and a `cat-file -p` spawn.
+struct CatFileBatch {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+    /// Set once a read fails halfway, leaving the stream out of step.
+    broken: bool,
+}
+
+thread_local! {
+    static CAT_FILE: RefCell<Option<CatFileBatch>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with this thread's `CatFileBatch`, starting it on first use and
+/// again after a read broke it.
+fn with_cat_file<T>(f: impl FnOnce(&mut CatFileBatch) -> Result<T, String>) -> Result<T, String> {
+    CAT_FILE.with(|cell| {
+        let mut slot = cell.borrow_mut();
+        if slot.as_ref().is_none_or(|batch| batch.broken) {
+            *slot = Some(CatFileBatch::open()?);
+        }
+        match slot.as_mut() {
+            Some(batch) => f(batch),
+            None => Err("internal error: git cat-file --batch is not running".to_string()),
+        }
+    })
+}
+
+impl CatFileBatch {
+    fn open() -> Result<CatFileBatch, String> {
+        let mut child = Command::new("git")
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to run git: {e}"))?;
+        let stdin = child.stdin.take();
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| "failed to open git stdout".to_string())?;
+        Ok(CatFileBatch {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            broken: false,
+        })
+    }
+
+    /// Streams blob `oid` to `sink` in chunks. Blobs over `--max-blob-size`
+    /// are skipped over without being handed to `sink`.
+    fn read_blob(&mut self, oid: &str, sink: &mut dyn FnMut(&[u8])) -> Result<(), String> {
+        match self.read_object(oid, sink) {
+            Ok(result) => result,
+            Err(e) => {
+                self.broken = true;
+                Err(format!("git cat-file --batch failed: {e}"))
+            }
+        }
+    }
+
+    /// The outer error means the stream is out of step; the inner one is
+    /// about `oid` only (missing, not a blob, too large).
+    fn read_object(
+        &mut self,
+        oid: &str,
+        sink: &mut dyn FnMut(&[u8]),
+    ) -> Result<Result<(), String>, String> {
+        let stdin = self.stdin.as_mut().ok_or("stdin is closed")?;
+        writeln!(stdin, "{oid}")
+            .and_then(|_| stdin.flush())
+            .map_err(|e| e.to_string())?;
+
+        let mut header = Vec::new();
+        self.stdout
+            .read_until(b'\n', &mut header)
+            .map_err(|e| e.to_string())?;
+        let header = String::from_utf8_lossy(&header);
+        let fields: Vec<&str> = header.split_whitespace().collect();
+        let (kind, size) = match fields[..] {
+            [_, kind, size] => (kind, size),
+            [_, "missing" | "ambiguous"] => {
+                return Ok(Err(format!("git cat-file: {oid} is {}", fields[1])));
+            }
+            _ => return Err(format!("unexpected output: {}", header.trim())),
+        };
+        let size: u64 = size
+            .parse()
+            .map_err(|_| format!("failed to parse blob size: {size}"))?;
+        let limit = MAX_BLOB_SIZE.load(Ordering::Relaxed);
+        let wanted = kind == "blob" && size <= limit;
+        if wanted {
+            BLOB_BYTES_READ.fetch_add(size, Ordering::Relaxed);
+        }
+
+        // The content is followed by a newline, which is read (and dropped)
+        // with it.
+        let mut remaining = size + 1;
+        while remaining > 0 {
+            let buf = self.stdout.fill_buf().map_err(|e| e.to_string())?;
+            if buf.is_empty() {
+                return Err("git exited early".to_string());
+            }
+            let n = buf.len().min(remaining as usize);
+            if wanted {
+                sink(&buf[..n.min((remaining - 1) as usize)]);
+            }
+            self.stdout.consume(n);
+            remaining -= n as u64;
+        }
+
+        Ok(if kind != "blob" {
+            Err(format!("{oid} is a {kind}, not a blob"))
+        } else if !wanted {
+            Err(format!(
+                "blob {oid} is {size} bytes, over the --max-blob-size limit of {limit}"
+            ))
+        } else {
+            Ok(())
+        })
+    }
+}
+
+impl Drop for CatFileBatch {
+    fn drop(&mut self) {
+        // Closing stdin ends the batch.
+        drop(self.stdin.take());
+        if let Ok(status) = self.child.wait() {
+            log_git(&["cat-file", "--batch"], status);
+        }
+    }
+}
+
+/// Blob bytes read so far, for `--max-total-bytes`.
+static BLOB_BYTES_READ: AtomicU64 = AtomicU64::new(0);
+/// Blobs kept whole, and blobs of which only the ends were kept; logged at
+/// the end of a run.
+static BLOBS_READ_WHOLE: AtomicU64 = AtomicU64::new(0);
+static BLOBS_READ_ENDS: AtomicU64 = AtomicU64::new(0);
+
+/// Parses a byte count with an optional binary `K`/`M`/`G` suffix.
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (digits, unit) = match s.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((i, _)) => s.split_at(i),
+        None => (s, ""),
+    };
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return None,
+    };
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Whether `--max-total-bytes` has been exceeded before item `done` of
+/// `total`; if so, reports the progress on stderr.
+fn over_byte_budget(args: &Args, done: usize, total: usize, unit: &str) -> bool {
+    let Some(cap) = args.max_total_bytes else {
+        return false;
+    };
+    let read = BLOB_BYTES_READ.load(Ordering::Relaxed);
+    if read <= cap {
+        return false;
+    }
+    warn(format!(
+        "stopping: read {read} blob bytes (--max-total-bytes {cap}); processed {done} of {total} {unit}, {} remaining",
+        total - done
+    ));
+    true
+}
+
+/// Default for `--max-blob-size`.
+const DEFAULT_MAX_BLOB_SIZE: u64 = 10_000_000;
+/// Blobs larger than this are never read (`--max-blob-size`).
+static MAX_BLOB_SIZE: AtomicU64 = AtomicU64::new(DEFAULT_MAX_BLOB_SIZE);
+
+fn run_n_gt1(args: &Args) -> Result<(), String> {
+    if args.n == 0 {
+        return Err("internal error: run_n_gt1 received --n 0".to_string());
+    }
+    if args.n == 1 {
+        return run_n1(args);
+    }
+
+    if !previews_only(args) {
+        ensure_clean_worktree()?;
+    }
+    ensure_not_in_rebase()?;
+    if args.engine == Engine::FilterRepo && !args.dry_run && !args.check {
+        ensure_filter_repo_installed()?;
+    }
+
+    let mut commits = recent_commits(args)?;
+    // The newest --min-commit-depth commits are replayed with their trees
+    // unchanged, so they are not scanned at all.
+    let protected = commits.split_off(commits.len().saturating_sub(args.min_commit_depth));
+
+    let oids = OidCache::default();
+    let scanned = scan_commits(&commits, args, &oids);
+    let mut needs_fix: Vec<String> = Vec::new();
+    // Author email -> (files, commits), for --group-by-author.
+    let mut by_author: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    // (commit, bytes removed, files), for --report-bytes-per-commit.
+    let mut byte_totals: Vec<(String, usize, usize)> = Vec::new();
+    for (commit, scan) in commits.iter().zip(scanned) {
+        let Some(scan) = scan else {
+            warn("not rewriting history: the scan did not finish".to_string());
+            return Ok(());
         };
-        let new_bytes = match fs::read(&path) {
-            Ok(b) => b,
-            Err(_) => continue,
+        let Some(paths) = scan? else {
+            continue;
         };
+        if args.group_by_author {
+            let (_, email) = commit_author(commit)?;
+            let counts = by_author.entry(email).or_default();
+            counts.0 += paths.len();
+            counts.1 += 1;
+        }
+        if args.report_bytes_per_commit {
+            let bytes = bytes_removed_from_commit(commit, &paths, args, &oids)?;
+            byte_totals.push((commit.clone(), bytes, paths.len()));
+        }
+        needs_fix.push(commit.clone());
+    }
+
+    if needs_fix.is_empty() {
+        return Ok(());
+    }
+
+    let earliest = needs_fix
+        .first()
+        .ok_or_else(|| "internal error: needs_fix is empty".to_string())?;
+    let base = first_parent_of_commit(earliest)?;
+
+    if let Some(todo) = &args.emit_rebase_todo {
+        let exe = std::env::current_exe()
+            .map_err(|e| format!("failed to locate current executable: {e}"))?;
+        let exec = format!("{} --n 1 --in-rebase", sh_path(&exe));
+        write_rebase_todo(todo, &base, &needs_fix, &exec, true)?;
+        println!("wrote rebase todo: {} (base {base})", todo.display());
+        return Ok(());
+    }
 
-        if added_eof_newline(&old_bytes, &new_bytes) {
-            if !args.dry_run {
-                strip_worktree_file(&path)?;
-                changed_any = true;
+    if args.check || (args.dry_run && args.format == OutputFormat::Json) {
+        let mut paths: BTreeSet<PathBuf> = BTreeSet::new();
+        for c in &needs_fix {
+            let fixes = commit_paths_needing_fix(c, args, &oids)?;
+            tally(|t| t.files += fixes.len());
+            paths.extend(fixes);
+        }
+        tally(|t| t.commits += needs_fix.len());
+        for path in &paths {
+            let finding = Finding {
+                path,
+                policy: policy_for(path, args),
+                target: "commit",
+                terminator: None,
+                applied: false,
+            };
+            report_path(args, &finding, display_path(path, args));
+        }
+        print_byte_totals(args, args.commit_order.arrange(byte_totals));
+        print_author_groups(args, &by_author);
+        return Ok(());
+    }
+
+    if args.dry_run {
+        if args.summary {
+            for c in &needs_fix {
+                let files = commit_paths_needing_fix(c, args, &oids)?.len();
+                tally(|t| t.files += files);
+            }
+            tally(|t| t.commits += needs_fix.len());
+        }
+        println!("will run filter-branch starting at base: {base}");
+        if args.annotate_notes {
+            for c in &needs_fix {
+                annotate_commit(c, &commit_paths_needing_fix(c, args, &oids)?, args)?;
+            }
+        }
+        for c in args.commit_order.arrange(needs_fix) {
+            println!("n>1 match commit: {c}");
+            if args.diff {
+                print_commit_diffs(&c, args, &oids)?;
             }
         }
+        print_byte_totals(args, args.commit_order.arrange(byte_totals));
+        print_author_groups(args, &by_author);
+        return Ok(());
     }
 
-    if changed_any {
-        let status = Command::new("git")
-            .args(["add", "-A"])
-            .status()
-            .map_err(|e| format!("failed to run git: {e}"))?;
-        if !status.success() {
-            return Err("git add -A failed".to_string());
+    if args.interactive && !args.yes {
+        let mut plan = Vec::new();
+        for c in args.commit_order.arrange(needs_fix.clone()) {
+            let paths = commit_paths_needing_fix(&c, args, &oids)?;
+            plan.push((c, paths));
         }
+        confirm_rewrite(args, &plan)?;
+    }
+    save_backup_ref(args, git_output(&["rev-parse", "HEAD"])?.trim())?;
+
+    // Commits outside the scan (newer than a range, or outside the dates)
+    // are in `base..HEAD` too, but must be left alone.
+    let partial_scan = args.range.is_some() || args.since.is_some() || args.until.is_some();
+    if args.engine == Engine::Rebase {
+        rewrite_with_rebase(args, &oids, &base, &needs_fix)?;
+    } else if args.engine == Engine::FilterRepo {
+        rewrite_with_filter_repo(args, &oids, &base, &needs_fix)?;
+    } else if protected.is_empty() && !partial_scan {
+        rewrite_with_filter_branch(args, &base, None, None)?;
+    } else {
+        let only_commits = git_path(&format!(
+            "git-fix-eof-newline-only-commits-{}",
+            std::process::id()
+        ))?;
+        fs::write(&only_commits, needs_fix.join("\n") + "\n").map_err(|e| {
+            format!(
+                "failed to write commit list {}: {e}",
+                only_commits.display()
+            )
+        })?;
+        let result = rewrite_with_filter_branch(args, &base, None, Some(&only_commits));
+        let _ = fs::remove_file(&only_commits);
+        result?;
     }
+    print_byte_totals(args, args.commit_order.arrange(byte_totals));
+    print_author_groups(args, &by_author);
 
     Ok(())
 }
 
-fn filter_branch_commit() -> String {
-    std::env::var("GIT_COMMIT").unwrap_or_else(|_| "HEAD".to_string())
+/// One commit's scan result: `Some(paths)` if it needs fixing (the paths
+/// are only listed when a report needs them), or `None` if `--max-total-bytes`
+/// stopped the scan before it.
+type CommitScan = Option<Result<Option<Vec<PathBuf>>, String>>;
+
+/// Scans `commits` on `--jobs` threads, each running its own git processes,
+/// and returns the results in the same order. Only the scan is parallel;
+/// the rewrite that follows is not.
+fn scan_commits(commits: &[String], args: &Args, oids: &OidCache) -> Vec<CommitScan> {
+    let jobs = match args.jobs {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+    .min(commits.len())
+    .max(1);
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<CommitScan>> = Mutex::new((0..commits.len()).map(|_| None).collect());
+    let stopped = Mutex::new(false);
+    let progress = ScanProgress::new(commits.len());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                loop {
+                    let done = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(commit) = commits.get(done) else {
+                        break;
+                    };
+                    {
+                        // Held while checking, so the budget is reported once.
+                        let mut stopped = stopped.lock().unwrap_or_else(|e| e.into_inner());
+                        if *stopped || over_byte_budget(args, done, commits.len(), "commits") {
+                            *stopped = true;
+                            break;
+                        }
+                    }
+                    let scan = scan_commit(commit, args, oids);
+                    progress.record(matches!(scan, Ok(Some(_))));
+                    results.lock().unwrap_or_else(|e| e.into_inner())[done] = Some(scan);
+                }
+            });
+        }
+    });
+    progress.finish();
+    results.into_inner().unwrap_or_else(|e| e.into_inner())
 }
 
-fn ensure_clean_worktree() -> Result<(), String> {
-    let out = git_output(&["status", "--porcelain"])?;
-    if !out.trim().is_empty() {
-        return Err("working tree is not clean; refusing to amend commits".to_string());
+/// The `scanned X/N commits, Y need fixing` line `scan_commits` keeps
+/// rewriting on stderr. Only shown on a terminal, and not with `--quiet`
+/// (or `--verbose`, which already prints a line per path).
+struct ScanProgress {
+    total: usize,
+    enabled: bool,
+    /// Commits scanned so far, and how many of them need fixing.
+    counts: Mutex<(usize, usize)>,
+}
+
+impl ScanProgress {
+    fn new(total: usize) -> ScanProgress {
+        use std::io::IsTerminal;
+        let enabled = VERBOSITY.load(Ordering::Relaxed) == 1 && std::io::stderr().is_terminal();
+        PROGRESS_SHOWN.store(enabled, Ordering::Relaxed);
+        ScanProgress {
+            total,
+            enabled,
+            counts: Mutex::new((0, 0)),
+        }
+    }
+
+    fn record(&self, needs_fix: bool) {
+        let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        counts.0 += 1;
+        counts.1 += usize::from(needs_fix);
+        if self.enabled {
+            // Printed under the lock, so the counts never go backwards.
+            eprint!("\r{}", scan_progress_line(counts.0, self.total, counts.1));
+        }
+    }
+
+    /// Ends the progress line, so later output starts on a line of its own.
+    fn finish(&self) {
+        let counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+        if self.enabled && counts.0 > 0 {
+            eprintln!();
+        }
+        PROGRESS_SHOWN.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Whether a `ScanProgress` line is on stderr, for `warn`.
+static PROGRESS_SHOWN: AtomicBool = AtomicBool::new(false);
+
+fn scan_progress_line(scanned: usize, total: usize, need_fixing: usize) -> String {
+    format!("scanned {scanned}/{total} commits, {need_fixing} need fixing")
+}
+
+fn scan_commit(commit: &str, args: &Args, oids: &OidCache) -> Result<Option<Vec<PathBuf>>, String> {
+    if !commit_matches_filters(commit, args)? {
+        return Ok(None);
+    }
+    if args.group_by_author || args.report_bytes_per_commit {
+        let paths = commit_paths_needing_fix(commit, args, oids)?;
+        return Ok((!paths.is_empty()).then_some(paths));
+    }
+    Ok(commit_has_added_eof_newline(commit, args, oids)?.then(Vec::new))
+}
+
+/// Where a rewrite records what it is about to change, so an interrupted run
+/// can be undone. The file lives in the git directory and is removed once
+/// filter-branch has finished.
+struct RunState {
+    file: PathBuf,
+    /// filter-branch's `-d` working directory.
+    temp_dir: PathBuf,
+}
+
+impl RunState {
+    const FILE: &'static str = "git-fix-eof-newline-run-state";
+    const TEMP_DIR: &'static str = "git-fix-eof-newline-rewrite";
+
+    /// Records `old_head` and the checked-out ref. Refuses to start while the
+    /// state of an earlier run is still present.
+    fn begin(old_head: &str) -> Result<RunState, String> {
+        let file = git_path(Self::FILE)?;
+        if file.exists() {
+            return Err(
+                "a previous rewrite was interrupted; run with --abort to restore the original \
+                 branch or --resume to restore it and retry"
+                    .to_string(),
+            );
+        }
+        let head_ref = git_output(&["symbolic-ref", "-q", "HEAD"]).unwrap_or_default();
+        let state = format!("head {old_head}\nref {}\n", head_ref.trim());
+        fs::write(&file, state)
+            .map_err(|e| format!("failed to write run state {}: {e}", file.display()))?;
+        Ok(RunState {
+            file,
+            temp_dir: git_path(Self::TEMP_DIR)?,
+        })
+    }
+
+    fn finish(self) {
+        let _ = fs::remove_file(&self.file);
+    }
+}
+
+/// `--resume`/`--abort`: puts the branch back where an interrupted rewrite
+/// found it and removes filter-branch's leftovers.
+fn restore_interrupted_rewrite() -> Result<(), String> {
+    let file = git_path(RunState::FILE)?;
+    let state = fs::read_to_string(&file)
+        .map_err(|_| "no interrupted rewrite to resume or abort".to_string())?;
+    let field = |key: &str| {
+        state
+            .lines()
+            .find_map(|l| l.strip_prefix(key)?.strip_prefix(' '))
+            .unwrap_or("")
+            .to_string()
+    };
+    let old_head = field("head");
+    let head_ref = field("ref");
+    if old_head.is_empty() {
+        return Err(format!("malformed run state {}", file.display()));
+    }
+
+    if !head_ref.is_empty() {
+        git_output(&["update-ref", &head_ref, &old_head])?;
+    }
+    let current_ref = git_output(&["symbolic-ref", "-q", "HEAD"]).unwrap_or_default();
+    if head_ref.is_empty() || current_ref.trim() == head_ref {
+        git_output(&["reset", "--hard", "-q", &old_head])?;
     }
+    let temp_dir = git_path(RunState::TEMP_DIR)?;
+    if temp_dir.exists() {
+        fs::remove_dir_all(&temp_dir)
+            .map_err(|e| format!("failed to remove {}: {e}", temp_dir.display()))?;
+    }
+    fs::remove_file(&file)
+        .map_err(|e| format!("failed to remove run state {}: {e}", file.display()))?;
+    let name = head_ref.strip_prefix("refs/heads/").unwrap_or(&head_ref);
+    println!(
+        "restored {} to {old_head}",
+        if name.is_empty() { "HEAD" } else { name }
+    );
     Ok(())
 }
 
-fn head_and_first_parent() -> Result<(String, String), String> {
-    let out = git_output(&["rev-list", "--parents", "-n", "1", "HEAD"])?;
-    let mut parts = out.split_whitespace();
-    let head = parts
-        .next()
-        .ok_or_else(|| "failed to parse HEAD".to_string())?
-        .to_string();
-    let parent = parts
-        .next()
-        .ok_or_else(|| "HEAD has no parent (cannot run --n 1 on an initial commit)".to_string())?
-        .to_string();
-    Ok((head, parent))
+/// Writes a `git rebase -i` todo list for `base..HEAD` that marks each of
+/// `needs_fix` with `edit`, followed by an `exec` that amends it.
+fn write_rebase_todo(
+    todo: &Path,
+    base: &str,
+    needs_fix: &[String],
+    exec: &str,
+    stop: bool,
+) -> Result<(), String> {
+    let range = format!("{base}..HEAD");
+    let log = git_output(&[
+        "log",
+        "--reverse",
+        "--first-parent",
+        "--format=%H %s",
+        &range,
+    ])?;
+    let mut out = String::new();
+    for line in log.lines() {
+        let commit = line.split(' ').next().unwrap_or(line);
+        if needs_fix.iter().any(|c| c == commit) {
+            let command = if stop { "edit" } else { "pick" };
+            out.push_str(&format!("{command} {line}\nexec {exec}\n"));
+        } else {
+            out.push_str(&format!("pick {line}\n"));
+        }
+    }
+    fs::write(todo, out).map_err(|e| format!("failed to write {}: {e}", todo.display()))
 }
 
-fn changed_paths_in_commit(commit: &str) -> Result<Vec<PathBuf>, String> {
-    let out = git_output(&["diff-tree", "--no-commit-id", "--name-status", "-r", commit])?;
-    let mut paths = Vec::new();
-    for line in out.lines() {
-        let mut parts = line.split('\t');
-        let status = match parts.next() {
-            Some(s) => s,
-            None => continue,
-        };
-        if status != "M" {
+/// Rewrites `base..HEAD` with `git rebase -i`, amending only `needs_fix`:
+/// an `exec` of `--n 1 --in-rebase` follows each of them and every other
+/// commit is a plain `pick`, which git fast-forwards until the first amend.
+fn rewrite_with_rebase(
+    args: &Args,
+    oids: &OidCache,
+    base: &str,
+    needs_fix: &[String],
+) -> Result<(), String> {
+    let merges = git_output(&["rev-list", "--merges", &format!("{base}..HEAD")])?;
+    if !merges.trim().is_empty() {
+        return Err(
+            "--engine rebase cannot rewrite merge commits; use --engine filter-branch".to_string(),
+        );
+    }
+    let mut fixed: Vec<(String, Vec<PathBuf>)> = Vec::new();
+    for commit in needs_fix {
+        fixed.push((
+            commit.clone(),
+            commit_paths_needing_fix(commit, args, oids)?,
+        ));
+    }
+
+    let exe =
+        std::env::current_exe().map_err(|e| format!("failed to locate current executable: {e}"))?;
+    let mut exec = vec![
+        sh_path(&exe),
+        "--n".to_string(),
+        "1".to_string(),
+        "--in-rebase".to_string(),
+    ];
+    if args.skip_hooks {
+        exec.push("--skip-hooks".to_string());
+    }
+    exec.extend(forwarded_fix_flags(args));
+    let todo = git_path(&format!(
+        "git-fix-eof-newline-rebase-todo-{}",
+        std::process::id()
+    ))?;
+    write_rebase_todo(&todo, base, needs_fix, &exec.join(" "), false)?;
+
+    let rebase_args = ["rebase", "-i", "--no-autosquash", base];
+    // git's progress and the amend summaries go to stderr, so stdout only
+    // has the report below.
+    let status = Command::new("git")
+        .args(rebase_args)
+        .stdout(std::io::stderr())
+        .env("GIT_SEQUENCE_EDITOR", format!("cp {}", sh_path(&todo)))
+        .status()
+        .map_err(|e| format!("failed to run git: {e}"));
+    let _ = fs::remove_file(&todo);
+    let status = status?;
+    log_git(&rebase_args, status);
+    if !status.success() {
+        return Err(
+            "git rebase stopped; finish it with `git rebase --continue` or undo it with `git rebase --abort`"
+                .to_string(),
+        );
+    }
+
+    report_rewritten_commits(args, fixed);
+    Ok(())
+}
+
+/// Prints each rewritten commit with the paths fixed in it, or one record
+/// per path for the non-text formats.
+fn report_rewritten_commits(args: &Args, fixed: Vec<(String, Vec<PathBuf>)>) {
+    tally(|t| {
+        t.files += fixed.iter().map(|(_, paths)| paths.len()).sum::<usize>();
+        t.commits += fixed.len();
+    });
+    for (commit, paths) in args.commit_order.arrange(fixed) {
+        if args.format != OutputFormat::Text {
+            for path in &paths {
+                let finding = Finding {
+                    path,
+                    policy: policy_for(path, args),
+                    target: "commit",
+                    terminator: None,
+                    applied: true,
+                };
+                report_path(args, &finding, String::new());
+            }
             continue;
         }
-        if let Some(path) = parts.next() {
-            paths.push(PathBuf::from(path));
+        let paths: Vec<String> = paths.iter().map(|p| display_path(p, args)).collect();
+        println!("rewrote {commit}: {}", paths.join(", "));
+    }
+}
+
+fn ensure_filter_repo_installed() -> Result<(), String> {
+    require_git("--engine filter-repo", FILTER_REPO_GIT_VERSION)?;
+    let installed = Command::new("git")
+        .args(["filter-repo", "--version"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success());
+    if !installed {
+        return Err(
+            "--engine filter-repo needs git-filter-repo, which was not found; install it \
+             (e.g. `pip install git-filter-repo`) or use --engine filter-branch"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Rewrites `base..<branch>` with `git filter-repo`. The fixed blobs are
+/// written up front; the commit callback only swaps them into the matching
+/// commits, so, as with `--engine rebase`, later commits that keep a path
+/// unchanged keep the fix too.
+fn rewrite_with_filter_repo(
+    args: &Args,
+    oids: &OidCache,
+    base: &str,
+    needs_fix: &[String],
+) -> Result<(), String> {
+    let head_ref = git_output(&["symbolic-ref", "-q", "HEAD"]).unwrap_or_default();
+    let head_ref = head_ref.trim();
+    if head_ref.is_empty() {
+        return Err("--engine filter-repo needs a checked-out branch".to_string());
+    }
+
+    let mut fixed: Vec<(String, Vec<PathBuf>)> = Vec::new();
+    let mut callback = String::from(
+        "import builtins\nfixes = getattr(builtins, 'fix_eof_newline', None)\nif fixes is None:\n    fixes = builtins.fix_eof_newline = {\n",
+    );
+    for commit in needs_fix {
+        let parent = first_parent_of_commit(commit)?;
+        let mut paths: Vec<PathBuf> = Vec::new();
+        let mut blobs = String::new();
+        for (path, old_path) in changed_paths_in_commit(commit, args)? {
+            if !commit_path_needs_fix(args, oids, &parent, commit, &path, &old_path) {
+                continue;
+            }
+            let spec = |rev: &str, path: &Path| format!("{rev}:{}", path.display());
+            let mut bytes = blob_bytes_limited(&oids.rev_parse(&spec(commit, &path))?)?;
+            let old_tail = parent_eof_tail(args, oids, &parent, &old_path).unwrap_or_default();
+            let policy = policy_for(&path, args);
+            if !policy.apply(&mut bytes, policy.eol(args, &old_tail)) {
+                continue;
+            }
+            let oid = git_hash_object_stdin(&bytes)?;
+            let name = py_bytes(path.as_os_str().as_encoded_bytes());
+            blobs.push_str(&format!("{name}: b'{oid}', "));
+            paths.push(path);
+        }
+        if !paths.is_empty() {
+            callback.push_str(&format!("        b'{commit}': {{{blobs}}},\n"));
+            fixed.push((commit.clone(), paths));
         }
     }
-    Ok(paths)
+    callback.push_str(concat!(
+        "    }\n",
+        "for change in commit.file_changes:\n",
+        "    oid = fixes.get(commit.original_id, {}).get(change.filename)\n",
+        "    if oid is not None and change.type == b'M':\n",
+        "        change.blob_id = oid\n",
+    ));
+
+    let old_head = rev_parse_oid("HEAD")?;
+    let run_state = RunState::begin(&old_head)?;
+    let range = format!("{base}..{head_ref}");
+    let repo_args = [
+        "filter-repo",
+        "--force",
+        "--quiet",
+        "--commit-callback",
+        &callback,
+        "--refs",
+        &range,
+    ];
+    let status = Command::new("git")
+        .args(repo_args)
+        .stdout(std::io::stderr())
+        .status()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    log_git(&["filter-repo", "--refs", &range], status);
+    if !status.success() {
+        return Err(
+            "git filter-repo failed; run with --abort to restore the original branch".to_string(),
+        );
+    }
+    // With --refs, filter-repo leaves the worktree at the old commits.
+    git_output(&["reset", "--hard", "-q", "HEAD"])?;
+    run_state.finish();
+
+    report_rewritten_commits(args, fixed);
+    Ok(())
 }
 
-fn commit_matches_author_filter(commit: &str, args: &Args) -> Result<bool, String> {
-    if args.author_name.is_none() && args.author_email.is_none() {
-        return Ok(true);
+/// `bytes` as a Python bytes literal.
+fn py_bytes(bytes: &[u8]) -> String {
+    let mut out = String::from("b'");
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || b"/._-".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("\\x{b:02x}"));
+        }
     }
-    let out = git_output(&["show", "-s", "--format=%an%x00%ae", commit])?;
-    let mut parts = out.split('\0');
-    let name = parts.next().unwrap_or("").trim();
-    let email = parts.next().unwrap_or("").trim();
+    out.push('\'');
+    out
+}
 
-    if let Some(needle) = &args.author_name {
-        if !name.to_lowercase().contains(&needle.to_lowercase()) {
-            return Ok(false);
+/// Rewrites `base..HEAD` with the tree-filter and prints what it changed.
+/// With `strip_list`, the tree-filter strips exactly the listed paths; with
+/// `only_commits`, it leaves every commit not in that list unchanged.
+fn rewrite_with_filter_branch(
+    args: &Args,
+    base: &str,
+    strip_list: Option<&Path>,
+    only_commits: Option<&Path>,
+) -> Result<(), String> {
+    let report_file = git_path(&format!(
+        "git-fix-eof-newline-report-{}",
+        std::process::id()
+    ))?;
+    fs::write(&report_file, b"").map_err(|e| {
+        format!(
+            "failed to create report file {}: {e}",
+            report_file.display()
+        )
+    })?;
+
+    let old_head = rev_parse_oid("HEAD")?;
+    let tree_filter_cmd =
+        build_filter_branch_tree_filter_command(args, &report_file, strip_list, only_commits)?;
+    let run_state = RunState::begin(&old_head)?;
+    let rev_range = format!("{base}..HEAD");
+    let mut filter_branch = Command::new("git");
+    // Its `Rewrite <sha>` progress would land among the JSON objects.
+    if args.format == OutputFormat::Json {
+        filter_branch.stdout(std::io::stderr());
+    }
+    let status = filter_branch
+        .arg("filter-branch")
+        .arg("-f")
+        .arg("-d")
+        .arg(&run_state.temp_dir)
+        .args([
+            "--prune-empty",
+            "--tree-filter",
+            &tree_filter_cmd,
+            &rev_range,
+        ])
+        .env("FILTER_BRANCH_SQUELCH_WARNING", "1")
+        .status()
+        .map_err(|e| format!("failed to run git: {e}"));
+    let report = read_filter_branch_report(&report_file);
+    let _ = fs::remove_file(&report_file);
+    let status = status?;
+    log_git(&["filter-branch", &rev_range], status);
+    if !status.success() {
+        // The state file stays behind so --resume/--abort can clean up.
+        return Err(
+            "git filter-branch failed; run again with --abort to restore the original branch \
+             or --resume to restore it and retry"
+                .to_string(),
+        );
+    }
+    run_state.finish();
+
+    let report = report?;
+    if args.safe_mode {
+        verify_rewrite(args, base, &old_head, &report)?;
+    }
+    if args.annotate_notes {
+        for (original, new) in rewritten_commits(args, base, &old_head)? {
+            let paths: Vec<PathBuf> = report
+                .iter()
+                .find(|(c, _)| *c == original)
+                .map(|(_, paths)| paths.iter().map(PathBuf::from).collect())
+                .unwrap_or_default();
+            if let Some(new) = new
+                && !paths.is_empty()
+            {
+                annotate_commit(&new, &paths, args)?;
+            }
         }
     }
-    if let Some(needle) = &args.author_email {
-        if !email.to_lowercase().contains(&needle.to_lowercase()) {
-            return Ok(false);
+    let modified: Vec<&(String, Vec<String>)> = report
+        .iter()
+        .filter(|(_, paths)| !paths.is_empty())
+        .collect();
+    tally(|t| {
+        t.files += modified.iter().map(|(_, paths)| paths.len()).sum::<usize>();
+        t.commits += modified.len();
+    });
+    if args.format != OutputFormat::Json {
+        println!(
+            "filter-branch processed {} commits, modified {}",
+            report.len(),
+            modified.len()
+        );
+    }
+    for (commit, paths) in args.commit_order.arrange(modified) {
+        if args.format != OutputFormat::Text {
+            for p in paths {
+                let finding = Finding {
+                    path: Path::new(p),
+                    policy: policy_for(Path::new(p), args),
+                    target: "commit",
+                    terminator: None,
+                    applied: true,
+                };
+                report_path(args, &finding, String::new());
+            }
+            continue;
+        }
+        let paths: Vec<String> = paths
+            .iter()
+            .map(|p| display_path(Path::new(p), args))
+            .collect();
+        println!("rewrote {commit}: {}", paths.join(", "));
+    }
+
+    Ok(())
+}
+
+/// How many bytes fixing `paths` removes from `commit`'s blobs. Restoring
+/// a newline removes nothing.
+fn bytes_removed_from_commit(
+    commit: &str,
+    paths: &[PathBuf],
+    args: &Args,
+    oids: &OidCache,
+) -> Result<usize, String> {
+    let mut total = 0;
+    for path in paths {
+        let oid = oids.rev_parse(&format!("{commit}:{}", path.display()))?;
+        let tail = blob_tail_limited(&oid)?;
+        let mut fixed = tail.clone();
+        policy_for(path, args).apply(&mut fixed, args.assume_eol);
+        total += tail.len().saturating_sub(fixed.len());
+    }
+    Ok(total)
+}
+
+/// Prints the `--report-bytes-per-commit` lines, or one object per commit
+/// with `--format json`.
+fn print_byte_totals(args: &Args, totals: Vec<(String, usize, usize)>) {
+    for (commit, bytes, files) in totals {
+        if args.format == OutputFormat::Json {
+            println!("{{\"commit\":\"{commit}\",\"bytes_removed\":{bytes},\"files\":{files}}}");
+            continue;
+        }
+        println!(
+            "{commit} removed {bytes} {} across {files} {}",
+            if bytes == 1 { "byte" } else { "bytes" },
+            if files == 1 { "file" } else { "files" }
+        );
+    }
+}
+
+/// Prints the `--group-by-author` lines, or one object per author with
+/// `--format json`.
+fn print_author_groups(args: &Args, by_author: &BTreeMap<String, (usize, usize)>) {
+    for (email, (files, commits)) in by_author {
+        if args.format == OutputFormat::Json {
+            println!(
+                "{{\"author_email\":{},\"files\":{files},\"commits\":{commits}}}",
+                json_string(email)
+            );
+            continue;
+        }
+        println!(
+            "{email}: {files} {} across {commits} {}",
+            if *files == 1 { "file" } else { "files" },
+            if *commits == 1 { "commit" } else { "commits" }
+        );
+    }
+}
+
+/// A path whose EOF `commits[added]` changed in a way its policy undoes (by
+/// default, gaining a newline) and `commits[removed]` changed back.
+struct Churn {
+    path: PathBuf,
+    added: usize,
+    removed: usize,
+}
+
+fn run_net(args: &Args) -> Result<(), String> {
+    let commits = recent_commits(args)?;
+    let oids = OidCache::default();
+
+    // Path -> index of the commit that made the EOF change its policy undoes
+    // (by default, that added its EOF newline). Such a change is found the
+    // way the other history modes find it, author filters included; the
+    // churn ends with the commit that changes the EOF back.
+    let mut open: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    let mut churns: Vec<Churn> = Vec::new();
+    for (index, commit) in commits.iter().enumerate() {
+        let parent = first_parent_of_commit(commit)?;
+        let matches_filters = commit_matches_filters(commit, args)?;
+        for (path, old_path) in changed_paths_in_commit(commit, args)? {
+            if let Some(&added) = open.get(&path) {
+                let tail = |rev: &str, path: &Path| -> Option<Vec<u8>> {
+                    let oid = oids.rev_parse(&format!("{rev}:{}", path.display())).ok()?;
+                    blob_or_skip(args, path, rev, blob_tail_limited(&oid))
+                };
+                if let (Some(old), Some(new)) = (tail(&parent, &old_path), tail(commit, &path))
+                    && policy_for(&path, args).needs_fix(&new, &old)
+                {
+                    open.remove(&path);
+                    churns.push(Churn {
+                        path,
+                        added,
+                        removed: index,
+                    });
+                }
+            } else if matches_filters
+                && commit_path_needs_fix(args, &oids, &parent, commit, &path, &old_path)
+            {
+                open.insert(path, index);
+            }
+        }
+    }
+
+    for churn in &churns {
+        let finding = Finding {
+            path: &churn.path,
+            policy: policy_for(&churn.path, args),
+            target: "commit",
+            terminator: None,
+            applied: false,
+        };
+        report_path(
+            args,
+            &finding,
+            format!(
+                "net churn: {} (added in {}, removed in {})",
+                display_path(&churn.path, args),
+                commits[churn.added],
+                commits[churn.removed]
+            ),
+        );
+    }
+
+    if !args.fix_intermediate || args.dry_run || churns.is_empty() {
+        return Ok(());
+    }
+
+    ensure_clean_worktree()?;
+    ensure_not_in_rebase()?;
+    save_backup_ref(args, git_output(&["rev-parse", "HEAD"])?.trim())?;
+
+    // Every commit from the one that added the newline up to (not including)
+    // the one that removed it carries it in its tree.
+    let mut lines = String::new();
+    for churn in &churns {
+        for commit in &commits[churn.added..churn.removed] {
+            lines.push_str(&format!("{commit}\t{}\0", churn.path.display()));
         }
     }
-    Ok(true)
-}
+    let strip_list = git_path(&format!(
+        "git-fix-eof-newline-strip-list-{}",
+        std::process::id()
+    ))?;
+    fs::write(&strip_list, lines)
+        .map_err(|e| format!("failed to write strip list {}: {e}", strip_list.display()))?;
 
-fn rev_parse_oid(spec: &str) -> Result<String, String> {
-    Ok(git_output(&["rev-parse", spec])?.trim().to_string())
+    let earliest = churns
+        .iter()
+        .map(|c| c.added)
+        .min()
+        .ok_or_else(|| "internal error: churns is empty".to_string())?;
+    let base = first_parent_of_commit(&commits[earliest])?;
+    let result = rewrite_with_filter_branch(args, &base, Some(&strip_list), None);
+    let _ = fs::remove_file(&strip_list);
+    result
 }
 
-fn blob_bytes_limited(oid: &str) -> Result<Vec<u8>, String> {
-    let size_s = git_output(&["cat-file", "-s", oid])?;
-    let size: u64 = size_s
-        .trim()
-        .parse()
-        .map_err(|_| format!("failed to parse blob size: {}", size_s.trim()))?;
-    if size > 10_000_000 {
-        return Err(format!("blob too large, skipping: {oid} ({size} bytes)"));
+/// `--check-patch`: classifies the hunks of a unified diff, e.g. one about
+/// to be applied with `git apply --cached`, without touching any repository.
+fn run_check_patch(file: &Path) -> Result<(), String> {
+    let bytes = if file == Path::new("-") {
+        let mut buf = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut buf)
+            .map_err(|e| format!("failed to read patch from stdin: {e}"))?;
+        buf
+    } else {
+        fs::read(file).map_err(|e| format!("failed to read patch {}: {e}", file.display()))?
+    };
+    for hunk in patch::hunks(&bytes) {
+        if hunk.adds_eof_newline() {
+            println!("patch: {} ({}) adds an EOF newline", hunk.path, hunk.header);
+        }
     }
-    git_output_bytes(&["cat-file", "-p", oid])
+    Ok(())
 }
 
-fn run_n_gt1(args: &Args) -> Result<(), String> {
-    if args.n == 0 {
-        return Err("internal error: run_n_gt1 received --n 0".to_string());
-    }
-    if args.n == 1 {
-        return run_n1(args);
+fn run_check_message_eof(args: &Args) -> Result<(), String> {
+    let commits = recent_commits(args)?;
+    for commit in args.commit_order.arrange(commits) {
+        if !commit_matches_filters(&commit, args)? {
+            continue;
+        }
+        let message = git_output_bytes(&["log", "-1", "--format=format:%B", &commit])?;
+        let count = count_trailing_newlines(&message);
+        if count > 1 {
+            println!("message eof: {commit} ({} trailing blank lines)", count - 1);
+        }
     }
 
-    ensure_clean_worktree()?;
-    ensure_not_in_rebase()?;
+    Ok(())
+}
 
-    let commits = recent_first_parent_commits(args.n)?;
+fn run_compare_with_origin(args: &Args) -> Result<(), String> {
+    let upstream = rev_parse_oid("@{upstream}")
+        .map_err(|_| "no upstream is configured for the current branch".to_string())?;
+    let changed = paths_from_zbytes(&git_output_bytes(&with_pathspecs(
+        vec!["diff", "--name-only", "--diff-filter=M", "-z", &upstream],
+        &args.pathspecs,
+    ))?);
+    let changed: Vec<PathBuf> = changed
+        .into_iter()
+        .filter(|p| !excluded_path(p, args))
+        .collect();
 
-    let mut needs_fix: Vec<String> = Vec::new();
-    for commit in &commits {
-        if !commit_matches_author_filter(commit, args)? {
-            continue;
+    let total = changed.len();
+    for (done, path) in changed.into_iter().enumerate() {
+        if over_byte_budget(args, done, total, "paths") {
+            break;
         }
-        if commit_has_added_eof_newline(commit)? {
-            needs_fix.push(commit.clone());
+        let old_oid = match rev_parse_oid(&format!("{upstream}:{}", path.display())) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let Some(old_bytes) = blob_or_skip(args, &path, "@{upstream}", blob_tail_limited(&old_oid))
+        else {
+            continue;
+        };
+        let new_bytes = match fs::read(&path) {
+            Ok(b) => b,
+            Err(_) => continue,
+        };
+        if added_eof_newline(&old_bytes, &new_bytes)
+            && binary_skip_reason(args, &path, &new_bytes).is_none()
+        {
+            let finding = Finding {
+                path: &path,
+                policy: Policy::StripOne,
+                target: "worktree",
+                terminator: Some(Policy::StripOne.terminator(args.assume_eol, &new_bytes)),
+                applied: false,
+            };
+            report_path(
+                args,
+                &finding,
+                format!("upstream drift: {}", display_path(&path, args)),
+            );
         }
     }
 
-    if needs_fix.is_empty() {
-        return Ok(());
-    }
+    Ok(())
+}
 
-    let earliest = needs_fix
-        .first()
-        .ok_or_else(|| "internal error: needs_fix is empty".to_string())?;
-    let base = first_parent_of_commit(earliest)?;
+/// `--compare-working-tree-to-index`: finds worktree files that gained an EOF
+/// newline since they were staged, e.g. an editor re-adding it on save.
+fn run_compare_worktree_to_index(args: &Args) -> Result<(), String> {
+    git_output(&["update-index", "-q", "--refresh"])?;
+    let changed = paths_from_zbytes(&git_output_bytes(&with_pathspecs(
+        vec!["diff", "--name-only", "--diff-filter=M", "-z"],
+        &args.pathspecs,
+    ))?);
+    let changed: Vec<PathBuf> = changed
+        .into_iter()
+        .filter(|p| !excluded_path(p, args))
+        .collect();
 
-    if args.dry_run {
-        println!("will run filter-branch starting at base: {base}");
-        for c in needs_fix {
-            println!("n>1 match commit: {c}");
+    let total = changed.len();
+    for (done, path) in changed.into_iter().enumerate() {
+        if over_byte_budget(args, done, total, "paths") {
+            break;
+        }
+        let staged_oid = match rev_parse_oid(&format!(":{}", path.display())) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        let Some(staged_bytes) =
+            blob_or_skip(args, &path, "the index", blob_tail_limited(&staged_oid))
+        else {
+            continue;
+        };
+        let worktree_bytes = match fs::read(&path) {
+            Ok(b) => b,
+            Err(_) => continue,
+        };
+        if added_eof_newline(&staged_bytes, &worktree_bytes)
+            && binary_skip_reason(args, &path, &worktree_bytes).is_none()
+        {
+            let finding = Finding {
+                path: &path,
+                policy: Policy::StripOne,
+                target: "worktree",
+                terminator: Some(Policy::StripOne.terminator(args.assume_eol, &worktree_bytes)),
+                applied: args.fix && !args.dry_run,
+            };
+            report_path(
+                args,
+                &finding,
+                format!("index drift: {}", display_path(&path, args)),
+            );
+            if args.fix && !args.dry_run {
+                fix_worktree_file(&path, Policy::StripOne, args.assume_eol)?;
+            }
         }
-        return Ok(());
-    }
-
-    let tree_filter_cmd = build_filter_branch_tree_filter_command(args)?;
-    let rev_range = format!("{base}..HEAD");
-    let status = Command::new("git")
-        .args([
-            "filter-branch",
-            "-f",
-            "--prune-empty",
-            "--tree-filter",
-            &tree_filter_cmd,
-            &rev_range,
-        ])
-        .env("FILTER_BRANCH_SQUELCH_WARNING", "1")
-        .status()
-        .map_err(|e| format!("failed to run git: {e}"))?;
-    if !status.success() {
-        return Err("git filter-branch failed".to_string());
     }
 
     Ok(())
 }
 
 fn ensure_not_in_rebase() -> Result<(), String> {
-    let rebase_apply = git_output(&["rev-parse", "--git-path", "rebase-apply"])?;
-    let rebase_merge = git_output(&["rev-parse", "--git-path", "rebase-merge"])?;
-    let apply_path = PathBuf::from(rebase_apply.trim());
-    let merge_path = PathBuf::from(rebase_merge.trim());
-    if apply_path.exists() || merge_path.exists() {
+    if git_path("rebase-apply")?.exists() || git_path("rebase-merge")?.exists() {
         return Err("detected an ongoing rebase; refusing to start another rebase".to_string());
     }
     Ok(())
 }
 
-fn recent_first_parent_commits(n: usize) -> Result<Vec<String>, String> {
-    let out = git_output(&["rev-list", "--first-parent", "-n", &n.to_string(), "HEAD"])?;
+/// The commits to scan, oldest first: the last `--n` commits, or those in
+/// `--range`, limited to `--since`/`--until`. The first-parent chain is
+/// walked by default, or every non-merge commit with `full_history`.
+fn recent_commits(args: &Args) -> Result<Vec<String>, String> {
+    let walk = if args.full_history {
+        "--no-merges"
+    } else {
+        "--first-parent"
+    };
+    let n = args.n.to_string();
+    let since = args.since.as_ref().map(|d| format!("--since={d}"));
+    let until = args.until.as_ref().map(|d| format!("--until={d}"));
+    let mut rev_list = vec!["rev-list", walk];
+    rev_list.extend(since.as_deref());
+    rev_list.extend(until.as_deref());
+    match &args.range {
+        Some(range) => rev_list.push(range),
+        None => rev_list.extend(["-n", &n, "HEAD"]),
+    }
+    let out = git_output(&rev_list)?;
     let mut commits: Vec<String> = out
         .lines()
         .filter(|l| !l.trim().is_empty())
         .map(|l| l.trim().to_string())
         .collect();
     commits.reverse();
+    // Only `base..HEAD` is rewritten, so the range has to be part of it.
+    if let (Some(range), Some(newest)) = (&args.range, commits.last())
+        && git_output(&["merge-base", "--is-ancestor", newest, "HEAD"]).is_err()
+    {
+        return Err(format!(
+            "--range {range} must end at HEAD or one of its ancestors"
+        ));
+    }
     Ok(commits)
 }
 
-fn commit_has_added_eof_newline(commit: &str) -> Result<bool, String> {
+fn commit_has_added_eof_newline(
+    commit: &str,
+    args: &Args,
+    oids: &OidCache,
+) -> Result<bool, String> {
     let parent = first_parent_of_commit(commit)?;
-    let changed = changed_paths_in_commit(commit)?;
-    for path in changed {
-        let old_oid = match rev_parse_oid(&format!("{parent}:{}", path.display())) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-        let new_oid = match rev_parse_oid(&format!("{commit}:{}", path.display())) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-        let old_bytes = match blob_bytes_limited(&old_oid) {
-            Ok(b) => b,
-            Err(_) => continue,
-        };
-        let new_bytes = match blob_bytes_limited(&new_oid) {
-            Ok(b) => b,
-            Err(_) => continue,
-        };
-        if added_eof_newline(&old_bytes, &new_bytes) {
-            return Ok(true);
+    let changed = changed_paths_in_commit(commit, args)?;
+    Ok(changed
+        .iter()
+        .any(|(path, old_path)| commit_path_needs_fix(args, oids, &parent, commit, path, old_path)))
+}
+
+fn commit_paths_needing_fix(
+    commit: &str,
+    args: &Args,
+    oids: &OidCache,
+) -> Result<Vec<PathBuf>, String> {
+    let parent = first_parent_of_commit(commit)?;
+    let changed = changed_paths_in_commit(commit, args)?;
+    Ok(changed
+        .into_iter()
+        .filter(|(path, old_path)| {
+            commit_path_needs_fix(args, oids, &parent, commit, path, old_path)
+        })
+        .map(|(path, _)| path)
+        .collect())
+}
+
+/// `--diff`: prints what fixing `bytes` (the content of `path` that would
+/// be fixed) changes, as a unified diff.
+fn print_eof_diff(path: &Path, policy: Policy, eol: Option<Eol>, bytes: Vec<u8>) {
+    let mut fixed = bytes.clone();
+    policy.apply(&mut fixed, eol);
+    let path = path.as_os_str().to_string_lossy();
+    print!("{}", patch::eof_diff(&path, &bytes, &fixed));
+}
+
+/// `print_eof_diff` for each path `commit` would have fixed, using the
+/// blobs the commit recorded.
+fn print_commit_diffs(commit: &str, args: &Args, oids: &OidCache) -> Result<(), String> {
+    let parent = first_parent_of_commit(commit)?;
+    let changed = changed_paths_in_commit(commit, args)?;
+    for path in commit_paths_needing_fix(commit, args, oids)? {
+        let oid = oids.rev_parse(&format!("{commit}:{}", path.display()))?;
+        let bytes = blob_bytes_limited(&oid)?;
+        let policy = policy_for(&path, args);
+        let old_tail = parent_eof_tail(args, oids, &parent, &changed[&path]).unwrap_or_default();
+        print_eof_diff(&path, policy, policy.eol(args, &old_tail), bytes);
+    }
+    Ok(())
+}
+
+/// The tail of `path` at `parent` before a commit changed it. A path the
+/// parent does not have is treated as empty under `--include-added`.
+fn parent_eof_tail(args: &Args, oids: &OidCache, parent: &str, path: &Path) -> Option<Vec<u8>> {
+    match oids.rev_parse(&format!("{parent}:{}", path.display())) {
+        Ok(oid) => blob_or_skip(args, path, parent, blob_tail_limited(&oid)),
+        Err(_) if args.include_added => Some(Vec::new()),
+        Err(_) => None,
+    }
+}
+
+/// Whether `path` itself (not what it points to) is a symlink.
+fn is_worktree_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// Whether `path` is a symlink in `rev`'s tree. Its blob is the link
+/// target's name, not file content, so it is never fixed.
+fn is_symlink_in_tree(rev: &str, path: &Path) -> bool {
+    let path = path.as_os_str().to_string_lossy();
+    git_output(&["ls-tree", "-z", "--full-tree", rev, "--", &path])
+        .is_ok_and(|out| out.starts_with("120000 "))
+}
+
+/// The result of reading one of `path`'s blobs at `rev`, or `None` after
+/// warning that the path is skipped (e.g. it is over `--max-blob-size`).
+fn blob_or_skip<T>(args: &Args, path: &Path, rev: &str, read: Result<T, String>) -> Option<T> {
+    match read {
+        Ok(v) => Some(v),
+        Err(e) => {
+            let reason = if e.contains("--max-blob-size") {
+                "large"
+            } else {
+                "unreadable"
+            };
+            skip(
+                reason,
+                format!("skipping {} at {rev}: {e}", display_path(path, args)),
+            );
+            None
         }
     }
-    Ok(false)
 }
 
-fn first_parent_of_commit(commit: &str) -> Result<String, String> {
-    let out = git_output(&["rev-list", "--parents", "-n", "1", commit])?;
-    let parts: Vec<&str> = out.split_whitespace().collect();
-    if parts.len() < 2 {
-        return Err(format!("{commit} has no parent"));
+/// Whether `commit` changed the EOF of `path` (`old_path` in `parent`) in a
+/// way its policy says to undo. Paths that cannot be read are never fixed.
+fn commit_path_needs_fix(
+    args: &Args,
+    oids: &OidCache,
+    parent: &str,
+    commit: &str,
+    path: &Path,
+    old_path: &Path,
+) -> bool {
+    let Some(old_bytes) = parent_eof_tail(args, oids, parent, old_path) else {
+        return false;
+    };
+    let new_oid = match oids.rev_parse(&format!("{commit}:{}", path.display())) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    let Some((new_head, new_bytes)) = blob_or_skip(args, path, commit, blob_ends_limited(&new_oid))
+    else {
+        return false;
+    };
+    let shown = display_path(path, args);
+    let policy = policy_for(path, args);
+    if !policy.needs_fix(&old_bytes, &new_bytes) {
+        trace(format!("no change: {shown} at {commit}"));
+        return false;
+    }
+    if let Some(reason) = binary_skip_reason(args, path, &new_head) {
+        let message = format!("skipping binary file ({reason}): {shown} at {commit}");
+        tally_skip("binary", &message);
+        trace(message);
+        return false;
     }
-    if parts.len() > 2 {
-        return Err(format!("{commit} is a merge commit; not supported"));
+    if is_symlink_in_tree(commit, path) {
+        let message = format!("skipping symlink: {shown} at {commit}");
+        tally_skip("symlink", &message);
+        trace(message);
+        return false;
+    }
+    if policy == Policy::StripOne && args.last_line_pattern.is_some() {
+        let full = blob_or_skip(args, path, commit, blob_bytes_limited(&new_oid));
+        if !full.is_some_and(|full| last_line_allows_strip(args, policy, &full)) {
+            trace(format!("last line does not match: {shown} at {commit}"));
+            return false;
+        }
+    }
+    trace(format!("matched: {shown} at {commit}"));
+    true
+}
+
+/// Why `path` is left alone as binary, if it is: `.gitattributes` marks it
+/// `-text` (or `binary`), or it is not marked `text`, its new content looks
+/// binary and `--include-binary` was not given. `head` needs only the first
+/// `BINARY_CHECK_LEN` bytes.
+fn binary_skip_reason(args: &Args, path: &Path, head: &[u8]) -> Option<&'static str> {
+    let reason = match text_attribute(path) {
+        Some(true) => return None,
+        Some(false) => "marked -text in .gitattributes",
+        None if !args.include_binary && looks_binary(head) => "pass --include-binary to fix it",
+        None => return None,
+    };
+    log(format!("skip binary ({reason}): {}", path.display()));
+    Some(reason)
+}
+
+/// `text` attributes looked up so far, by path.
+static TEXT_ATTRIBUTES: Mutex<BTreeMap<PathBuf, Option<bool>>> = Mutex::new(BTreeMap::new());
+
+/// The `text` attribute git assigns to `path`: `Some(true)` if set,
+/// `Some(false)` if unset (`-text`, `binary`), `None` if unspecified or
+/// `auto`. Lookups are cached for the rest of the process.
+fn text_attribute(path: &Path) -> Option<bool> {
+    let mut cache = TEXT_ATTRIBUTES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(value) = cache.get(path) {
+        return *value;
+    }
+    let mut input = path.as_os_str().to_string_lossy().into_owned().into_bytes();
+    input.push(0);
+    let value = match git_output_bytes_with_stdin(&["check-attr", "-z", "--stdin", "text"], &input)
+    {
+        // `<path>\0text\0<value>\0`
+        Ok(out) => match out.split(|b| *b == 0).nth(2) {
+            Some(b"set") => Some(true),
+            Some(b"unset") => Some(false),
+            _ => None,
+        },
+        Err(e) => {
+            log(format!("check-attr failed for {}: {e}", path.display()));
+            None
+        }
+    };
+    cache.insert(path.to_path_buf(), value);
+    value
+}
+
+/// Applies `--strip-only-if-last-line-matches` to the full new content.
+/// The pattern only restricts stripping, never `require-one` paths.
+fn last_line_allows_strip(args: &Args, policy: Policy, new_bytes: &[u8]) -> bool {
+    match &args.last_line_pattern {
+        Some(re) if policy == Policy::StripOne => {
+            re.is_match(&String::from_utf8_lossy(last_non_empty_line(new_bytes)))
+        }
+        _ => true,
     }
-    Ok(parts[1].to_string())
 }
 
-fn build_filter_branch_tree_filter_command(args: &Args) -> Result<String, String> {
+/// The parent `commit` is compared against; for a merge, the branch it was
+/// merged into.
+fn first_parent_of_commit(commit: &str) -> Result<String, String> {
+    let out = git_output(&["rev-list", "--parents", "-n", "1", commit])?;
+    out.split_whitespace()
+        .nth(1)
+        .map(str::to_string)
+        .ok_or_else(|| format!("{commit} has no parent"))
+}
+
+fn build_filter_branch_tree_filter_command(
+    args: &Args,
+    report_file: &Path,
+    strip_list: Option<&Path>,
+    only_commits: Option<&Path>,
+) -> Result<String, String> {
     let exe =
         std::env::current_exe().map_err(|e| format!("failed to locate current executable: {e}"))?;
-    let exe_s = exe.to_string_lossy().to_string();
-    let mut parts: Vec<String> = Vec::new();
-    parts.push(sh_quote(&exe_s));
-    parts.push("--in-filter-branch".to_string());
-    parts.push("--n".to_string());
-    parts.push("1".to_string());
+    let mut parts: Vec<String> = vec![
+        sh_path(&exe),
+        "--in-filter-branch".to_string(),
+        "--n".to_string(),
+        "1".to_string(),
+        "--report-file".to_string(),
+        sh_path(report_file),
+    ];
 
+    if let Some(strip_list) = strip_list {
+        parts.push("--strip-list".to_string());
+        parts.push(sh_path(strip_list));
+    }
+    if let Some(only_commits) = only_commits {
+        parts.push("--only-commits".to_string());
+        parts.push(sh_path(only_commits));
+    }
+    parts.extend(forwarded_fix_flags(args));
     if let Some(v) = &args.author_name {
         parts.push("--author-name".to_string());
         parts.push(sh_quote(v));
@@ -615,10 +5351,108 @@ fn build_filter_branch_tree_filter_command(args: &Args) -> Result<String, String
         parts.push("--author-email".to_string());
         parts.push(sh_quote(v));
     }
+    if let Some(re) = &args.author_name_regex {
+        parts.push("--author-name-regex".to_string());
+        parts.push(sh_quote(re.as_str()));
+    }
+    if let Some(re) = &args.author_email_regex {
+        parts.push("--author-email-regex".to_string());
+        parts.push(sh_quote(re.as_str()));
+    }
+    if let Some(v) = &args.committer_name {
+        parts.push("--committer-name".to_string());
+        parts.push(sh_quote(v));
+    }
+    if let Some(v) = &args.committer_email {
+        parts.push("--committer-email".to_string());
+        parts.push(sh_quote(v));
+    }
 
     Ok(parts.join(" "))
 }
 
+/// The options that change how a single commit is fixed, shell-quoted for
+/// the `--n 1` child of a history rewrite.
+fn forwarded_fix_flags(args: &Args) -> Vec<String> {
+    let mut parts = Vec::new();
+    // The child's stderr is filter-branch's, so `auto` decides the same.
+    match args.color {
+        ColorChoice::Always => parts.extend(["--color".to_string(), "always".to_string()]),
+        ColorChoice::Never => parts.extend(["--color".to_string(), "never".to_string()]),
+        ColorChoice::Auto => {}
+    }
+    if let Some(log_file) = &args.log_file {
+        parts.push("--log-file".to_string());
+        parts.push(sh_path(log_file));
+    }
+    match args.assume_eol {
+        Some(Eol::Lf) => parts.push("--assume-lf".to_string()),
+        Some(Eol::CrLf) => parts.push("--assume-crlf".to_string()),
+        None => {}
+    }
+    if let Some(re) = &args.last_line_pattern {
+        parts.push("--strip-only-if-last-line-matches".to_string());
+        parts.push(sh_quote(re.as_str()));
+    }
+    if args.full_history {
+        parts.push("--full-history".to_string());
+    }
+    if args.restore {
+        parts.push("--restore".to_string());
+    }
+    if args.include_added {
+        parts.push("--include-added".to_string());
+    }
+    if args.include_binary {
+        parts.push("--include-binary".to_string());
+    }
+    if args.max_blob_size != DEFAULT_MAX_BLOB_SIZE {
+        parts.push("--max-blob-size".to_string());
+        parts.push(args.max_blob_size.to_string());
+    }
+    match args.verbosity {
+        0 => parts.push("--quiet".to_string()),
+        2 => parts.push("--verbose".to_string()),
+        _ => {}
+    }
+    for glob in &args.require_newline_globs {
+        parts.push("--require-eof-newline-for".to_string());
+        parts.push(sh_quote(glob.as_str()));
+    }
+    for spec in &args.pathspecs {
+        parts.push("--pathspec".to_string());
+        parts.push(sh_quote(spec));
+    }
+    for glob in &args.include_globs {
+        parts.push("--include".to_string());
+        parts.push(sh_quote(glob.as_str()));
+    }
+    for glob in &args.exclude_globs {
+        parts.push("--exclude".to_string());
+        parts.push(sh_quote(glob.as_str()));
+    }
+    if !args.exclude_extensions.is_empty() {
+        let list: Vec<&str> = args.exclude_extensions.iter().map(String::as_str).collect();
+        parts.push("--exclude-extension".to_string());
+        parts.push(sh_quote(&list.join(",")));
+    }
+    parts
+}
+
+/// `path` quoted for the `sh` that runs hooks, tree-filters and rebase
+/// `exec` lines. On Windows that is Git for Windows' MSYS shell, whose tools
+/// expect `C:/dir/file`: a `\\?\` prefix is dropped and the separators
+/// become forward slashes.
+fn sh_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        let path = path.strip_prefix(r"\\?\").unwrap_or(&path);
+        sh_quote(&path.replace('\\', "/"))
+    } else {
+        sh_quote(&path)
+    }
+}
+
 fn sh_quote(s: &str) -> String {
     if s.is_empty() {
         return "''".to_string();
@@ -634,3 +5468,415 @@ fn sh_quote(s: &str) -> String {
     out.push('\'');
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_status_records_handle_renames() {
+        let records = name_status_records(b"M\0a.txt\0R087\0old name\0new name\0D\0gone\0");
+        assert_eq!(
+            records,
+            vec![
+                DiffRecord {
+                    status: 'M',
+                    old: PathBuf::from("a.txt"),
+                    new: PathBuf::from("a.txt"),
+                },
+                DiffRecord {
+                    status: 'R',
+                    old: PathBuf::from("old name"),
+                    new: PathBuf::from("new name"),
+                },
+                DiffRecord {
+                    status: 'D',
+                    old: PathBuf::from("gone"),
+                    new: PathBuf::from("gone"),
+                },
+            ]
+        );
+
+        let fixable = fixable_paths(&records);
+        assert_eq!(fixable.len(), 2);
+        assert_eq!(fixable[Path::new("new name")], PathBuf::from("old name"));
+    }
+
+    #[test]
+    fn name_status_records_handle_copies() {
+        let records = name_status_records(b"C075\0src.txt\0copy.txt\0M\0src.txt\0");
+        assert_eq!(records[0].status, 'C');
+        assert_eq!(records[0].old, PathBuf::from("src.txt"));
+        assert_eq!(records[0].new, PathBuf::from("copy.txt"));
+        let fixable = fixable_paths(&records);
+        assert_eq!(fixable[Path::new("copy.txt")], PathBuf::from("src.txt"));
+        assert_eq!(fixable[Path::new("src.txt")], PathBuf::from("src.txt"));
+    }
+
+    /// `parse_args` without any `fixeof.*` git config.
+    fn parse_args(argv: Vec<std::ffi::OsString>) -> Result<Args, String> {
+        parse_args_with_config(argv, |_| None)
+    }
+
+    fn argv(args: &[&str]) -> Vec<std::ffi::OsString> {
+        std::iter::once("git-fix-eof-newline")
+            .chain(args.iter().copied())
+            .map(Into::into)
+            .collect()
+    }
+
+    #[test]
+    fn parse_args_config_defaults() {
+        let config = |key: &str| match key {
+            "fixeof.n" => Some("3".to_string()),
+            "fixeof.maxBlobSize" => Some("1k".to_string()),
+            _ => None,
+        };
+        let args = parse_args_with_config(argv(&[]), config).unwrap();
+        assert_eq!((args.n, args.max_blob_size), (3, 1024));
+        let args = parse_args_with_config(argv(&["--n", "0"]), config).unwrap();
+        assert_eq!(args.n, 0);
+        assert_eq!(
+            parse_args_with_config(argv(&[]), |_| Some("x".to_string())).unwrap_err(),
+            "invalid fixeof.n value in git config: x"
+        );
+    }
+
+    #[test]
+    fn parse_args_in_rebase() {
+        let args = parse_args(argv(&["--n", "1"])).unwrap();
+        assert!(!args.in_rebase);
+
+        let args = parse_args(argv(&["--n", "1", "--in-rebase"])).unwrap();
+        assert!(args.in_rebase);
+        assert_eq!(args.n, 1);
+        assert!(check_mode_combinations(&args).is_ok());
+
+        let args = parse_args(argv(&["--n", "0", "--in-rebase"])).unwrap();
+        assert!(check_mode_combinations(&args).is_err());
+    }
+
+    #[test]
+    fn parse_args_pathspecs_after_double_dash() {
+        let args = parse_args(argv(&[
+            "--pathspec",
+            "docs",
+            "--n",
+            "0",
+            "--",
+            "src/",
+            "--n",
+        ]))
+        .unwrap();
+        assert_eq!(args.n, 0);
+        assert_eq!(args.pathspecs, ["docs", "src/", "--n"]);
+
+        let args = parse_args(argv(&["--"])).unwrap();
+        assert!(args.pathspecs.is_empty());
+    }
+
+    #[test]
+    fn parse_args_author_regex() {
+        let args = parse_args(argv(&["--author-email-regex", "^(ci-bot|release-bot)@"])).unwrap();
+        let re = args.author_email_regex.unwrap();
+        assert!(re.is_match("ci-bot@example.com"));
+        assert!(!re.is_match("alice@example.com"));
+
+        let err = parse_args(argv(&["--author-name-regex", "(alice"])).unwrap_err();
+        assert!(
+            err.starts_with("--author-name-regex: invalid regex \"(alice\""),
+            "{err}"
+        );
+        let err = parse_args(argv(&["--author-email-regex"])).unwrap_err();
+        assert_eq!(err, "--author-email-regex requires a regex");
+    }
+
+    #[test]
+    fn parse_args_last_line_pattern() {
+        let args = parse_args(argv(&["--strip-only-if-last-line-matches", "^# vim:"])).unwrap();
+        assert!(args.last_line_pattern.unwrap().is_match("# vim: set ft=sh"));
+
+        let err = parse_args(argv(&["--strip-only-if-last-line-matches", "[abc"])).unwrap_err();
+        assert!(
+            err.starts_with("--strip-only-if-last-line-matches: invalid regex \"[abc\""),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn parse_args_range() {
+        let args = parse_args(argv(&["--range", "origin/main..HEAD"])).unwrap();
+        assert_eq!(args.range.as_deref(), Some("origin/main..HEAD"));
+        assert!(args.n > 1);
+
+        let err = parse_args(argv(&["--range", "a..b", "--n", "3"])).unwrap_err();
+        assert_eq!(err, "--range cannot be combined with --n");
+        assert!(parse_args(argv(&["--range", "a..b", "--workdir-only"])).is_err());
+    }
+
+    #[test]
+    fn commit_paths_never_include_deletions() {
+        let records = name_status_records(b"D\0gone.txt\0A\0new.txt\0M\0kept.txt\0");
+        let args = parse_args(argv(&["--n", "2", "--include-added"])).unwrap();
+        let paths: Vec<PathBuf> = commit_paths(&records, &args).into_keys().collect();
+        assert_eq!(paths, [PathBuf::from("kept.txt"), PathBuf::from("new.txt")]);
+    }
+
+    #[test]
+    fn oid_cache_looks_up_commit_specs_once() {
+        let oids = OidCache::default();
+        let lookups = std::cell::Cell::new(0);
+        let lookup = |spec: &str| {
+            lookups.set(lookups.get() + 1);
+            if spec.ends_with("missing") {
+                Err(format!("no {spec}"))
+            } else {
+                Ok(format!("oid of {spec}"))
+            }
+        };
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        let spec = format!("{commit}:a.txt");
+        assert_eq!(
+            oids.get_or_lookup(&spec, lookup).unwrap(),
+            format!("oid of {spec}")
+        );
+        assert_eq!(
+            oids.get_or_lookup(&spec, lookup).unwrap(),
+            format!("oid of {spec}")
+        );
+        assert_eq!(lookups.get(), 1);
+
+        // Failures are cached too.
+        let missing = format!("{commit}:missing");
+        assert!(oids.get_or_lookup(&missing, lookup).is_err());
+        assert!(oids.get_or_lookup(&missing, lookup).is_err());
+        assert_eq!(lookups.get(), 2);
+
+        // Specs that can change during a run are always looked up.
+        for spec in ["HEAD:a.txt", ":a.txt", "HEAD:a.txt"] {
+            oids.get_or_lookup(spec, lookup).unwrap();
+        }
+        assert_eq!(lookups.get(), 5);
+    }
+
+    #[test]
+    fn parse_args_jobs() {
+        assert_eq!(parse_args(argv(&["--n", "5"])).unwrap().jobs, 0);
+        assert_eq!(
+            parse_args(argv(&["--n", "5", "--jobs", "4"])).unwrap().jobs,
+            4
+        );
+        assert!(parse_args(argv(&["--n", "5", "--jobs", "0"])).is_err());
+        let args = parse_args(argv(&["--n", "1", "--jobs", "2"])).unwrap();
+        assert_eq!(
+            check_mode_combinations(&args).unwrap_err(),
+            "--jobs can only be used with --n > 1"
+        );
+    }
+
+    #[test]
+    fn parse_args_positional_files_and_subcommands() {
+        let args = parse_args(argv(&["--n", "0", "a.txt", "./src/b.txt"])).unwrap();
+        assert_eq!(
+            args.files,
+            [PathBuf::from("a.txt"), PathBuf::from("src/b.txt")]
+        );
+        assert!(check_mode_combinations(&args).is_ok());
+        let args = parse_args(argv(&["a.txt"])).unwrap();
+        assert!(check_mode_combinations(&args).is_err());
+
+        assert!(
+            parse_args(argv(&["print-precommit-hook"]))
+                .unwrap()
+                .print_precommit_hook
+        );
+        // A subcommand name anywhere else is just a file.
+        let args = parse_args(argv(&["--n", "0", "install-hook"])).unwrap();
+        assert!(!args.install_hook);
+        assert_eq!(args.files, [PathBuf::from("install-hook")]);
+        assert!(precommit_hook_entry().contains("entry: git-fix-eof-newline --n 0\n"));
+    }
+
+    #[test]
+    fn parse_args_verbosity() {
+        assert_eq!(parse_args(argv(&[])).unwrap().verbosity, 1);
+        assert_eq!(parse_args(argv(&["--quiet"])).unwrap().verbosity, 0);
+        assert_eq!(parse_args(argv(&["--verbose"])).unwrap().verbosity, 2);
+        // The last one given wins.
+        let args = parse_args(argv(&["--verbose", "--n", "0", "--quiet"])).unwrap();
+        assert_eq!(args.verbosity, 0);
+        assert_eq!(args.n, 0);
+    }
+
+    #[test]
+    fn parse_size_cases() {
+        assert_eq!(parse_size("100"), Some(100));
+        assert_eq!(parse_size("4k"), Some(4096));
+        assert_eq!(parse_size("2M"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Some(1 << 30));
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("12T"), None);
+        assert_eq!(parse_size("-1"), None);
+    }
+
+    #[test]
+    fn parse_git_version_cases() {
+        assert_eq!(parse_git_version("git version 2.39.2"), Some((2, 39, 2)));
+        assert_eq!(parse_git_version("git version 2.39.2\n"), Some((2, 39, 2)));
+        assert_eq!(
+            parse_git_version("git version 2.39.2 (Apple Git-143)"),
+            Some((2, 39, 2))
+        );
+        assert_eq!(
+            parse_git_version("git version 2.41.0.windows.1"),
+            Some((2, 41, 0))
+        );
+        assert_eq!(
+            parse_git_version("git version 2.45.0-rc1"),
+            Some((2, 45, 0))
+        );
+        assert_eq!(parse_git_version("git version 3.0"), Some((3, 0, 0)));
+        assert_eq!(parse_git_version("hg version 6.1"), None);
+        assert_eq!(parse_git_version("git version"), None);
+    }
+
+    #[test]
+    fn git_version_requirements() {
+        assert!(git_version_supports((2, 13, 0), "--autostash", AUTOSTASH_GIT_VERSION).is_ok());
+        assert!(git_version_supports((3, 0, 0), "--autostash", AUTOSTASH_GIT_VERSION).is_ok());
+        assert_eq!(
+            git_version_supports((2, 11, 4), "--autostash", AUTOSTASH_GIT_VERSION).unwrap_err(),
+            "--autostash needs git 2.13.0 or newer; found git 2.11.4"
+        );
+    }
+
+    #[test]
+    fn file_abridged_keeps_only_the_ends() {
+        let dir = std::env::temp_dir().join(format!("fix-eof-abridged-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let short = dir.join("short");
+        fs::write(&short, b"abc\n").unwrap();
+        assert_eq!(file_abridged(&short).unwrap(), b"abc\n");
+
+        let long = dir.join("long");
+        let mut content = vec![b'x'; BINARY_CHECK_LEN + 100];
+        content.extend_from_slice(b"yz\n");
+        fs::write(&long, &content).unwrap();
+        let ends = file_abridged(&long).unwrap();
+        assert_eq!(ends.len(), BINARY_CHECK_LEN + EOF_TAIL_LEN);
+        assert!(ends.ends_with(b"z\n"));
+        assert_eq!(abridged(content[..BINARY_CHECK_LEN].to_vec(), b"z\n"), ends);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hook_block_is_inserted_once_and_removed_cleanly() {
+        let block = format!("{HOOK_BLOCK_START}\nfix --n 0 || exit $?\n{HOOK_BLOCK_END}\n");
+        let new = with_hook_block(None, &block).unwrap();
+        assert_eq!(new, format!("#!/bin/sh\n{block}"));
+        assert_eq!(with_hook_block(Some(&new), &block).unwrap(), new);
+
+        let foreign = "#!/bin/bash\nmake lint\nexit 0\n";
+        let merged = with_hook_block(Some(foreign), &block).unwrap();
+        assert_eq!(merged, format!("#!/bin/bash\n{block}make lint\nexit 0\n"));
+        assert_eq!(with_hook_block(Some(&merged), &block).unwrap(), merged);
+        assert_eq!(without_hook_block(&merged).as_deref(), Some(foreign));
+        assert_eq!(without_hook_block(foreign), None);
+
+        let legacy = format!("#!/bin/sh\n{HOOK_MARKER}\nexec fix --n 0\n");
+        assert_eq!(with_hook_block(Some(&legacy), &block).unwrap(), new);
+        assert!(with_hook_block(Some("#!/usr/bin/env python3\n"), &block).is_err());
+    }
+
+    #[test]
+    fn scan_progress_line_counts() {
+        assert_eq!(
+            scan_progress_line(0, 500, 0),
+            "scanned 0/500 commits, 0 need fixing"
+        );
+        assert_eq!(
+            scan_progress_line(37, 500, 2),
+            "scanned 37/500 commits, 2 need fixing"
+        );
+    }
+
+    #[test]
+    fn tally_line_counts_fixes_and_skips() {
+        let mut tally = Tally {
+            files: 3,
+            commits: 2,
+            unchanged: 0,
+            skipped: BTreeMap::new(),
+        };
+        assert_eq!(tally.line(false, true), "fixed 3 files across 2 commits");
+        tally_skipped(&mut tally, "binary", "skipping binary file: a.bin");
+        // The same skip seen again (a commit scanned twice) counts once.
+        tally_skipped(&mut tally, "binary", "skipping binary file: a.bin");
+        assert_eq!(
+            tally.line(false, true),
+            "fixed 3 files across 2 commits (1 skipped: binary)"
+        );
+        tally_skipped(&mut tally, "large", "skipping big.txt at HEAD: too big");
+        tally.files = 1;
+        tally.unchanged = 4;
+        assert_eq!(
+            tally.line(true, false),
+            "would fix 1 file (4 unchanged, 2 skipped: 1 binary, 1 large)"
+        );
+    }
+
+    fn tally_skipped(tally: &mut Tally, reason: &'static str, message: &str) {
+        tally
+            .skipped
+            .entry(reason)
+            .or_default()
+            .insert(message.to_string());
+    }
+
+    #[test]
+    fn interactive_needs_a_rewriting_mode() {
+        let check = |a: &[&str]| check_mode_combinations(&parse_args(argv(a)).unwrap());
+        assert!(check(&["--n", "3", "--interactive", "--yes"]).is_ok());
+        assert_eq!(
+            check(&["--n", "0", "--interactive"]).unwrap_err(),
+            "--interactive requires --n >= 1"
+        );
+        assert_eq!(
+            check(&["--n", "1", "--yes"]).unwrap_err(),
+            "--yes can only be used with --interactive"
+        );
+    }
+
+    #[test]
+    fn confirmation_needs_an_explicit_yes() {
+        let answer = |input: &[u8]| read_confirmation(&mut &input[..]).unwrap();
+        assert!(answer(b"y\n"));
+        assert!(answer(b" Y \n"));
+        assert!(!answer(b"n\n"));
+        assert!(!answer(b"yes\n"));
+        assert!(!answer(b"\n"));
+        assert!(!answer(b""));
+    }
+
+    #[test]
+    fn pathspecs_from_a_subdirectory() {
+        assert_eq!(pathspec_under("sub/", "a.txt"), "sub/a.txt");
+        assert_eq!(
+            pathspec_under("sub/", ":(glob)**/*.md"),
+            ":(glob)sub/**/*.md"
+        );
+        assert_eq!(pathspec_under("sub/", ":!gen"), ":!sub/gen");
+        assert_eq!(pathspec_under("sub/", ":^:gen"), ":^sub/gen");
+        assert_eq!(pathspec_under("sub/", ":/docs"), ":/docs");
+        assert_eq!(pathspec_under("sub/", ":(top,glob)*.md"), ":(top,glob)*.md");
+        assert_eq!(
+            normalize_repo_path(Path::new("sub/deeper/../../a.txt")),
+            PathBuf::from("a.txt")
+        );
+        assert_eq!(
+            normalize_repo_path(Path::new("sub/./b.txt")),
+            PathBuf::from("sub/b.txt")
+        );
+    }
+}
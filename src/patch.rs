@@ -0,0 +1,339 @@
+//! Reading unified diffs (as produced by `git diff`/`git format-patch`),
+//! and writing the small ones an EOF fix makes.
+//!
+//! Each hunk is turned back into its pre- and post-image so the same EOF
+//! predicates used on blobs can classify it.
+
+use crate::added_eof_newline;
+
+/// One `@@` hunk of a unified diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// The post-image path (the pre-image path for deletions), without the
+    /// `a/`/`b/` prefix.
+    pub path: String,
+    /// The `@@ -a,b +c,d @@` line, without any section heading after it.
+    pub header: String,
+    /// The hunk's lines as they were before the change.
+    pub old: Vec<u8>,
+    /// The hunk's lines as they are after the change.
+    pub new: Vec<u8>,
+}
+
+impl Hunk {
+    /// Whether the hunk turns a file without a final newline into one with
+    /// it, i.e. its old side ends in `\ No newline at end of file` and its
+    /// new side does not.
+    pub fn adds_eof_newline(&self) -> bool {
+        added_eof_newline(&self.old, &self.new)
+    }
+}
+
+/// Splits a unified diff into hunks. Lines outside of hunks (commit
+/// messages, `diff --git`/`index` headers) are ignored.
+///
+/// The line counts in each `@@` header decide where a hunk ends, so removed
+/// lines that happen to start with `-- ` are not mistaken for file headers.
+pub fn hunks(patch: &[u8]) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut old_path: Option<String> = None;
+    let mut path: Option<String> = None;
+    // Lines still expected on each side of the current hunk.
+    let mut remaining = (0usize, 0usize);
+    // Which sides the previous hunk line belonged to, for `\ No newline`.
+    let mut last_sides = (false, false);
+
+    let mut lines: Vec<&[u8]> = patch.split(|b| *b == b'\n').collect();
+    if lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    for line in lines {
+        if let Some(hunk) = hunks.last_mut() {
+            if line.first() == Some(&b'\\') {
+                if last_sides.0 {
+                    hunk.old.pop();
+                }
+                if last_sides.1 {
+                    hunk.new.pop();
+                }
+                last_sides = (false, false);
+                continue;
+            }
+            if remaining != (0, 0) {
+                // An empty line is a context line whose space was trimmed.
+                let (old, new) = match line.first() {
+                    Some(b'-') => (true, false),
+                    Some(b'+') => (false, true),
+                    _ => (true, true),
+                };
+                let text = line.get(1..).unwrap_or(b"");
+                if old {
+                    push_line(&mut hunk.old, text);
+                    remaining.0 = remaining.0.saturating_sub(1);
+                }
+                if new {
+                    push_line(&mut hunk.new, text);
+                    remaining.1 = remaining.1.saturating_sub(1);
+                }
+                last_sides = (old, new);
+                continue;
+            }
+        }
+        last_sides = (false, false);
+        if let Some(rest) = line.strip_prefix(b"--- ") {
+            old_path = file_path(rest);
+        } else if let Some(rest) = line.strip_prefix(b"+++ ") {
+            path = file_path(rest).or_else(|| old_path.clone());
+        } else if line.starts_with(b"@@ ")
+            && let Some((header, counts)) = hunk_header(line)
+        {
+            remaining = counts;
+            hunks.push(Hunk {
+                path: path.clone().unwrap_or_default(),
+                header,
+                old: Vec::new(),
+                new: Vec::new(),
+            });
+        }
+    }
+    hunks
+}
+
+/// Parses `@@ -a[,b] +c[,d] @@ ...` into the bare header and the old/new
+/// line counts (a missing count means 1).
+fn hunk_header(line: &[u8]) -> Option<(String, (usize, usize))> {
+    let line = String::from_utf8_lossy(line);
+    let inner = line.strip_prefix("@@ ")?;
+    let end = inner.find(" @@")?;
+    let mut ranges = inner[..end].split(' ');
+    let count = |range: Option<&str>, sign: char| -> Option<usize> {
+        let range = range?.strip_prefix(sign)?;
+        match range.split_once(',') {
+            Some((_, count)) => count.parse().ok(),
+            None => range.parse::<usize>().ok().map(|_| 1),
+        }
+    };
+    let old = count(ranges.next(), '-')?;
+    let new = count(ranges.next(), '+')?;
+    Some((format!("@@ {} @@", &inner[..end]), (old, new)))
+}
+
+fn push_line(side: &mut Vec<u8>, line: &[u8]) {
+    side.extend_from_slice(line);
+    side.push(b'\n');
+}
+
+/// The path from a `---`/`+++` line, or `None` for `/dev/null`.
+fn file_path(rest: &[u8]) -> Option<String> {
+    let rest = String::from_utf8_lossy(rest);
+    // `git diff` appends a tab and timestamp for some non-git inputs.
+    let rest = rest.split('\t').next().unwrap_or("").trim_end();
+    if rest == "/dev/null" {
+        return None;
+    }
+    let path = rest
+        .strip_prefix("a/")
+        .or_else(|| rest.strip_prefix("b/"))
+        .unwrap_or(rest);
+    Some(path.to_string())
+}
+
+/// Lines of context `eof_diff` shows before a change, as `git diff` does.
+const CONTEXT_LINES: usize = 3;
+
+/// A unified diff of `path` going from `old` to `new`, which only differ at
+/// their ends (as after an EOF fix). The result is empty if they are equal.
+///
+/// A side that stops without a newline gets the `\ No newline at end of
+/// file` marker after its last line, so the diff applies with `git apply`.
+pub fn eof_diff(path: &str, old: &[u8], new: &[u8]) -> String {
+    if old == new {
+        return String::new();
+    }
+    let common = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    // The hunk starts with the line the first difference is in.
+    let start = line_start(old, common);
+    let mut context = start;
+    for _ in 0..CONTEXT_LINES {
+        if context == 0 {
+            break;
+        }
+        context = line_start(old, context - 1);
+    }
+    let first_line = old[..context].iter().filter(|b| **b == b'\n').count() + 1;
+
+    let context_lines: Vec<&[u8]> = old[context..start]
+        .split_inclusive(|b| *b == b'\n')
+        .collect();
+    let old_lines: Vec<&[u8]> = old[start..].split_inclusive(|b| *b == b'\n').collect();
+    let new_lines: Vec<&[u8]> = new[start..].split_inclusive(|b| *b == b'\n').collect();
+
+    let mut out = format!(
+        "diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n@@ -{} +{} @@\n",
+        hunk_range(first_line, context_lines.len() + old_lines.len()),
+        hunk_range(first_line, context_lines.len() + new_lines.len()),
+    );
+    let sides = [(' ', &context_lines), ('-', &old_lines), ('+', &new_lines)];
+    for (prefix, lines) in sides {
+        for line in lines.iter() {
+            out.push(prefix);
+            match line.strip_suffix(b"\n") {
+                Some(text) => {
+                    out.push_str(&String::from_utf8_lossy(text));
+                    out.push('\n');
+                }
+                None => {
+                    out.push_str(&String::from_utf8_lossy(line));
+                    out.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Where the line containing `bytes[at]` (or ending just before it) starts.
+fn line_start(bytes: &[u8], at: usize) -> usize {
+    bytes[..at]
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1)
+}
+
+/// One side of an `@@` header: `first,count`, with a count of 1 left out and
+/// an empty side numbered after the line before it.
+fn hunk_range(first: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", first - 1),
+        1 => first.to_string(),
+        _ => format!("{first},{count}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDS_NEWLINE: &[u8] = b"diff --git a/a.txt b/a.txt
+index 1234567..89abcde 100644
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@ fn main
+ first
+-second
+\\ No newline at end of file
++second
+";
+
+    const KEEPS_MISSING_NEWLINE: &[u8] = b"--- a/b.txt
++++ b/b.txt
+@@ -1 +1 @@
+-old
+\\ No newline at end of file
++new
+\\ No newline at end of file
+";
+
+    const MIDDLE_OF_FILE: &[u8] = b"--- a/c.txt
++++ b/c.txt
+@@ -1,3 +1,3 @@
+ one
+-two
++2
+ three
+";
+
+    #[test]
+    fn hunk_that_adds_eof_newline() {
+        let hunks = hunks(ADDS_NEWLINE);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].path, "a.txt");
+        assert_eq!(hunks[0].header, "@@ -1,2 +1,2 @@");
+        assert_eq!(hunks[0].old, b"first\nsecond");
+        assert_eq!(hunks[0].new, b"first\nsecond\n");
+        assert!(hunks[0].adds_eof_newline());
+    }
+
+    #[test]
+    fn hunks_that_do_not_add_eof_newline() {
+        let hunks_b = hunks(KEEPS_MISSING_NEWLINE);
+        assert_eq!(hunks_b.len(), 1);
+        assert_eq!(hunks_b[0].new, b"new");
+        assert!(!hunks_b[0].adds_eof_newline());
+
+        let hunks_c = hunks(MIDDLE_OF_FILE);
+        assert_eq!(hunks_c.len(), 1);
+        assert!(!hunks_c[0].adds_eof_newline());
+    }
+
+    #[test]
+    fn multiple_files_and_new_files() {
+        let mut patch = ADDS_NEWLINE.to_vec();
+        patch.extend_from_slice(MIDDLE_OF_FILE);
+        patch.extend_from_slice(
+            b"--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+",
+        );
+        let hunks = hunks(&patch);
+        let paths: Vec<&str> = hunks.iter().map(|h| h.path.as_str()).collect();
+        assert_eq!(paths, ["a.txt", "c.txt", "new.txt"]);
+        // A new file has no old side to compare against.
+        assert_eq!(hunks[2].old, b"");
+        assert_eq!(hunks[2].new, b"hello\n");
+    }
+
+    #[test]
+    fn removed_lines_that_look_like_headers() {
+        let patch = b"--- a/d.txt
++++ b/d.txt
+@@ -1,2 +0,0 @@
+--- not a header
+-++ nor this
+";
+        let hunks = hunks(patch);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].path, "d.txt");
+        assert_eq!(hunks[0].old, b"-- not a header\n++ nor this\n");
+        assert_eq!(hunks[0].new, b"");
+    }
+
+    #[test]
+    fn eof_diff_of_a_stripped_newline() {
+        let diff = eof_diff("a.txt", b"1\n2\n3\n4\n5\n", b"1\n2\n3\n4\n5");
+        assert_eq!(
+            diff,
+            "diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -2,4 +2,4 @@
+ 2
+ 3
+ 4
+-5
++5
+\\ No newline at end of file
+"
+        );
+        // Read back, the hunk is the fix undone in reverse.
+        let hunks = hunks(diff.as_bytes());
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].old, b"2\n3\n4\n5\n");
+        assert_eq!(hunks[0].new, b"2\n3\n4\n5");
+    }
+
+    #[test]
+    fn eof_diff_of_a_restored_newline_and_an_extra_blank_line() {
+        assert_eq!(
+            eof_diff("b", b"x", b"x\n"),
+            "diff --git a/b b/b\n--- a/b\n+++ b/b\n@@ -1 +1 @@\n-x\n\\ No newline at end of file\n+x\n"
+        );
+        assert_eq!(
+            eof_diff("c", b"x\n\n", b"x\n"),
+            "diff --git a/c b/c\n--- a/c\n+++ b/c\n@@ -1,2 +1 @@\n x\n-\n"
+        );
+        assert_eq!(eof_diff("d", b"same", b"same"), "");
+    }
+}
@@ -0,0 +1,612 @@
+//! A small regular expression matcher.
+//!
+//! Supports literals, `.`, character classes (`[a-z]`, `[^0-9]`), the escapes
+//! `\d \w \s` (and their negations), anchors `^`/`$`, groups, alternation and
+//! the quantifiers `* + ? {n} {n,} {n,m}`. Matching is unanchored unless the
+//! pattern uses `^`/`$`.
+//!
+//! Patterns compile to a Pike VM program that keeps every live thread in
+//! step with the input, so matching takes time linear in the text and
+//! constant stack however long the line is.
+//!
+//! [`Glob`] translates shell-style path globs onto the same engine.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class {
+        items: Vec<ClassItem>,
+        negated: bool,
+    },
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            ClassItem::Range(lo, hi) => *lo <= c && c <= *hi,
+            ClassItem::Digit(negated) => c.is_ascii_digit() != *negated,
+            ClassItem::Word(negated) => (c.is_alphanumeric() || c == '_') != *negated,
+            ClassItem::Space(negated) => c.is_whitespace() != *negated,
+        }
+    }
+}
+
+/// One instruction of a compiled pattern. `Split` and `Jmp` name the
+/// instructions to continue at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Inst {
+    Char(char),
+    Any,
+    Class {
+        items: Vec<ClassItem>,
+        negated: bool,
+    },
+    Start,
+    End,
+    Split(usize, usize),
+    Jmp(usize),
+    Match,
+}
+
+impl Inst {
+    /// Whether this instruction consumes `c`; assertions and jumps never do.
+    fn consumes(&self, c: char) -> bool {
+        match self {
+            Inst::Char(want) => c == *want,
+            Inst::Any => c != '\n',
+            Inst::Class { items, negated } => items.iter().any(|item| item.matches(c)) != *negated,
+            _ => false,
+        }
+    }
+}
+
+/// The most instructions a pattern may compile to, which bounds what
+/// counted repetitions such as `a{1000000}` expand to.
+const MAX_PROGRAM_LEN: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regex {
+    source: String,
+    program: Vec<Inst>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut parser = Parser {
+            chars: &chars,
+            pos: 0,
+        };
+        let root = parser.alternation()?;
+        if parser.pos < chars.len() {
+            return Err(format!(
+                "invalid regex {pattern:?}: unmatched ')' at offset {}",
+                parser.pos
+            ));
+        }
+        let mut program = Vec::new();
+        compile(&root, &mut program)
+            .map_err(|what| format!("invalid regex {pattern:?}: {what}"))?;
+        program.push(Inst::Match);
+        Ok(Regex {
+            source: pattern.to_string(),
+            program,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        // `seen[pc] == pos + 1` once `pc` is queued for position `pos`.
+        let mut seen = vec![0; self.program.len()];
+        let mut current = Vec::new();
+        let mut next = Vec::new();
+        for pos in 0..=chars.len() {
+            // A new thread at every position makes the match unanchored.
+            if self.add_thread(&mut current, &mut seen, 0, pos, chars.len()) {
+                return true;
+            }
+            let Some(&c) = chars.get(pos) else {
+                return false;
+            };
+            next.clear();
+            for &pc in &current {
+                if self.program[pc].consumes(c)
+                    && self.add_thread(&mut next, &mut seen, pc + 1, pos + 1, chars.len())
+                {
+                    return true;
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+        }
+        false
+    }
+
+    /// Queues the consuming instructions reachable from `pc` at `pos` onto
+    /// `threads`, following jumps and assertions. Returns whether `Match` is
+    /// reachable, i.e. the pattern has matched.
+    fn add_thread(
+        &self,
+        threads: &mut Vec<usize>,
+        seen: &mut [usize],
+        pc: usize,
+        pos: usize,
+        len: usize,
+    ) -> bool {
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if seen[pc] == pos + 1 {
+                continue;
+            }
+            seen[pc] = pos + 1;
+            match self.program[pc] {
+                Inst::Match => return true,
+                Inst::Jmp(to) => stack.push(to),
+                Inst::Split(a, b) => stack.extend([b, a]),
+                Inst::Start if pos == 0 => stack.push(pc + 1),
+                Inst::End if pos == len => stack.push(pc + 1),
+                Inst::Start | Inst::End => {}
+                _ => threads.push(pc),
+            }
+        }
+        false
+    }
+}
+
+/// A shell-style glob over `/`-separated repository paths.
+///
+/// `*` and `?` stay within one path component, `**` crosses components and
+/// `[...]`/`[!...]` are character classes. A glob without a `/` matches the
+/// file name at any depth, so `*.sh` matches `scripts/build.sh`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    source: String,
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(glob: &str) -> Result<Glob, String> {
+        let invalid = |what: &str| format!("invalid glob {glob:?}: {what}");
+        let chars: Vec<char> = glob.trim_start_matches('/').chars().collect();
+        let mut re = String::from("^");
+        if !glob.contains('/') {
+            re.push_str("(.*/)?");
+        }
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    if chars.get(i + 2) == Some(&'/') {
+                        re.push_str("(.*/)?");
+                        i += 3;
+                    } else {
+                        re.push_str(".*");
+                        i += 2;
+                    }
+                    continue;
+                }
+                '*' => re.push_str("[^/]*"),
+                '?' => re.push_str("[^/]"),
+                '[' => {
+                    let close = chars[i + 1..]
+                        .iter()
+                        .skip(1)
+                        .position(|c| *c == ']')
+                        .map(|p| i + 2 + p)
+                        .ok_or_else(|| invalid("missing ']'"))?;
+                    re.push('[');
+                    let mut class = &chars[i + 1..close];
+                    if let Some(rest) = class.strip_prefix(&['!']) {
+                        re.push('^');
+                        class = rest;
+                    }
+                    re.extend(class);
+                    re.push(']');
+                    i = close;
+                }
+                c if ".+()|^${}\\".contains(c) => {
+                    re.push('\\');
+                    re.push(c);
+                }
+                c => re.push(c),
+            }
+            i += 1;
+        }
+        re.push('$');
+        let regex = Regex::new(&re).map_err(|e| invalid(&e))?;
+        Ok(Glob {
+            source: glob.to_string(),
+            regex,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+}
+
+struct Parser<'a> {
+    chars: &'a [char],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, what: &str) -> String {
+        let pattern: String = self.chars.iter().collect();
+        format!("invalid regex {pattern:?}: {what} at offset {}", self.pos)
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut alts = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alts.push(self.concat()?);
+        }
+        Ok(if alts.len() == 1 {
+            alts.remove(0)
+        } else {
+            Node::Alt(alts)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.remove(0),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or_else(|| self.error("unexpected end"))?;
+        self.pos += 1;
+        match c {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '(' => {
+                let inner = self.alternation()?;
+                if self.peek() != Some(')') {
+                    return Err(self.error("missing ')'"));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            '[' => self.class(),
+            '\\' => self.escape(),
+            '*' | '+' | '?' | '{' => Err(self.error("quantifier without a preceding atom")),
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn escape(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or_else(|| self.error("trailing '\\'"))?;
+        self.pos += 1;
+        let item = match c {
+            'd' => ClassItem::Digit(false),
+            'D' => ClassItem::Digit(true),
+            'w' => ClassItem::Word(false),
+            'W' => ClassItem::Word(true),
+            's' => ClassItem::Space(false),
+            'S' => ClassItem::Space(true),
+            'n' => return Ok(Node::Char('\n')),
+            'r' => return Ok(Node::Char('\r')),
+            't' => return Ok(Node::Char('\t')),
+            c => return Ok(Node::Char(c)),
+        };
+        Ok(Node::Class {
+            items: vec![item],
+            negated: false,
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("missing ']'"))?;
+            self.pos += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let lo = if c == '\\' {
+                let e = self.peek().ok_or_else(|| self.error("trailing '\\'"))?;
+                self.pos += 1;
+                match e {
+                    'd' => {
+                        items.push(ClassItem::Digit(false));
+                        continue;
+                    }
+                    'w' => {
+                        items.push(ClassItem::Word(false));
+                        continue;
+                    }
+                    's' => {
+                        items.push(ClassItem::Space(false));
+                        continue;
+                    }
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    e => e,
+                }
+            } else {
+                c
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                let hi = self.chars[self.pos + 1];
+                self.pos += 2;
+                if hi < lo {
+                    return Err(self.error("invalid class range"));
+                }
+                items.push(ClassItem::Range(lo, hi));
+            } else {
+                items.push(ClassItem::Range(lo, lo));
+            }
+        }
+        Ok(Node::Class { items, negated })
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => return self.braces(atom),
+            _ => return Ok(atom),
+        };
+        self.pos += 1;
+        if matches!(atom, Node::Start | Node::End) {
+            return Err(self.error("quantifier after an anchor"));
+        }
+        Ok(Node::Repeat {
+            node: Box::new(atom),
+            min,
+            max,
+        })
+    }
+
+    fn braces(&mut self, atom: Node) -> Result<Node, String> {
+        self.pos += 1;
+        let min = self
+            .number()?
+            .ok_or_else(|| self.error("expected a count"))?;
+        let max = match self.peek() {
+            Some(',') => {
+                self.pos += 1;
+                self.number()?
+            }
+            _ => Some(min),
+        };
+        if self.peek() != Some('}') {
+            return Err(self.error("missing '}'"));
+        }
+        self.pos += 1;
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error("invalid repetition range"));
+        }
+        Ok(Node::Repeat {
+            node: Box::new(atom),
+            min,
+            max,
+        })
+    }
+
+    fn number(&mut self) -> Result<Option<usize>, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits
+            .parse()
+            .map(Some)
+            .map_err(|_| self.error("repetition count too large"))
+    }
+}
+
+/// Appends the instructions for `node` to `program`.
+fn compile(node: &Node, program: &mut Vec<Inst>) -> Result<(), String> {
+    if program.len() > MAX_PROGRAM_LEN {
+        return Err("pattern is too large".to_string());
+    }
+    match node {
+        Node::Empty => {}
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class { items, negated } => program.push(Inst::Class {
+            items: items.clone(),
+            negated: *negated,
+        }),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, program)?;
+            }
+        }
+        Node::Alt(alts) => {
+            let mut jumps = Vec::new();
+            for (n, alt) in alts.iter().enumerate() {
+                if n + 1 == alts.len() {
+                    compile(alt, program)?;
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(alt, program)?;
+                jumps.push(program.len());
+                program.push(Inst::Jmp(0));
+                program[split] = Inst::Split(split + 1, program.len());
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jmp(end);
+            }
+        }
+        Node::Repeat { node, min, max } => {
+            for _ in 0..*min {
+                compile(node, program)?;
+            }
+            match max {
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(program.len() + 1, 0));
+                        compile(node, program)?;
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(node, program)?;
+                    program.push(Inst::Jmp(split));
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn literals_and_anchors() {
+        assert!(matches("vim", "# vim: set ft=sh"));
+        assert!(matches("^# vim:", "# vim: set ft=sh"));
+        assert!(!matches("^vim", "# vim: set ft=sh"));
+        assert!(matches("ft=sh$", "# vim: set ft=sh"));
+        assert!(!matches("^ft$", "ft=sh"));
+        assert!(matches("", "anything"));
+    }
+
+    #[test]
+    fn classes_and_quantifiers() {
+        assert!(matches("^[a-z]+-bot@", "ci-bot@example.com"));
+        assert!(!matches("^[a-z]+-bot@", "CI-bot@example.com"));
+        assert!(matches("^[^@]+@example\\.com$", "alice@example.com"));
+        assert!(!matches("^[^@]+@example\\.com$", "alice@examplexcom"));
+        assert!(matches("^\\d{3}-\\d{2,}$", "123-45678"));
+        assert!(!matches("^\\d{3}-\\d{2,}$", "123-4"));
+        assert!(matches("^colou?r$", "color"));
+        assert!(matches("^a.*z$", "abcz"));
+        assert!(matches("^(ab)*$", ""));
+    }
+
+    #[test]
+    fn alternation_and_groups() {
+        let re = Regex::new("^(ci-bot|release-bot)@").unwrap();
+        assert!(re.is_match("ci-bot@example.com"));
+        assert!(re.is_match("release-bot@example.com"));
+        assert!(!re.is_match("alice@example.com"));
+        assert!(matches("^(a|b)+c$", "ababc"));
+        assert!(matches("^(a|)+$", "aa"));
+        assert!(matches("^(|a)b$", "b"));
+        assert!(matches("^a{2,3}$", "aaa"));
+        assert!(!matches("^a{2,3}$", "aaaa"));
+    }
+
+    #[test]
+    fn long_lines_match_in_linear_time() {
+        let line = "x".repeat(1 << 20);
+        assert!(matches("^x*$", &line));
+        assert!(!matches("^x*y$", &line));
+        assert!(matches("x{3}$", &line));
+        // Backtracking would try every split of the a's between the branches.
+        let line = format!("{}b", "a".repeat(10_000));
+        assert!(!matches("^(a|a)*$", &line));
+        assert!(!matches("^(a*)*$", &line));
+    }
+
+    #[test]
+    fn globs() {
+        let glob = |g: &str, path: &str| Glob::new(g).unwrap().is_match(path);
+        assert!(glob("*.sh", "build.sh"));
+        assert!(glob("*.sh", "scripts/ci/build.sh"));
+        assert!(!glob("*.sh", "build.shx"));
+        assert!(glob("scripts/*.sh", "scripts/build.sh"));
+        assert!(!glob("scripts/*.sh", "scripts/ci/build.sh"));
+        assert!(!glob("scripts/*.sh", "other/scripts/build.sh"));
+        assert!(glob("/scripts/*.sh", "scripts/build.sh"));
+        assert!(glob("docs/**/*.md", "docs/a.md"));
+        assert!(glob("docs/**/*.md", "docs/x/y/a.md"));
+        assert!(glob("docs/**", "docs/x/y/a.md"));
+        assert!(glob("file?.txt", "file1.txt"));
+        assert!(!glob("file?.txt", "file/.txt"));
+        assert!(glob("[abc].txt", "b.txt"));
+        assert!(glob("[!abc].txt", "d.txt"));
+        assert!(!glob("[!abc].txt", "a.txt"));
+        assert!(glob("a+b(1).txt", "a+b(1).txt"));
+        assert!(Glob::new("[abc").is_err());
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(Regex::new("(abc").is_err());
+        assert!(Regex::new("abc)").is_err());
+        assert!(Regex::new("[abc").is_err());
+        assert!(Regex::new("*a").is_err());
+        assert!(Regex::new("a{3,1}").is_err());
+        assert!(Regex::new("[z-a]").is_err());
+        assert!(Regex::new("a\\").is_err());
+        assert!(Regex::new("a{1000000}").is_err());
+    }
+}
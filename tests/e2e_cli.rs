@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const POLICY_NAMES: &[&str] = &["strip-one", "require-one"];
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let pid = std::process::id();
+    std::env::temp_dir().join(format!("{prefix}-{pid}-{nanos}"))
+}
+
+#[test]
+fn list_policies_names_every_policy() {
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(std::env::temp_dir())
+        .arg("--list-policies")
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    for name in POLICY_NAMES {
+        assert!(
+            stdout.lines().any(|l| l.starts_with(&format!("{name}: "))),
+            "missing policy {name} in:\n{stdout}"
+        );
+    }
+    assert!(stdout.contains(r#"strip-one: "a\n" -> "a""#));
+}
+
+#[test]
+fn workdir_only_rejects_history_modes() {
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(std::env::temp_dir())
+        .args(["--workdir-only", "--n", "1"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains("--workdir-only cannot be combined with --n 1"));
+}
+
+#[test]
+fn usage_errors_exit_64() {
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    for args in [&["--in-rebase", "--n", "0"][..], &["--no-such-option"][..]] {
+        let out = Command::new(bin)
+            .current_dir(std::env::temp_dir())
+            .args(args)
+            .output()
+            .unwrap();
+        assert_eq!(out.status.code(), Some(64), "{args:?}");
+    }
+}
+
+#[test]
+fn runtime_errors_exit_70() {
+    // Not a git repository, so the first git call fails.
+    let dir = unique_temp_dir("git-fix-eof-newline-not-a-repo");
+    fs::create_dir_all(&dir).unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&dir)
+        .env("GIT_CEILING_DIRECTORIES", std::env::temp_dir())
+        .args(["--n", "0"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn help_exits_successfully() {
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin).arg("--help").output().unwrap();
+    assert!(out.status.success());
+    assert!(
+        String::from_utf8(out.stdout)
+            .unwrap()
+            .contains("Exit status:")
+    );
+}
+
+#[test]
+fn check_patch_reports_hunks_adding_eof_newline() {
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let dir = unique_temp_dir("codex-no-newline-e2e-check-patch");
+    fs::create_dir_all(&dir).unwrap();
+
+    let adds = dir.join("adds.patch");
+    fs::write(
+        &adds,
+        "diff --git a/a.txt b/a.txt\n\
+         --- a/a.txt\n\
+         +++ b/a.txt\n\
+         @@ -1,2 +1,2 @@\n \
+         first\n\
+         -second\n\
+         \\ No newline at end of file\n\
+         +second\n",
+    )
+    .unwrap();
+    let keeps = dir.join("keeps.patch");
+    fs::write(
+        &keeps,
+        "diff --git a/b.txt b/b.txt\n\
+         --- a/b.txt\n\
+         +++ b/b.txt\n\
+         @@ -1 +1 @@\n\
+         -old\n\
+         \\ No newline at end of file\n\
+         +new\n\
+         \\ No newline at end of file\n",
+    )
+    .unwrap();
+
+    // Runs outside any repository.
+    let out = Command::new(bin)
+        .current_dir(&dir)
+        .env("GIT_CEILING_DIRECTORIES", &dir)
+        .args(["--check-patch", "adds.patch"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "patch: a.txt (@@ -1,2 +1,2 @@) adds an EOF newline\n"
+    );
+
+    let out = Command::new(bin)
+        .current_dir(&dir)
+        .env("GIT_CEILING_DIRECTORIES", &dir)
+        .args(["--check-patch", "keeps.patch"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
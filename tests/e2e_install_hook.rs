@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let pid = std::process::id();
+    std::env::temp_dir().join(format!("{prefix}-{pid}-{nanos}"))
+}
+
+fn run_git(repo_dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .current_dir(repo_dir)
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+fn git_stdout(repo_dir: &Path, args: &[&str]) -> Vec<u8> {
+    let out = Command::new("git")
+        .current_dir(repo_dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "git {:?} failed", args);
+    out.stdout
+}
+
+#[test]
+fn install_hook_in_linked_worktree_uses_common_hooks_dir() {
+    let root = unique_temp_dir("git-fix-eof-newline-install-hook");
+    let main_dir = root.join("main");
+    let wt_dir = root.join("wt");
+    fs::create_dir_all(&main_dir).unwrap();
+
+    run_git(&main_dir, &["init"]);
+    run_git(&main_dir, &["config", "user.name", "Test User"]);
+    run_git(&main_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(main_dir.join("a.txt"), b"x").unwrap();
+    run_git(&main_dir, &["add", "a.txt"]);
+    run_git(&main_dir, &["commit", "-m", "base"]);
+    run_git(
+        &main_dir,
+        &["worktree", "add", "-b", "wt", wt_dir.to_str().unwrap()],
+    );
+    // In a linked worktree `.git` is a file pointing at the main repository.
+    assert!(wt_dir.join(".git").is_file());
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&wt_dir)
+        .arg("--install-hook")
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let hook = main_dir.join(".git/hooks/pre-commit");
+    let script = fs::read_to_string(&hook).unwrap();
+    assert!(script.contains("--n 0"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_ne!(fs::metadata(&hook).unwrap().permissions().mode() & 0o111, 0);
+    }
+
+    // The hook runs for commits made in the worktree.
+    fs::write(wt_dir.join("a.txt"), b"y\n").unwrap();
+    run_git(&wt_dir, &["add", "a.txt"]);
+    run_git(&wt_dir, &["commit", "-m", "adds newline"]);
+    assert_eq!(git_stdout(&wt_dir, &["show", "HEAD:a.txt"]), b"y");
+
+    // Reinstalling over our own hook is fine.
+    let out = Command::new(bin)
+        .current_dir(&wt_dir)
+        .arg("--install-hook")
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn install_hook_subcommand_keeps_an_existing_hook_and_uninstall_restores_it() {
+    let dir = unique_temp_dir("git-fix-eof-newline-install-hook-sub");
+    fs::create_dir_all(&dir).unwrap();
+    run_git(&dir, &["init"]);
+
+    let hook = dir.join(".git/hooks/pre-commit");
+    let original = "#!/bin/sh\necho checking\nexit 0\n";
+    fs::write(&hook, original).unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let run = |arg: &str| {
+        let out = Command::new(bin)
+            .current_dir(&dir)
+            .arg(arg)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    };
+
+    run("install-hook");
+    let script = fs::read_to_string(&hook).unwrap();
+    assert!(script.starts_with("#!/bin/sh\n# >>> git-fix-eof-newline >>>\n"));
+    assert!(script.contains(" --n 0 || exit $?\n"));
+    assert!(script.ends_with("# <<< git-fix-eof-newline <<<\necho checking\nexit 0\n"));
+
+    // Installing again does not add a second block.
+    run("install-hook");
+    assert_eq!(fs::read_to_string(&hook).unwrap(), script);
+
+    run("uninstall-hook");
+    assert_eq!(fs::read_to_string(&hook).unwrap(), original);
+
+    // A hook that only ran the tool is removed altogether.
+    fs::remove_file(&hook).unwrap();
+    run("install-hook");
+    run("uninstall-hook");
+    assert!(!hook.exists());
+    run("uninstall-hook");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
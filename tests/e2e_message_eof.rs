@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let pid = std::process::id();
+    std::env::temp_dir().join(format!("{prefix}-{pid}-{nanos}"))
+}
+
+fn run_git(repo_dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .current_dir(repo_dir)
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+fn git_stdout(repo_dir: &Path, args: &[&str]) -> Vec<u8> {
+    let out = Command::new("git")
+        .current_dir(repo_dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "git {:?} failed", args);
+    out.stdout
+}
+
+#[test]
+fn check_message_eof_reports_trailing_blank_lines() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-message-eof");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let file_path = repo_dir.join("a.txt");
+    fs::write(&file_path, b"hello").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "clean message"]);
+
+    fs::write(&file_path, b"hello again").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(
+        &repo_dir,
+        &["commit", "--cleanup=verbatim", "-m", "noisy message\n\n\n"],
+    );
+
+    let noisy = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"]))
+        .unwrap()
+        .trim()
+        .to_string();
+    let clean = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD~1"]))
+        .unwrap()
+        .trim()
+        .to_string();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2", "--check-message-eof"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains(&format!("message eof: {noisy} (2 trailing blank lines)")));
+    assert!(!stdout.contains(&clean));
+
+    let bytes = fs::read(&file_path).unwrap();
+    assert_eq!(bytes, b"hello again");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
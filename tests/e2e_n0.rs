@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -21,6 +22,16 @@ fn run_git(repo_dir: &Path, args: &[&str]) {
     assert!(status.success(), "git {:?} failed", args);
 }
 
+fn git_stdout(repo_dir: &Path, args: &[&str]) -> Vec<u8> {
+    let out = Command::new("git")
+        .current_dir(repo_dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "git {:?} failed", args);
+    out.stdout
+}
+
 #[test]
 fn n0_fixes_added_eof_newline_in_worktree() {
     let repo_dir = unique_temp_dir("git-fix-eof-newline-n0");
@@ -50,3 +61,1511 @@ fn n0_fixes_added_eof_newline_in_worktree() {
 
     fs::remove_dir_all(&repo_dir).unwrap();
 }
+
+#[test]
+fn n0_leaves_index_alone_when_worktree_diverged() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-index-worktree");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let file_path = repo_dir.join("a.txt");
+    fs::write(&file_path, b"hello").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+
+    fs::write(&file_path, b"hello\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    fs::write(&file_path, b"hello world\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--apply-to-index-only-when-matching-worktree"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(git_stdout(&repo_dir, &["show", ":a.txt"]), b"hello\n");
+    assert_eq!(fs::read(&file_path).unwrap(), b"hello world\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+fn partially_staged_repo(prefix: &str) -> PathBuf {
+    let repo_dir = unique_temp_dir(prefix);
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let file_path = repo_dir.join("a.txt");
+    fs::write(&file_path, b"hello").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+
+    fs::write(&file_path, b"hello\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    fs::write(&file_path, b"hello world\n").unwrap();
+
+    repo_dir
+}
+
+fn run_partial_strategy(repo_dir: &Path, extra: &[&str]) -> (Vec<u8>, Vec<u8>) {
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(repo_dir)
+        .args(["--n", "0"])
+        .args(extra)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let index = git_stdout(repo_dir, &["show", ":a.txt"]);
+    let worktree = fs::read(repo_dir.join("a.txt")).unwrap();
+    (index, worktree)
+}
+
+#[test]
+fn n0_partial_stage_strategy_skip() {
+    let repo_dir = partially_staged_repo("git-fix-eof-newline-n0-partial-skip");
+    let (index, worktree) = run_partial_strategy(&repo_dir, &["--partial-stage-strategy", "skip"]);
+    assert_eq!(index, b"hello\n");
+    assert_eq!(worktree, b"hello world\n");
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_partial_stage_strategy_worktree() {
+    let repo_dir = partially_staged_repo("git-fix-eof-newline-n0-partial-worktree");
+    let (index, worktree) =
+        run_partial_strategy(&repo_dir, &["--partial-stage-strategy", "worktree"]);
+    assert_eq!(index, b"hello\n");
+    assert_eq!(worktree, b"hello world");
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_include_partial_fixes_only_the_worktree_copy() {
+    let repo_dir = partially_staged_repo("git-fix-eof-newline-n0-include-partial");
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("pass --include-partial"), "{stderr}");
+
+    let (index, worktree) = run_partial_strategy(&repo_dir, &["--include-partial"]);
+    assert_eq!(index, b"hello\n");
+    assert_eq!(worktree, b"hello world");
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_partial_stage_strategy_index() {
+    let repo_dir = partially_staged_repo("git-fix-eof-newline-n0-partial-index");
+    let (index, worktree) = run_partial_strategy(&repo_dir, &["--partial-stage-strategy", "index"]);
+    assert_eq!(index, b"hello");
+    assert_eq!(worktree, b"hello world\n");
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_partial_stage_strategy_both() {
+    let repo_dir = partially_staged_repo("git-fix-eof-newline-n0-partial-both");
+    let (index, worktree) = run_partial_strategy(&repo_dir, &["--partial-stage-strategy", "both"]);
+    assert_eq!(index, b"hello");
+    assert_eq!(worktree, b"hello world");
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_partial_stage_strategy_index_respects_matching_worktree_guard() {
+    let repo_dir = partially_staged_repo("git-fix-eof-newline-n0-partial-index-guard");
+    let (index, worktree) = run_partial_strategy(
+        &repo_dir,
+        &[
+            "--partial-stage-strategy",
+            "index",
+            "--apply-to-index-only-when-matching-worktree",
+        ],
+    );
+    assert_eq!(index, b"hello\n");
+    assert_eq!(worktree, b"hello world\n");
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_ignores_files_that_were_only_touched() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-touched");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let file_path = repo_dir.join("a.txt");
+    fs::write(&file_path, b"hello\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(&file_path, b"hello\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty());
+    assert!(out.stderr.is_empty());
+    assert_eq!(fs::read(&file_path).unwrap(), b"hello\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_force_path_checks_file_outside_git_diff() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-force-path");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let forced = repo_dir.join("a.txt");
+    let other = repo_dir.join("b.txt");
+    fs::write(&forced, b"hello").unwrap();
+    fs::write(&other, b"world").unwrap();
+    run_git(&repo_dir, &["add", "a.txt", "b.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+
+    // Staged, so `git diff` does not list it.
+    fs::write(&forced, b"hello\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    // Listed by `git diff`, but not forced.
+    fs::write(&other, b"world\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--force-path", "a.txt"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(fs::read(&forced).unwrap(), b"hello");
+    assert_eq!(fs::read(&other).unwrap(), b"world\n");
+
+    // A forced path that is not in HEAD is skipped; the others are fixed.
+    fs::write(&forced, b"hello\n").unwrap();
+    fs::write(repo_dir.join("new.txt"), b"new\n").unwrap();
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args([
+            "--n",
+            "0",
+            "--force-path",
+            "new.txt",
+            "--force-path",
+            "a.txt",
+        ])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("skipping file missing from HEAD: new.txt"),
+        "{stderr}"
+    );
+    assert_eq!(fs::read(&forced).unwrap(), b"hello");
+    assert_eq!(fs::read(repo_dir.join("new.txt")).unwrap(), b"new\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_strip_only_if_last_line_matches() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-last-line");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let marked = repo_dir.join("marked.sh");
+    let plain = repo_dir.join("plain.txt");
+    fs::write(&marked, b"echo hi\n# vim: set ft=sh").unwrap();
+    fs::write(&plain, b"hello").unwrap();
+    run_git(&repo_dir, &["add", "marked.sh", "plain.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+
+    fs::write(&marked, b"echo hi\n# vim: set ft=sh\n").unwrap();
+    fs::write(&plain, b"hello\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--strip-only-if-last-line-matches", "^# vim:"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(fs::read(&marked).unwrap(), b"echo hi\n# vim: set ft=sh");
+    assert_eq!(fs::read(&plain).unwrap(), b"hello\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_strip_only_if_last_line_matches_a_long_line() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-long-last-line");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    // A minified file: one 1 MiB line.
+    let line = vec![b'x'; 1 << 20];
+    let file = repo_dir.join("a.js");
+    fs::write(&file, &line).unwrap();
+    run_git(&repo_dir, &["add", "a.js"]);
+    run_git(&repo_dir, &["commit", "-m", "add a.js"]);
+
+    fs::write(&file, [line.as_slice(), b"\n"].concat()).unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--strip-only-if-last-line-matches", "^x*$"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(&file).unwrap(), line);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_emit_script_writes_equivalent_fixes() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-emit-script");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    // A quote and a space exercise the script's shell quoting.
+    let unstaged_path = repo_dir.join("it's here.txt");
+    let staged_path = repo_dir.join("b.txt");
+    fs::write(&unstaged_path, b"hello").unwrap();
+    fs::write(&staged_path, b"world").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+
+    fs::write(&unstaged_path, b"hello\n").unwrap();
+    fs::write(&staged_path, b"world\n").unwrap();
+    run_git(&repo_dir, &["add", "b.txt"]);
+
+    let script = repo_dir.join(".git").join("fix.sh");
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--emit-script"])
+        .arg(&script)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(fs::read(&unstaged_path).unwrap(), b"hello\n");
+    assert_eq!(git_stdout(&repo_dir, &["show", ":b.txt"]), b"world\n");
+
+    let status = Command::new("sh")
+        .current_dir(&repo_dir)
+        .arg(&script)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(fs::read(&unstaged_path).unwrap(), b"hello");
+    assert_eq!(fs::read(&staged_path).unwrap(), b"world");
+    assert_eq!(git_stdout(&repo_dir, &["show", ":b.txt"]), b"world");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_pathspec_limits_considered_paths() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-pathspec");
+    fs::create_dir_all(repo_dir.join("src")).unwrap();
+    fs::create_dir_all(repo_dir.join("docs")).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let inside = repo_dir.join("src").join("a.txt");
+    let outside = repo_dir.join("docs").join("b.txt");
+    fs::write(&inside, b"hello").unwrap();
+    fs::write(&outside, b"hello").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+
+    fs::write(&inside, b"hello\n").unwrap();
+    fs::write(&outside, b"hello\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--pathspec", "src"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(fs::read(&inside).unwrap(), b"hello");
+    assert_eq!(fs::read(&outside).unwrap(), b"hello\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_compares_renamed_file_against_its_old_name() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-rename");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(repo_dir.join("old.txt"), b"line 1\nline 2\nline 3").unwrap();
+    run_git(&repo_dir, &["add", "old.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add old"]);
+
+    run_git(&repo_dir, &["mv", "old.txt", "new.txt"]);
+    let new_path = repo_dir.join("new.txt");
+    fs::write(&new_path, b"line 1\nline 2\nline 3\n").unwrap();
+    run_git(&repo_dir, &["add", "new.txt"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(fs::read(&new_path).unwrap(), b"line 1\nline 2\nline 3");
+    assert_eq!(
+        git_stdout(&repo_dir, &["show", ":new.txt"]),
+        b"line 1\nline 2\nline 3"
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_max_total_bytes_stops_early() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-max-total-bytes");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let names = ["f1.txt", "f2.txt", "f3.txt", "f4.txt", "f5.txt"];
+    let content = vec![b'x'; 1000];
+    for name in names {
+        fs::write(repo_dir.join(name), &content).unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+
+    let mut with_newline = content.clone();
+    with_newline.push(b'\n');
+    for name in names {
+        fs::write(repo_dir.join(name), &with_newline).unwrap();
+    }
+
+    // Each file costs one 1000-byte HEAD blob, so the cap is crossed after
+    // the second file.
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--max-total-bytes", "1500"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains("processed 2 of 5 paths, 3 remaining"));
+    for (i, name) in names.iter().enumerate() {
+        let expected = if i < 2 { &content } else { &with_newline };
+        assert_eq!(&fs::read(repo_dir.join(name)).unwrap(), expected, "{name}");
+    }
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_output_relative_to_rewrites_displayed_paths() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-output-relative-to");
+    fs::create_dir_all(repo_dir.join("sub")).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(repo_dir.join("sub").join("a.txt"), b"hello").unwrap();
+    fs::write(repo_dir.join("top.txt"), b"hello").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+
+    fs::write(repo_dir.join("sub").join("a.txt"), b"hello\n").unwrap();
+    fs::write(repo_dir.join("top.txt"), b"hello\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--dry-run", "--output-relative-to", "sub"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let top = fs::canonicalize(&repo_dir).unwrap().join("top.txt");
+    assert!(stdout.contains("n=0 match (worktree): a.txt\n"), "{stdout}");
+    assert!(
+        stdout.contains(&format!("n=0 match (worktree): {}\n", top.display())),
+        "{stdout}"
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_require_eof_newline_for_glob_restores_removed_newline() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-require");
+    fs::create_dir_all(repo_dir.join("scripts")).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let snap = repo_dir.join("a.snap");
+    let script = repo_dir.join("scripts").join("run.sh");
+    fs::write(&snap, b"x").unwrap();
+    fs::write(&script, b"echo hi\n").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+
+    fs::write(&snap, b"x\n").unwrap();
+    fs::write(&script, b"echo hi").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--require-eof-newline-for", "*.sh"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(fs::read(&snap).unwrap(), b"x");
+    assert_eq!(fs::read(&script).unwrap(), b"echo hi\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_safe_mode_accepts_a_normal_fix() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-safe-mode");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let unstaged = repo_dir.join("a.txt");
+    let staged = repo_dir.join("b.txt");
+    let untouched = repo_dir.join("c.txt");
+    fs::write(&unstaged, b"a").unwrap();
+    fs::write(&staged, b"b").unwrap();
+    fs::write(&untouched, b"c").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+
+    fs::write(&unstaged, b"a\n").unwrap();
+    fs::write(&staged, b"b\n").unwrap();
+    run_git(&repo_dir, &["add", "b.txt"]);
+    fs::write(&untouched, b"c2").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--safe-mode"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    assert_eq!(fs::read(&unstaged).unwrap(), b"a");
+    assert_eq!(fs::read(&staged).unwrap(), b"b");
+    assert_eq!(git_stdout(&repo_dir, &["show", ":b.txt"]), b"b");
+    assert_eq!(fs::read(&untouched).unwrap(), b"c2");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_exclude_extension_skips_matching_files() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-exclude-ext");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    let names = ["main.rs", "lib.rs", "a.snap", "B.SNAP"];
+    for name in names {
+        fs::write(repo_dir.join(name), b"x").unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    for name in names {
+        fs::write(repo_dir.join(name), b"x\n").unwrap();
+    }
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--exclude-extension", "snap"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    assert_eq!(fs::read(repo_dir.join("main.rs")).unwrap(), b"x");
+    assert_eq!(fs::read(repo_dir.join("lib.rs")).unwrap(), b"x");
+    assert_eq!(fs::read(repo_dir.join("a.snap")).unwrap(), b"x\n");
+    assert_eq!(fs::read(repo_dir.join("B.SNAP")).unwrap(), b"x\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_include_and_exclude_globs_filter_paths() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-include-exclude");
+    fs::create_dir_all(repo_dir.join("src")).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    let names = ["src/main.rs", "src/generated.rs", "notes.txt"];
+    for name in names {
+        fs::write(repo_dir.join(name), b"x").unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    for name in names {
+        fs::write(repo_dir.join(name), b"x\n").unwrap();
+    }
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--include", "*.rs", "--exclude", "generated.rs"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    assert_eq!(fs::read(repo_dir.join("src/main.rs")).unwrap(), b"x");
+    assert_eq!(fs::read(repo_dir.join("src/generated.rs")).unwrap(), b"x\n");
+    assert_eq!(fs::read(repo_dir.join("notes.txt")).unwrap(), b"x\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn compare_working_tree_to_index_reports_and_fixes_drift() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-worktree-vs-index");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"old\n").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    // Staged without a newline (HEAD had one), then an editor re-added it.
+    fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    fs::write(repo_dir.join("a.txt"), b"a\n").unwrap();
+    // An unrelated unstaged edit that keeps the EOF as it was.
+    fs::write(repo_dir.join("b.txt"), b"bb").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .arg("--compare-working-tree-to-index")
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "index drift: a.txt\n"
+    );
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"a\n");
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--compare-working-tree-to-index", "--fix"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"a");
+    assert_eq!(git_stdout(&repo_dir, &["show", ":a.txt"]), b"a");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_format_github_prints_workflow_annotations() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-github");
+    fs::create_dir_all(repo_dir.join("src")).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("src/a,b.txt"), b"x").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("src/a,b.txt"), b"x\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--dry-run", "--format", "github"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "::warning file=src/a%2Cb.txt::added EOF newline would be stripped\n"
+    );
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--dry-run", "--format", "github", "--strict"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "::error file=src/a%2Cb.txt::added EOF newline would be stripped\n"
+    );
+    assert_eq!(fs::read(repo_dir.join("src/a,b.txt")).unwrap(), b"x\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_fixes_index_blob_when_worktree_file_is_missing() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-missing-worktree");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a"), b"a").unwrap();
+    run_git(&repo_dir, &["add", "a"]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    fs::write(repo_dir.join("a"), b"a\n").unwrap();
+    run_git(&repo_dir, &["add", "a"]);
+    fs::remove_file(repo_dir.join("a")).unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--missing-worktree-strategy", "skip"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("missing from the worktree: a"));
+    assert_eq!(git_stdout(&repo_dir, &["show", ":a"]), b"a\n");
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(git_stdout(&repo_dir, &["show", ":a"]), b"a");
+    assert!(!repo_dir.join("a").exists());
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_log_file_records_args_commands_and_decisions() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-log-file");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"x").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("a.txt"), b"x\n").unwrap();
+
+    let log = repo_dir.join("run.log");
+    fs::write(&log, "earlier run\n").unwrap();
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--log-file", "run.log"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let text = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    // Appended, not truncated.
+    assert_eq!(lines[0], "earlier run");
+    let entry = |needle: &str| {
+        lines
+            .iter()
+            .any(|l| l.starts_with('[') && l.contains(needle))
+    };
+    assert!(entry("] args: "), "{text}");
+    assert!(entry("--log-file run.log"), "{text}");
+    assert!(entry("] git version "), "{text}");
+    assert!(entry("] git diff -M --name-status -z: exit 0"), "{text}");
+    assert!(entry("] fix worktree (strip-one): a.txt"), "{text}");
+    assert_eq!(lines.last().map(|l| l.ends_with("] exit 0")), Some(true));
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"x");
+
+    // An unwritable log is a clean runtime error.
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--log-file", "missing-dir/run.log"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("failed to open log file"));
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_leaves_binary_files_alone_unless_include_binary() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-binary");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let old: &[u8] = b"\x89PNG\r\n\x1a\0\0\0\rIHDR\x01";
+    let new: &[u8] = b"\x89PNG\r\n\x1a\0\0\0\rIHDR\x01\n";
+    fs::write(repo_dir.join("img.png"), old).unwrap();
+    fs::write(repo_dir.join("a.txt"), b"x").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("img.png"), new).unwrap();
+    fs::write(repo_dir.join("a.txt"), b"x\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(fs::read(repo_dir.join("img.png")).unwrap(), new);
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"x");
+    assert!(
+        String::from_utf8_lossy(&out.stderr)
+            .contains("skipping binary file (pass --include-binary to fix it): img.png")
+    );
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--include-binary"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(repo_dir.join("img.png")).unwrap(), old);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_follows_gitattributes_text_classification() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-gitattributes");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(
+        repo_dir.join(".gitattributes"),
+        "keep.txt binary\nforced.dat text\n",
+    )
+    .unwrap();
+    fs::write(repo_dir.join("keep.txt"), b"plain").unwrap();
+    fs::write(repo_dir.join("forced.dat"), b"a\0b").unwrap();
+    fs::write(repo_dir.join("other.txt"), b"other").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    fs::write(repo_dir.join("keep.txt"), b"plain\n").unwrap();
+    fs::write(repo_dir.join("forced.dat"), b"a\0b\n").unwrap();
+    fs::write(repo_dir.join("other.txt"), b"other\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(fs::read(repo_dir.join("keep.txt")).unwrap(), b"plain\n");
+    assert_eq!(fs::read(repo_dir.join("forced.dat")).unwrap(), b"a\0b");
+    assert_eq!(fs::read(repo_dir.join("other.txt")).unwrap(), b"other");
+    assert!(
+        String::from_utf8_lossy(&out.stderr)
+            .contains("skipping binary file (marked -text in .gitattributes): keep.txt")
+    );
+
+    // --include-binary only overrides the content heuristic.
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--include-binary"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(repo_dir.join("keep.txt")).unwrap(), b"plain\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_autocrlf_worktree_is_compared_like_git_would() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-autocrlf");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    run_git(&repo_dir, &["config", "core.autocrlf", "true"]);
+
+    fs::write(repo_dir.join("a.txt"), b"one\r\ntwo").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"kept\r\n").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    // The blobs are stored with LF only.
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:a.txt"]), b"one\ntwo");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:b.txt"]), b"kept\n");
+
+    // An editor adds a CRLF at EOF; b.txt only differs by conversion.
+    fs::write(repo_dir.join("a.txt"), b"one\r\ntwo\r\n").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"kept\r\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "n=0 match (worktree): a.txt\n"
+    );
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"one\r\ntwo");
+    assert_eq!(fs::read(repo_dir.join("b.txt")).unwrap(), b"kept\r\n");
+    let status = git_stdout(&repo_dir, &["status", "--porcelain"]);
+    assert!(status.is_empty(), "{}", String::from_utf8_lossy(&status));
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_max_blob_size_skips_large_files_and_fixes_the_rest() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-max-blob-size");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let big = vec![b'x'; 2048];
+    fs::write(repo_dir.join("big.txt"), &big).unwrap();
+    fs::write(repo_dir.join("small.txt"), b"s").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    let mut big_nl = big.clone();
+    big_nl.push(b'\n');
+    fs::write(repo_dir.join("big.txt"), &big_nl).unwrap();
+    fs::write(repo_dir.join("small.txt"), b"s\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--max-blob-size", "1K"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("skipping big.txt at HEAD: blob ")
+            && stderr.contains("is 2048 bytes, over the --max-blob-size limit of 1024"),
+        "{stderr}"
+    );
+    assert_eq!(fs::read(repo_dir.join("big.txt")).unwrap(), big_nl);
+    assert_eq!(fs::read(repo_dir.join("small.txt")).unwrap(), b"s");
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--max-blob-size", "4K"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(repo_dir.join("big.txt")).unwrap(), big);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+/// Parses one flat JSON object of string, number and `null` values, which is
+/// all `--format json` emits. `null` becomes `None`.
+fn parse_json_object(line: &str) -> BTreeMap<String, Option<String>> {
+    fn string(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+        assert_eq!(chars.next(), Some('"'));
+        let mut out = String::new();
+        loop {
+            match chars.next().expect("unterminated string") {
+                '"' => return out,
+                '\\' => match chars.next().unwrap() {
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    'u' => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        out.push(char::from_u32(u32::from_str_radix(&hex, 16).unwrap()).unwrap());
+                    }
+                    c => out.push(c),
+                },
+                c => out.push(c),
+            }
+        }
+    }
+
+    let mut fields = BTreeMap::new();
+    let mut chars = line.trim().chars().peekable();
+    assert_eq!(chars.next(), Some('{'), "{line}");
+    while chars.peek() != Some(&'}') {
+        let key = string(&mut chars);
+        assert_eq!(chars.next(), Some(':'), "{line}");
+        let value = if chars.peek() == Some(&'"') {
+            Some(string(&mut chars))
+        } else {
+            let raw: String =
+                std::iter::from_fn(|| chars.next_if(|c| *c != ',' && *c != '}')).collect();
+            (raw != "null").then_some(raw)
+        };
+        fields.insert(key, value);
+        chars.next_if_eq(&',');
+    }
+    assert_eq!(chars.next(), Some('}'), "{line}");
+    assert_eq!(chars.next(), None, "{line}");
+    fields
+}
+
+#[test]
+fn n0_format_json_emits_one_object_per_path_and_a_summary() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-json");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a \"q\".txt"), b"a").unwrap();
+    fs::write(repo_dir.join("b.bin"), b"\0b").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("a \"q\".txt"), b"a\r\n").unwrap();
+    fs::write(repo_dir.join("b.bin"), b"\0b\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let run = |extra: &[&str]| {
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(["--n", "0"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout)
+            .unwrap()
+            .lines()
+            .map(parse_json_object)
+            .collect::<Vec<_>>()
+    };
+    let field = |v: &str| Some(v.to_string());
+
+    let dry = run(&["--dry-run", "--json"]);
+    assert_eq!(dry.len(), 2, "{dry:?}");
+    assert_eq!(dry[0]["path"], field("a \"q\".txt"));
+    assert_eq!(dry[0]["target"], field("worktree"));
+    assert_eq!(dry[0]["action"], field("would-strip"));
+    assert_eq!(dry[0]["terminator"], field("crlf"));
+    assert_eq!(dry[1]["changed"], field("1"));
+    assert_eq!(dry[1]["skipped"], field("1"));
+    assert_eq!(fs::read(repo_dir.join("a \"q\".txt")).unwrap(), b"a\r\n");
+
+    let real = run(&["--format", "json"]);
+    assert_eq!(real.len(), 2, "{real:?}");
+    assert_eq!(real[0]["path"], field("a \"q\".txt"));
+    assert_eq!(real[0]["action"], field("strip"));
+    assert_eq!(fs::read(repo_dir.join("a \"q\".txt")).unwrap(), b"a");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn n0_skips_changed_symlinks_without_touching_their_target() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-symlink");
+    let outside_dir = unique_temp_dir("git-fix-eof-newline-n0-symlink-target");
+    fs::create_dir_all(&repo_dir).unwrap();
+    fs::create_dir_all(&outside_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    // The committed link's blob is its target's name, without a newline;
+    // the file it now points to (outside the repo) ends in one.
+    let target = outside_dir.join("target.txt");
+    fs::write(&target, b"outside\n").unwrap();
+    let link = repo_dir.join("link");
+    std::os::unix::fs::symlink("nowhere", &link).unwrap();
+    run_git(&repo_dir, &["add", "link"]);
+    run_git(&repo_dir, &["commit", "-m", "add link"]);
+    fs::remove_file(&link).unwrap();
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(
+        out.stdout.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&out.stdout)
+    );
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("skipping symlink: link"), "{stderr}");
+    assert_eq!(fs::read(&target).unwrap(), b"outside\n");
+    assert_eq!(fs::read_link(&link).unwrap(), target);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+    fs::remove_dir_all(&outside_dir).unwrap();
+}
+
+#[test]
+fn n0_on_an_unborn_branch_changes_nothing() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-unborn");
+    fs::create_dir_all(&repo_dir).unwrap();
+    run_git(&repo_dir, &["init"]);
+    fs::write(repo_dir.join("a.txt"), b"staged\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    fs::write(repo_dir.join("b.txt"), b"untracked\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    for extra in [&[][..], &["--force-path", "b.txt"][..]] {
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(["--n", "0"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("HEAD has no commits yet"), "{stderr}");
+    }
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"staged\n");
+    assert_eq!(git_stdout(&repo_dir, &["show", ":a.txt"]), b"staged\n");
+    assert_eq!(fs::read(repo_dir.join("b.txt")).unwrap(), b"untracked\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_skips_force_added_ignored_files_unless_asked() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-ignored");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join(".gitignore"), b"*.log\n").unwrap();
+    fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+    fs::write(repo_dir.join("build.log"), b"log").unwrap();
+    run_git(&repo_dir, &["add", ".gitignore", "a.txt"]);
+    run_git(&repo_dir, &["add", "-f", "build.log"]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+    fs::write(repo_dir.join("a.txt"), b"a\n").unwrap();
+    fs::write(repo_dir.join("build.log"), b"log\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("skipping ignored file"), "{stderr}");
+    assert!(stderr.contains("build.log"), "{stderr}");
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"a");
+    assert_eq!(fs::read(repo_dir.join("build.log")).unwrap(), b"log\n");
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--include-ignored"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(repo_dir.join("build.log")).unwrap(), b"log");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_file_arguments_limit_the_paths_considered() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-files");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(repo_dir.join(name), name).unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(repo_dir.join(name), format!("{name}\n")).unwrap();
+    }
+    // Staged, as the pre-commit framework would hand them over.
+    run_git(&repo_dir, &["add", "."]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "a.txt", "./c.txt"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(git_stdout(&repo_dir, &["show", ":a.txt"]), b"a.txt");
+    assert_eq!(git_stdout(&repo_dir, &["show", ":b.txt"]), b"b.txt\n");
+    assert_eq!(git_stdout(&repo_dir, &["show", ":c.txt"]), b"c.txt");
+    assert_eq!(fs::read(repo_dir.join("b.txt")).unwrap(), b"b.txt\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_file_argument_leaves_other_changed_files_alone() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-one-file");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+    fs::write(repo_dir.join("a.txt"), b"a\n").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b\n").unwrap();
+
+    // An absolute path, as an editor would pass it.
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .arg(repo_dir.join("a.txt"))
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"a");
+    assert_eq!(fs::read(repo_dir.join("b.txt")).unwrap(), b"b\n");
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "/elsewhere/b.txt"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("outside the repository"));
+    assert_eq!(fs::read(repo_dir.join("b.txt")).unwrap(), b"b\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_dry_run_diff_shows_the_newline_going_away() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-diff");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"one\ntwo").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+    fs::write(repo_dir.join("a.txt"), b"one\ntwo\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--dry-run", "--diff"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "n=0 match (worktree): a.txt
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++two
+\\ No newline at end of file
+"
+    );
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"one\ntwo\n");
+
+    // The diff is the fix itself.
+    let diff = stdout.split_once('\n').unwrap().1;
+    fs::write(repo_dir.join("fix.patch"), diff).unwrap();
+    run_git(&repo_dir, &["apply", "fix.patch"]);
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"one\ntwo");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_color_paints_matches_and_warnings_only_when_asked() {
+    // a.txt is partially staged (a warning); b.txt is a plain match.
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-color");
+    fs::create_dir_all(&repo_dir).unwrap();
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+    fs::write(repo_dir.join("a.txt"), b"a\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    fs::write(repo_dir.join("a.txt"), b"a b\n").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let run = |extra: &[&str]| {
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(["--n", "0", "--dry-run"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        (
+            String::from_utf8(out.stdout).unwrap(),
+            String::from_utf8(out.stderr).unwrap(),
+        )
+    };
+
+    let (stdout, stderr) = run(&["--color", "never"]);
+    assert_eq!(stdout, "n=0 match (worktree): b.txt\n");
+    assert!(
+        stderr.contains("skipping partially-staged file"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains('\x1b'), "{stderr:?}");
+
+    // Piped output is plain by default.
+    assert_eq!(run(&[]), (stdout, stderr));
+
+    let (stdout, stderr) = run(&["--color", "always"]);
+    assert_eq!(stdout, "\x1b[32mn=0 match (worktree): b.txt\x1b[0m\n");
+    assert!(
+        stderr.starts_with("\x1b[33mskipping partially-staged file"),
+        "{stderr:?}"
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_summary_counts_fixed_unchanged_and_skipped_files() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-summary");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+    fs::write(repo_dir.join("b.bin"), b"b\0").unwrap();
+    fs::write(repo_dir.join("c.txt"), b"one\ntwo\n").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+    fs::write(repo_dir.join("a.txt"), b"a\n").unwrap();
+    fs::write(repo_dir.join("b.bin"), b"b\0\n").unwrap();
+    fs::write(repo_dir.join("c.txt"), b"one\n2\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--summary"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert_eq!(
+        stderr.lines().last(),
+        Some("fixed 1 file (1 unchanged, 1 skipped: binary)"),
+        "{stderr}"
+    );
+
+    // Nothing left to do now, and --quiet drops the line.
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--summary"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert_eq!(
+        stderr.lines().last(),
+        Some("fixed 0 files (1 unchanged, 1 skipped: binary)")
+    );
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--summary", "--quiet"])
+        .output()
+        .unwrap();
+    assert_eq!(out.stderr, b"");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n0_from_a_nested_subdirectory_fixes_files_at_the_root() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n0-subdir");
+    let nested = repo_dir.join("src/deeper");
+    fs::create_dir_all(&nested).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("root.txt"), b"root").unwrap();
+    fs::write(nested.join("here.txt"), b"here").unwrap();
+    fs::write(nested.join("other.txt"), b"other").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+    fs::write(repo_dir.join("root.txt"), b"root\n").unwrap();
+    fs::write(nested.join("here.txt"), b"here\n").unwrap();
+    fs::write(nested.join("other.txt"), b"other\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    // File arguments are relative to where the tool runs.
+    let out = Command::new(bin)
+        .current_dir(&nested)
+        .args(["--n", "0", "here.txt"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(fs::read(nested.join("here.txt")).unwrap(), b"here");
+    assert_eq!(fs::read(nested.join("other.txt")).unwrap(), b"other\n");
+    assert_eq!(fs::read(repo_dir.join("root.txt")).unwrap(), b"root\n");
+
+    let out = Command::new(bin)
+        .current_dir(&nested)
+        .args(["--n", "0"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(fs::read(repo_dir.join("root.txt")).unwrap(), b"root");
+    assert_eq!(fs::read(nested.join("other.txt")).unwrap(), b"other");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
@@ -73,3 +73,1000 @@ fn n1_amends_head_to_remove_added_eof_newline() {
 
     fs::remove_dir_all(&repo_dir).unwrap();
 }
+
+#[cfg(unix)]
+#[test]
+fn n1_skip_hooks_bypasses_failing_commit_msg_hook() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-skip-hooks");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let file_path = repo_dir.join("a.txt");
+    fs::write(&file_path, b"hello").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+
+    fs::write(&file_path, b"hello\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add eof newline"]);
+
+    let hook = repo_dir.join(".git").join("hooks").join("commit-msg");
+    fs::create_dir_all(hook.parent().unwrap()).unwrap();
+    fs::write(&hook, b"#!/bin/sh\nexit 1\n").unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1"])
+        .status()
+        .unwrap();
+    assert!(!status.success());
+
+    run_git(&repo_dir, &["reset", "--hard", "HEAD"]);
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--skip-hooks"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let bytes = git_stdout(&repo_dir, &["show", "HEAD:a.txt"]);
+    assert_eq!(bytes, b"hello");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_add_trailer_appends_trailer_to_amended_message() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-trailer");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let file_path = repo_dir.join("a.txt");
+    fs::write(&file_path, b"hello").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+
+    fs::write(&file_path, b"hello\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add eof newline"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--add-trailer", "Fixed-by=git-fix-eof-newline"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let message = String::from_utf8(git_stdout(&repo_dir, &["log", "-1", "--format=%B"])).unwrap();
+    assert!(message.starts_with("add eof newline\n"));
+    assert!(message.contains("\nFixed-by: git-fix-eof-newline\n"));
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:a.txt"]), b"hello");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_annotate_notes_records_fixed_paths() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-notes");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(repo_dir.join("a.txt"), b"hello").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"world").unwrap();
+    fs::write(repo_dir.join("c.txt"), b"keep").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    fs::write(repo_dir.join("a.txt"), b"hello\n").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"world\n").unwrap();
+    fs::write(repo_dir.join("c.txt"), b"kept").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add eof newlines"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--annotate-notes"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let note = git_stdout(&repo_dir, &["notes", "--ref=fix-eof", "show", "HEAD"]);
+    assert_eq!(
+        String::from_utf8(note).unwrap(),
+        "strip-one: a.txt\nstrip-one: b.txt\n"
+    );
+    // The message itself is untouched.
+    let message = git_stdout(&repo_dir, &["log", "-1", "--format=%B", "HEAD"]);
+    assert_eq!(message, b"add eof newlines\n\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_restore_reappends_removed_eof_newline_in_old_style() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-restore");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    run_git(&repo_dir, &["config", "core.autocrlf", "false"]);
+
+    fs::write(repo_dir.join("lf.txt"), b"one\n").unwrap();
+    fs::write(repo_dir.join("crlf.txt"), b"two\r\n").unwrap();
+    fs::write(repo_dir.join("added.txt"), b"three").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    fs::write(repo_dir.join("lf.txt"), b"one").unwrap();
+    fs::write(repo_dir.join("crlf.txt"), b"two").unwrap();
+    fs::write(repo_dir.join("added.txt"), b"three\n").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "drop newlines"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--restore", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    assert_eq!(lines, ["n=1 match: crlf.txt", "n=1 match: lf.txt"]);
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--restore"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:lf.txt"]), b"one\n");
+    assert_eq!(
+        git_stdout(&repo_dir, &["show", "HEAD:crlf.txt"]),
+        b"two\r\n"
+    );
+    // Added newlines are left alone in restore mode.
+    assert_eq!(
+        git_stdout(&repo_dir, &["show", "HEAD:added.txt"]),
+        b"three\n"
+    );
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--restore", "--require-eof-newline-for", "*.sh"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(64));
+    assert!(
+        String::from_utf8_lossy(&out.stderr)
+            .contains("--restore cannot be combined with --require-eof-newline-for")
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_include_added_strips_newline_from_new_file() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-include-added");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(repo_dir.join("base.txt"), b"base").unwrap();
+    run_git(&repo_dir, &["add", "base.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    fs::write(repo_dir.join("new.txt"), b"single line\n").unwrap();
+    run_git(&repo_dir, &["add", "new.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add new"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    // Without the flag an added file has nothing to compare against.
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        git_stdout(&repo_dir, &["show", "HEAD:new.txt"]),
+        b"single line\n"
+    );
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--include-added"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        git_stdout(&repo_dir, &["show", "HEAD:new.txt"]),
+        b"single line"
+    );
+    assert_eq!(fs::read(repo_dir.join("new.txt")).unwrap(), b"single line");
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0", "--include-added"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(64));
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_strips_newline_added_while_renaming() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-rename");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let content = b"line one\nline two\nline three\nline four";
+    fs::write(repo_dir.join("a.txt"), content).unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+
+    run_git(&repo_dir, &["mv", "a.txt", "b.txt"]);
+    let mut renamed = content.to_vec();
+    renamed.push(b'\n');
+    fs::write(repo_dir.join("b.txt"), &renamed).unwrap();
+    run_git(&repo_dir, &["add", "b.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "rename a to b"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "n=1 match: b.txt\n");
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:b.txt"]), content);
+    let names = git_stdout(&repo_dir, &["ls-tree", "--name-only", "HEAD"]);
+    assert_eq!(names, b"b.txt\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_handles_non_ascii_and_space_in_file_names() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-quotepath");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    // The default, spelled out: git would print this name C-quoted.
+    run_git(&repo_dir, &["config", "core.quotepath", "true"]);
+
+    let name = "café menu.txt";
+    fs::write(repo_dir.join(name), b"soup").unwrap();
+    run_git(&repo_dir, &["add", name]);
+    run_git(&repo_dir, &["commit", "-m", "add menu"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    fs::write(repo_dir.join(name), b"soup\n").unwrap();
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "0"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(fs::read(repo_dir.join(name)).unwrap(), b"soup");
+
+    fs::write(repo_dir.join(name), b"soup\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "add newline"]);
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        format!("n=1 match: {name}\n")
+    );
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        git_stdout(&repo_dir, &["show", &format!("HEAD:{name}")]),
+        b"soup"
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_leaves_binary_files_alone() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-binary");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(repo_dir.join("data.bin"), b"\0\x01\x02").unwrap();
+    run_git(&repo_dir, &["add", "data.bin"]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("data.bin"), b"\0\x01\x02\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "append 0x0a"]);
+    let head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+    assert_eq!(
+        git_stdout(&repo_dir, &["show", "HEAD:data.bin"]),
+        b"\0\x01\x02\n"
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_skips_oversized_blob_without_aborting() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-oversized");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let big = vec![b'x'; 4096];
+    fs::write(repo_dir.join("big.txt"), &big).unwrap();
+    fs::write(repo_dir.join("small.txt"), b"s").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    let mut big_nl = big.clone();
+    big_nl.push(b'\n');
+    fs::write(repo_dir.join("big.txt"), &big_nl).unwrap();
+    fs::write(repo_dir.join("small.txt"), b"s\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "add newlines"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--max-blob-size", "1K"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("skipping big.txt at "), "{stderr}");
+    assert!(
+        stderr.contains("over the --max-blob-size limit of 1024"),
+        "{stderr}"
+    );
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:small.txt"]), b"s");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:big.txt"]), big_nl);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_check_lists_paths_and_exits_nonzero_without_changes() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-check");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    fs::write(repo_dir.join("b.txt"), b"b2").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "no newline change"]);
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--check"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(0));
+    assert!(out.stdout.is_empty());
+
+    fs::write(repo_dir.join("a.txt"), b"a\n").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b2\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "add newlines"]);
+    let head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--check"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "a.txt\nb.txt\n");
+    assert!(String::from_utf8_lossy(&out.stderr).contains("2 path(s) need fixing"));
+    assert_eq!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:a.txt"]), b"a\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_pathspecs_after_double_dash_scope_the_fix() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-double-dash");
+    fs::create_dir_all(repo_dir.join("src")).unwrap();
+    fs::create_dir_all(repo_dir.join("docs")).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(repo_dir.join("src/a.txt"), b"a").unwrap();
+    fs::write(repo_dir.join("docs/b.txt"), b"b").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("src/a.txt"), b"a\n").unwrap();
+    fs::write(repo_dir.join("docs/b.txt"), b"b\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "add newlines"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--", "src/"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:src/a.txt"]), b"a");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:docs/b.txt"]), b"b\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_on_detached_head_amends_it_and_leaves_the_branch_alone() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-detached");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"hello").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+    fs::write(repo_dir.join("a.txt"), b"hello\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "add eof newline"]);
+    fs::write(repo_dir.join("b.txt"), b"b\n").unwrap();
+    run_git(&repo_dir, &["add", "b.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add b"]);
+    let branch = String::from_utf8(git_stdout(&repo_dir, &["symbolic-ref", "HEAD"])).unwrap();
+    let branch_head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+    let old = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD~1"])).unwrap();
+    run_git(&repo_dir, &["checkout", "-q", old.trim()]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("HEAD is detached"), "{stderr}");
+    assert!(
+        stderr.contains(&format!("(was {})", old.trim())),
+        "{stderr}"
+    );
+
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:a.txt"]), b"hello");
+    assert_eq!(
+        git_stdout(&repo_dir, &["rev-parse", "HEAD~1"]),
+        git_stdout(&repo_dir, &["rev-parse", &format!("{}~1", old.trim())])
+    );
+    // Still detached, and the branch was not moved.
+    assert!(
+        !Command::new("git")
+            .current_dir(&repo_dir)
+            .args(["symbolic-ref", "-q", "HEAD"])
+            .status()
+            .unwrap()
+            .success()
+    );
+    assert_eq!(
+        git_stdout(&repo_dir, &["rev-parse", branch.trim()]),
+        branch_head
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_without_any_commit_is_a_clear_error() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-unborn");
+    fs::create_dir_all(&repo_dir).unwrap();
+    run_git(&repo_dir, &["init"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("HEAD does not point to a commit yet"),
+        "{stderr}"
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_commit_that_deletes_a_file_is_left_alone() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-delete");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"hello").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"bye\n").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+    run_git(&repo_dir, &["rm", "-q", "a.txt", "b.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "delete files"]);
+    let head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    for extra in [&[][..], &["--include-added"][..]] {
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(["--n", "1"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        assert!(out.stdout.is_empty());
+        assert_eq!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+        assert!(!repo_dir.join("a.txt").exists());
+        assert!(!repo_dir.join("b.txt").exists());
+    }
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_copy_is_compared_against_its_source_with_diff_renames_copies() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-copy");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    run_git(&repo_dir, &["config", "diff.renames", "copies"]);
+    let source = b"one\ntwo\nthree\nfour\nfive\nsix\nseven\neight";
+    fs::write(repo_dir.join("src.txt"), source).unwrap();
+    run_git(&repo_dir, &["add", "src.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add src"]);
+
+    // `-C` looks for copy sources among the files the commit modified, so
+    // the source changes too (but keeps its EOF).
+    fs::write(
+        repo_dir.join("src.txt"),
+        b"one\ntwo\nthree\nfour\nfive\nsix\nseven\nEIGHT",
+    )
+    .unwrap();
+    let mut copy = source.to_vec();
+    copy.push(b'\n');
+    fs::write(repo_dir.join("copy.txt"), &copy).unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "copy src"]);
+    let status = git_stdout(
+        &repo_dir,
+        &[
+            "diff-tree",
+            "-C",
+            "--name-status",
+            "-r",
+            "--no-commit-id",
+            "HEAD",
+        ],
+    );
+    assert!(
+        String::from_utf8_lossy(&status).contains("C0"),
+        "not a copy"
+    );
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:copy.txt"]), source);
+    assert_eq!(
+        git_stdout(&repo_dir, &["show", "HEAD:src.txt"]),
+        b"one\ntwo\nthree\nfour\nfive\nsix\nseven\nEIGHT"
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_check_format_github_annotates_the_matched_path() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-github");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"hello").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+    fs::write(repo_dir.join("a.txt"), b"hello\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add eof newline"]);
+    let head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--check", "--format", "github"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "::warning file=a.txt::added EOF newline would be stripped\n"
+    );
+    assert_eq!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_interactive_does_not_wait_without_a_terminal() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-interactive");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"hello").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+    fs::write(repo_dir.join("a.txt"), b"hello\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add eof newline"]);
+    let head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+
+    // stdin is a pipe that stays open, as in a CI job: the answer in it is
+    // never read, and nothing waits for the end of it.
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let mut child = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--interactive"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"n\n").unwrap();
+    let out = child.wait_with_output().unwrap();
+    drop(stdin);
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{stderr}");
+    assert!(!stderr.contains("Proceed?"), "{stderr}");
+    assert_ne!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:a.txt"]), b"hello");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_backup_ref_saves_head_before_amending() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-backup-ref");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"hello").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+    fs::write(repo_dir.join("a.txt"), b"hello\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add eof newline"]);
+    let head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--backup-ref"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let name = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("saved original HEAD as "))
+        .unwrap_or_else(|| panic!("{stdout}"));
+    assert!(name.starts_with("refs/fix-eof-backup/"), "{name}");
+    assert_eq!(git_stdout(&repo_dir, &["rev-parse", name]), head);
+    assert_ne!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+
+    // The backup undoes the amend.
+    run_git(&repo_dir, &["reset", "--hard", name]);
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:a.txt"]), b"hello\n");
+
+    // An existing backup is never moved.
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--backup-ref", name])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("already exists"));
+    assert_eq!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_dry_run_works_on_a_dirty_worktree() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-dry-run-dirty");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"hello").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b\n").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+    fs::write(repo_dir.join("a.txt"), b"hello\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add eof newline"]);
+    let head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+
+    // Unstaged edits, including to the file that would be fixed.
+    fs::write(repo_dir.join("a.txt"), b"hello\nworld\n").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"edited\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--dry-run", "--diff"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    // The diff is against the committed copy, not the edited one.
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "n=1 match: a.txt
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-hello
++hello
+\\ No newline at end of file
+"
+    );
+    assert_eq!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+    assert_eq!(fs::read(repo_dir.join("a.txt")).unwrap(), b"hello\nworld\n");
+
+    // Without --dry-run the tree still has to be clean.
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("working tree is not clean"));
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n1_in_a_linked_worktree_ignores_a_rebase_in_the_main_one() {
+    let root = unique_temp_dir("git-fix-eof-newline-n1-linked-worktree");
+    let main_dir = root.join("main");
+    let linked_dir = root.join("linked");
+    let other_dir = root.join("other");
+    fs::create_dir_all(&main_dir).unwrap();
+
+    run_git(&main_dir, &["init"]);
+    run_git(&main_dir, &["config", "user.name", "Test User"]);
+    run_git(&main_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(main_dir.join("a.txt"), b"hello").unwrap();
+    fs::write(main_dir.join("c.txt"), b"base\n").unwrap();
+    run_git(&main_dir, &["add", "."]);
+    run_git(&main_dir, &["commit", "-m", "base"]);
+    run_git(&main_dir, &["branch", "side"]);
+    run_git(&main_dir, &["branch", "fix"]);
+    fs::write(main_dir.join("c.txt"), b"main\n").unwrap();
+    run_git(&main_dir, &["commit", "-am", "main change"]);
+    run_git(&main_dir, &["switch", "side"]);
+    fs::write(main_dir.join("c.txt"), b"side\n").unwrap();
+    run_git(&main_dir, &["commit", "-am", "side change"]);
+    run_git(&main_dir, &["switch", "-"]);
+
+    let worktree = |dir: &Path, branch: &str| {
+        let dir = dir.to_str().unwrap();
+        run_git(&main_dir, &["worktree", "add", dir, branch]);
+    };
+    worktree(&linked_dir, "fix");
+    worktree(&other_dir, "side");
+
+    // Leave a conflicted rebase in the main worktree.
+    let status = Command::new("git")
+        .current_dir(&main_dir)
+        .args(["rebase", "side"])
+        .output()
+        .unwrap()
+        .status;
+    assert!(!status.success());
+
+    fs::write(linked_dir.join("a.txt"), b"hello\n").unwrap();
+    run_git(&linked_dir, &["commit", "-am", "add eof newline"]);
+    fs::write(linked_dir.join("notes.txt"), b"local\n").unwrap();
+    fs::write(other_dir.join("c.txt"), b"other\n").unwrap();
+
+    // Another worktree stashes while the amend runs, on top of our stash.
+    let hooks_dir = main_dir.join(".git").join("hooks");
+    fs::create_dir_all(&hooks_dir).unwrap();
+    let hook = hooks_dir.join("pre-commit");
+    fs::write(
+        &hook,
+        format!(
+            "#!/bin/sh\nenv -u GIT_INDEX_FILE -u GIT_DIR git -C '{}' stash push -q -m other\n",
+            other_dir.display()
+        ),
+    )
+    .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&linked_dir)
+        .args(["--n", "1", "--autostash"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(git_stdout(&linked_dir, &["show", "HEAD:a.txt"]), b"hello");
+    assert_eq!(fs::read(linked_dir.join("notes.txt")).unwrap(), b"local\n");
+    assert_eq!(fs::read(linked_dir.join("c.txt")).unwrap(), b"base\n");
+    let stashes = git_stdout(&main_dir, &["stash", "list", "--format=%s"]);
+    assert_eq!(String::from_utf8(stashes).unwrap().trim(), "On side: other");
+
+    // The rebase still blocks the main worktree itself.
+    fs::remove_file(&hook).unwrap();
+    let out = Command::new(bin)
+        .current_dir(&main_dir)
+        .args(["--n", "2", "--dry-run"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("ongoing rebase"));
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn n1_format_json_keeps_stdout_to_json_objects() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n1-json");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"x").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("a.txt"), b"x\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "adds a newline"]);
+    let head = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"])).unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "1", "--json", "--report-bytes-per-commit"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    // git's own `[branch sha] message` amend line goes to stderr.
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            r#"{"path":"a.txt","target":"commit","action":"strip","terminator":"lf"}"#.to_string(),
+            format!(
+                r#"{{"commit":"{}","bytes_removed":1,"files":1}}"#,
+                head.trim()
+            ),
+            r#"{"changed":1,"skipped":0}"#.to_string(),
+        ]
+    );
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:a.txt"]), b"x");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
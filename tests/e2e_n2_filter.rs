@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -42,6 +42,51 @@ fn git_stdout(repo_dir: &Path, args: &[&str]) -> Vec<u8> {
     out.stdout
 }
 
+/// Parses one flat JSON object of string, number and `null` values, which is
+/// all `--format json` emits. `null` becomes `None`.
+fn parse_json_object(line: &str) -> BTreeMap<String, Option<String>> {
+    fn string(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+        assert_eq!(chars.next(), Some('"'));
+        let mut out = String::new();
+        loop {
+            match chars.next().expect("unterminated string") {
+                '"' => return out,
+                '\\' => match chars.next().unwrap() {
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    'u' => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        out.push(char::from_u32(u32::from_str_radix(&hex, 16).unwrap()).unwrap());
+                    }
+                    c => out.push(c),
+                },
+                c => out.push(c),
+            }
+        }
+    }
+
+    let mut fields = BTreeMap::new();
+    let mut chars = line.trim().chars().peekable();
+    assert_eq!(chars.next(), Some('{'), "{line}");
+    while chars.peek() != Some(&'}') {
+        let key = string(&mut chars);
+        assert_eq!(chars.next(), Some(':'), "{line}");
+        let value = if chars.peek() == Some(&'"') {
+            Some(string(&mut chars))
+        } else {
+            let raw: String =
+                std::iter::from_fn(|| chars.next_if(|c| *c != ',' && *c != '}')).collect();
+            (raw != "null").then_some(raw)
+        };
+        fields.insert(key, value);
+        chars.next_if_eq(&',');
+    }
+    assert_eq!(chars.next(), Some('}'), "{line}");
+    assert_eq!(chars.next(), None, "{line}");
+    fields
+}
+
 #[test]
 fn n2_filters_by_author_email() {
     let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-filter");
@@ -110,3 +155,1568 @@ fn n2_filters_by_author_email() {
 
     fs::remove_dir_all(&repo_dir).unwrap();
 }
+
+#[test]
+fn n2_filters_by_committer_email() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-committer-filter");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    // Alice authors both commits; only the first is committed by Carol, as
+    // a cherry-pick would record it.
+    let mut envs = HashMap::new();
+    envs.insert("GIT_AUTHOR_NAME", "Alice");
+    envs.insert("GIT_AUTHOR_EMAIL", "alice@example.com");
+    envs.insert("GIT_COMMITTER_NAME", "Carol");
+    envs.insert("GIT_COMMITTER_EMAIL", "carol@example.com");
+    fs::write(repo_dir.join("a.txt"), b"a\n").unwrap();
+    run_git_env(&repo_dir, &["commit", "-am", "carol commits"], &envs);
+
+    envs.insert("GIT_COMMITTER_NAME", "Alice");
+    envs.insert("GIT_COMMITTER_EMAIL", "alice@example.com");
+    fs::write(repo_dir.join("b.txt"), b"b\n").unwrap();
+    run_git_env(&repo_dir, &["commit", "-am", "alice commits"], &envs);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args([
+            "--n",
+            "2",
+            "--author-email",
+            "alice@",
+            "--committer-email",
+            "CAROL@example.com",
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:a.txt"]), b"a");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:b.txt"]), b"b\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_reports_commits_modified_by_filter_branch() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-report");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let file_path = repo_dir.join("a.txt");
+    fs::write(&file_path, b"x").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    fs::write(&file_path, b"x1\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "adds newline"]);
+    let adds = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"]))
+        .unwrap()
+        .trim()
+        .to_string();
+
+    fs::write(&file_path, b"x2\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "keeps newline"]);
+    let keeps = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"]))
+        .unwrap()
+        .trim()
+        .to_string();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("filter-branch processed 2 commits, modified 1"));
+    assert!(stdout.contains(&format!("rewrote {adds}: a.txt")));
+    assert!(!stdout.contains(&format!("rewrote {keeps}")));
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_group_by_author_counts_files_and_commits() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-group-by-author");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    for name in ["a.txt", "b.txt", "c.txt", "d.txt"] {
+        fs::write(repo_dir.join(name), b"x").unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    let alice: HashMap<&str, &str> = [
+        ("GIT_AUTHOR_NAME", "Alice"),
+        ("GIT_AUTHOR_EMAIL", "alice@example.com"),
+    ]
+    .into_iter()
+    .collect();
+    let bob: HashMap<&str, &str> = [
+        ("GIT_AUTHOR_NAME", "Bob"),
+        ("GIT_AUTHOR_EMAIL", "bob@example.com"),
+    ]
+    .into_iter()
+    .collect();
+
+    for (files, envs) in [
+        (&["a.txt", "b.txt"][..], &alice),
+        (&["c.txt"][..], &bob),
+        (&["d.txt"][..], &alice),
+    ] {
+        for name in files {
+            fs::write(repo_dir.join(name), b"x\n").unwrap();
+        }
+        run_git(&repo_dir, &["add", "."]);
+        run_git_env(&repo_dir, &["commit", "-m", "change"], envs);
+    }
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let run = |extra: &[&str]| {
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(["--n", "3", "--dry-run", "--group-by-author"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let stdout = run(&[]);
+    assert!(stdout.contains("alice@example.com: 3 files across 2 commits\n"));
+    assert!(stdout.contains("bob@example.com: 1 file across 1 commit\n"));
+
+    let groups: Vec<_> = run(&["--json"])
+        .lines()
+        .map(parse_json_object)
+        .filter(|object| object.contains_key("author_email"))
+        .collect();
+    let field = |v: &str| Some(v.to_string());
+    assert_eq!(groups.len(), 2, "{groups:?}");
+    assert_eq!(groups[0]["author_email"], field("alice@example.com"));
+    assert_eq!(groups[0]["files"], field("3"));
+    assert_eq!(groups[0]["commits"], field("2"));
+    assert_eq!(groups[1]["author_email"], field("bob@example.com"));
+    assert_eq!(groups[1]["files"], field("1"));
+    assert_eq!(groups[1]["commits"], field("1"));
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_full_history_finds_side_branch_commit() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-full-history");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init", "-b", "main"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(repo_dir.join("a.txt"), b"x").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"y").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    run_git(&repo_dir, &["checkout", "-b", "side"]);
+    fs::write(repo_dir.join("a.txt"), b"x\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "side adds newline"]);
+    let side = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"]))
+        .unwrap()
+        .trim()
+        .to_string();
+
+    run_git(&repo_dir, &["checkout", "main"]);
+    fs::write(repo_dir.join("b.txt"), b"y2").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "main change"]);
+    run_git(&repo_dir, &["merge", "--no-ff", "-m", "merge side", "side"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let first_parent = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2", "--dry-run"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(first_parent.stdout).unwrap();
+    assert!(!stdout.contains(&side));
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2", "--full-history", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains(&format!("n>1 match commit: {side}")));
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2", "--full-history"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:a.txt"]), b"x");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD^2:a.txt"]), b"x");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:b.txt"]), b"y2");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_net_reports_and_fixes_intermediate_churn() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-net");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let a_path = repo_dir.join("a.txt");
+    let b_path = repo_dir.join("b.txt");
+    fs::write(&a_path, b"x").unwrap();
+    fs::write(&b_path, b"y").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    let mut commits = Vec::new();
+    for (path, content) in [(&a_path, &b"x\n"[..]), (&b_path, b"y2"), (&a_path, b"x2")] {
+        fs::write(path, content).unwrap();
+        run_git(&repo_dir, &["commit", "-am", "change"]);
+        let head = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"])).unwrap();
+        commits.push(head.trim().to_string());
+    }
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "3", "--net"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        format!(
+            "net churn: a.txt (added in {}, removed in {})\n",
+            commits[0], commits[2]
+        )
+    );
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "3", "--net", "--fix-intermediate"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let revs = String::from_utf8(git_stdout(&repo_dir, &["rev-list", "HEAD"])).unwrap();
+    for rev in revs.lines() {
+        let bytes = git_stdout(&repo_dir, &["show", &format!("{rev}:a.txt")]);
+        assert!(!bytes.ends_with(b"\n"), "{rev} still has the newline");
+    }
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:a.txt"]), b"x2");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:b.txt"]), b"y2");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n3_net_follows_policies_and_author_filters() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n3-net-policy");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"x").unwrap();
+    fs::write(repo_dir.join("r.md"), b"r\n").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    // Someone else adds a newline to a.txt; r.md loses its newline and gets
+    // it back.
+    fs::write(repo_dir.join("a.txt"), b"x\n").unwrap();
+    run_git(
+        &repo_dir,
+        &[
+            "-c",
+            "user.email=other@example.com",
+            "commit",
+            "-am",
+            "add newline",
+        ],
+    );
+    fs::write(repo_dir.join("r.md"), b"r").unwrap();
+    fs::write(repo_dir.join("c.txt"), b"c\n").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "drop newline"]);
+    fs::write(repo_dir.join("a.txt"), b"x2").unwrap();
+    fs::write(repo_dir.join("r.md"), b"r2\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "change back"]);
+    let rev = |spec: &str| {
+        let out = git_stdout(&repo_dir, &["rev-parse", spec]);
+        String::from_utf8(out).unwrap().trim().to_string()
+    };
+    let (other, dropped, back) = (rev("HEAD~2"), rev("HEAD~1"), rev("HEAD"));
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let net = |extra: &[&str]| {
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(["--n", "3", "--net"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8(out.stdout).unwrap()
+    };
+    assert_eq!(
+        net(&[]),
+        format!("net churn: a.txt (added in {other}, removed in {back})\n")
+    );
+    let r_churn = format!("net churn: r.md (added in {dropped}, removed in {back})\n");
+    assert_eq!(
+        net(&["--require-eof-newline-for", "*.md"]),
+        format!("net churn: a.txt (added in {other}, removed in {back})\n{r_churn}")
+    );
+    let filtered = ["--require-eof-newline-for", "*.md"];
+    let filtered = [&filtered[..], &["--author-email", "test@example.com"]].concat();
+    assert_eq!(net(&filtered), r_churn);
+
+    // The fix restores r.md in between and leaves the other author's
+    // commit alone.
+    let rewrite = [&filtered[..], &["--fix-intermediate"]].concat();
+    net(&rewrite);
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:r.md"]), b"r\n");
+    assert_eq!(rev("HEAD~2"), other);
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:r.md"]), b"r2\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_safe_mode_accepts_a_normal_rewrite() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-safe-mode");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let file_path = repo_dir.join("a.txt");
+    fs::write(&file_path, b"x").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    // Only adds the newline, so the rewrite prunes it.
+    fs::write(&file_path, b"x\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "adds newline"]);
+
+    fs::write(repo_dir.join("b.txt"), b"y").unwrap();
+    run_git(&repo_dir, &["add", "b.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "adds b"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2", "--safe-mode"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("filter-branch processed 2 commits, modified 1"));
+    assert_eq!(
+        git_stdout(&repo_dir, &["rev-list", "--count", "HEAD"]),
+        b"2\n"
+    );
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:b.txt"]), b"y");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_commit_order_only_changes_report_order() {
+    // Two commits that each add an EOF newline to a different file.
+    fn setup(repo_dir: &std::path::Path) -> (String, String) {
+        fs::create_dir_all(repo_dir).unwrap();
+        run_git(repo_dir, &["init"]);
+        run_git(repo_dir, &["config", "user.name", "Test User"]);
+        run_git(repo_dir, &["config", "user.email", "test@example.com"]);
+        fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+        fs::write(repo_dir.join("b.txt"), b"b").unwrap();
+        run_git(repo_dir, &["add", "."]);
+        run_git(repo_dir, &["commit", "-m", "base"]);
+
+        let mut commits = Vec::new();
+        for name in ["a.txt", "b.txt"] {
+            fs::write(repo_dir.join(name), b"changed\n").unwrap();
+            run_git(repo_dir, &["add", name]);
+            run_git(repo_dir, &["commit", "-m", name]);
+            let head = String::from_utf8(git_stdout(repo_dir, &["rev-parse", "HEAD"])).unwrap();
+            commits.push(head.trim().to_string());
+        }
+        (commits[0].clone(), commits[1].clone())
+    }
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let mut trees = Vec::new();
+    for order in [None, Some("oldest-first")] {
+        let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-commit-order");
+        let (older, newer) = setup(&repo_dir);
+
+        let mut cmd = Command::new(bin);
+        cmd.current_dir(&repo_dir).args(["--n", "2"]);
+        if let Some(order) = order {
+            cmd.args(["--commit-order", order]);
+        }
+        let out = cmd.output().unwrap();
+        assert!(out.status.success());
+
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        let rewrote: Vec<&str> = stdout
+            .lines()
+            .filter(|l| l.starts_with("rewrote "))
+            .collect();
+        let older_line = format!("rewrote {older}: a.txt");
+        let newer_line = format!("rewrote {newer}: b.txt");
+        let expected = match order {
+            None => [newer_line.as_str(), older_line.as_str()],
+            Some(_) => [older_line.as_str(), newer_line.as_str()],
+        };
+        assert_eq!(rewrote, expected, "{order:?}");
+
+        let tree = |rev: &str| git_stdout(&repo_dir, &["rev-parse", &format!("{rev}^{{tree}}")]);
+        trees.push((tree("HEAD"), tree("HEAD~1")));
+        assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:a.txt"]), b"changed");
+        assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:b.txt"]), b"changed");
+
+        fs::remove_dir_all(&repo_dir).unwrap();
+    }
+    assert_eq!(trees[0], trees[1]);
+}
+
+#[test]
+fn n4_min_commit_depth_leaves_newest_commits_untouched() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n4-min-depth");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    let names = ["a.txt", "b.txt", "c.txt", "d.txt"];
+    for name in names {
+        fs::write(repo_dir.join(name), b"x").unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    // Each of the four commits adds an EOF newline to its own file.
+    let mut commits = Vec::new();
+    for name in names {
+        fs::write(repo_dir.join(name), b"y\n").unwrap();
+        run_git(&repo_dir, &["add", name]);
+        run_git(&repo_dir, &["commit", "-m", name]);
+        let head = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"])).unwrap();
+        commits.push(head.trim().to_string());
+    }
+    let tree = |rev: &str| git_stdout(&repo_dir, &["rev-parse", &format!("{rev}^{{tree}}")]);
+    let protected_trees = (tree(&commits[2]), tree(&commits[3]));
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "4", "--min-commit-depth", "2"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("filter-branch processed 4 commits, modified 2"));
+    assert!(stdout.contains(&format!("rewrote {}: a.txt", commits[0])));
+    assert!(stdout.contains(&format!("rewrote {}: b.txt", commits[1])));
+    assert!(!stdout.contains(&format!("rewrote {}", commits[2])));
+    assert!(!stdout.contains(&format!("rewrote {}", commits[3])));
+
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~3:a.txt"]), b"y");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~2:b.txt"]), b"y");
+    assert_eq!((tree("HEAD~1"), tree("HEAD")), protected_trees);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_annotate_notes_attaches_to_rewritten_commits() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-notes");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"x").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("a.txt"), b"x1\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "adds newline"]);
+    fs::write(repo_dir.join("b.txt"), b"y").unwrap();
+    run_git(&repo_dir, &["add", "b.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "unrelated"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2", "--annotate-notes"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    // The note sits on the rewritten commit, not the original.
+    let note = git_stdout(&repo_dir, &["notes", "--ref=fix-eof", "show", "HEAD~1"]);
+    assert_eq!(note, b"strip-one: a.txt\n");
+    let listed = git_stdout(&repo_dir, &["notes", "--ref=fix-eof", "list"]);
+    assert_eq!(String::from_utf8(listed).unwrap().lines().count(), 1);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n3_emit_rebase_todo_marks_matching_commits_edit() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n3-rebase-todo");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    let mut commits = Vec::new();
+    for (name, bytes, message) in [
+        ("a.txt", &b"a\n"[..], "adds a newline"),
+        ("c.txt", &b"c"[..], "unrelated"),
+        ("b.txt", &b"b\n"[..], "adds b newline"),
+    ] {
+        fs::write(repo_dir.join(name), bytes).unwrap();
+        run_git(&repo_dir, &["add", name]);
+        run_git(&repo_dir, &["commit", "-m", message]);
+        let head = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"])).unwrap();
+        commits.push(head.trim().to_string());
+    }
+    let old_head = commits[2].clone();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let todo = repo_dir.join("todo.txt");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "3", "--emit-rebase-todo", todo.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    // Nothing is rewritten.
+    let head = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"])).unwrap();
+    assert_eq!(head.trim(), old_head);
+
+    let text = fs::read_to_string(&todo).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 5, "{text}");
+    assert_eq!(lines[0], format!("edit {} adds a newline", commits[0]));
+    assert!(lines[1].starts_with("exec ") && lines[1].ends_with(" --n 1 --in-rebase"));
+    assert_eq!(lines[2], format!("pick {} unrelated", commits[1]));
+    assert_eq!(lines[3], format!("edit {} adds b newline", commits[2]));
+    assert_eq!(lines[4], lines[1]);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_report_bytes_per_commit_totals_removed_bytes() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-bytes");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    run_git(&repo_dir, &["config", "core.autocrlf", "false"]);
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(repo_dir.join(name), b"x").unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    // A CRLF (2 bytes) and an LF (1 byte) in one commit, one LF in the next.
+    fs::write(repo_dir.join("a.txt"), b"x\r\n").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"x\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "two files"]);
+    let older = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"])).unwrap();
+    fs::write(repo_dir.join("c.txt"), b"x\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "one file"]);
+    let newer = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"])).unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let run = |extra: &[&str]| {
+        // Each run starts from the unfixed commits.
+        run_git(&repo_dir, &["reset", "-q", "--hard", newer.trim()]);
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(["--n", "2", "--report-bytes-per-commit"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let stdout = run(&[]);
+    let totals: Vec<&str> = stdout.lines().filter(|l| l.contains(" removed ")).collect();
+    assert_eq!(
+        totals,
+        [
+            format!("{} removed 1 byte across 1 file", newer.trim()),
+            format!("{} removed 3 bytes across 2 files", older.trim()),
+        ]
+    );
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:a.txt"]), b"x");
+
+    let totals: Vec<_> = run(&["--format", "json"])
+        .lines()
+        .map(parse_json_object)
+        .filter(|object| object.contains_key("bytes_removed"))
+        .collect();
+    let field = |v: &str| Some(v.to_string());
+    assert_eq!(totals.len(), 2, "{totals:?}");
+    assert_eq!(totals[0]["commit"], field(newer.trim()));
+    assert_eq!(totals[0]["bytes_removed"], field("1"));
+    assert_eq!(totals[0]["files"], field("1"));
+    assert_eq!(totals[1]["commit"], field(older.trim()));
+    assert_eq!(totals[1]["bytes_removed"], field("3"));
+    assert_eq!(totals[1]["files"], field("2"));
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:a.txt"]), b"x");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+/// Builds a copy of git's exec path whose `git` is a wrapper that fails
+/// `git add` while filter-branch rewrites `fail_at`, interrupting the
+/// tree-filter mid-way. git puts its exec path first on `PATH` for
+/// everything it runs, so this is what the tree-filter's `git` resolves to.
+#[cfg(unix)]
+fn failing_git_exec_path(dir: &Path, fail_at: &str) -> PathBuf {
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    let real = String::from_utf8(git_stdout(dir, &["--exec-path"])).unwrap();
+    let real = PathBuf::from(real.trim());
+    let fake = dir.join(format!("fake-exec-path-{fail_at}"));
+    fs::create_dir_all(&fake).unwrap();
+    for entry in fs::read_dir(&real).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_name() != "git" {
+            symlink(entry.path(), fake.join(entry.file_name())).unwrap();
+        }
+    }
+    let wrapper = fake.join("git");
+    fs::write(
+        &wrapper,
+        format!(
+            "#!/bin/sh\nif [ \"$1\" = add ] && [ \"$GIT_COMMIT\" = {fail_at} ]; then exit 1; fi\nexec {} \"$@\"\n",
+            real.join("git").display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&wrapper, fs::Permissions::from_mode(0o755)).unwrap();
+    fake
+}
+
+#[cfg(unix)]
+#[test]
+fn n2_interrupted_rewrite_can_be_aborted_and_resumed() {
+    let root = unique_temp_dir("git-fix-eof-newline-n2-resume");
+    let repo_dir = root.join("repo");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    let mut commits = Vec::new();
+    for name in ["a.txt", "b.txt"] {
+        fs::write(repo_dir.join(name), b"x\n").unwrap();
+        run_git(&repo_dir, &["commit", "-am", name]);
+        let head = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"])).unwrap();
+        commits.push(head.trim().to_string());
+    }
+    let old_head = commits[1].clone();
+    let head = || {
+        String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"]))
+            .unwrap()
+            .trim()
+            .to_string()
+    };
+
+    // The tree-filter fails on the second commit, after the first was done.
+    let fake_exec_path = failing_git_exec_path(&root, &commits[1]);
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .env("GIT_EXEC_PATH", &fake_exec_path)
+        .args(["--n", "2"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+    let state = repo_dir.join(".git/git-fix-eof-newline-run-state");
+    assert!(state.exists());
+
+    // A plain rerun refuses to start over the leftovers.
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--resume"));
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .arg("--abort")
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(head(), old_head);
+    assert!(!state.exists());
+    assert!(!repo_dir.join(".git/git-fix-eof-newline-rewrite").exists());
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .arg("--abort")
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+
+    // Interrupt again, then resume: the rewrite completes this time.
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .env("GIT_EXEC_PATH", &fake_exec_path)
+        .args(["--n", "2"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2", "--resume"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(
+        stdout.contains(&format!("restored master to {old_head}")) || stdout.contains("restored ")
+    );
+    assert!(stdout.contains("filter-branch processed 2 commits, modified 2"));
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:a.txt"]), b"x");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:b.txt"]), b"x");
+    assert!(!state.exists());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn n2_strips_newline_added_while_renaming() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-rename");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let content = b"line one\nline two\nline three\nline four";
+    fs::write(repo_dir.join("a.txt"), content).unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add a"]);
+
+    run_git(&repo_dir, &["mv", "a.txt", "b.txt"]);
+    let mut renamed = content.to_vec();
+    renamed.push(b'\n');
+    fs::write(repo_dir.join("b.txt"), &renamed).unwrap();
+    run_git(&repo_dir, &["add", "b.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "rename a to b"]);
+
+    fs::write(repo_dir.join("c.txt"), b"c").unwrap();
+    run_git(&repo_dir, &["add", "c.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add c"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:b.txt"]), content);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_compares_merge_commits_against_first_parent() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-merge");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init", "-b", "main"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    fs::write(repo_dir.join("a.txt"), b"x").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"y").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    run_git(&repo_dir, &["checkout", "-b", "side"]);
+    fs::write(repo_dir.join("a.txt"), b"x\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "side adds newline"]);
+
+    run_git(&repo_dir, &["checkout", "main"]);
+    fs::write(repo_dir.join("b.txt"), b"y2").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "main change"]);
+    run_git(&repo_dir, &["merge", "--no-ff", "-m", "merge side", "side"]);
+    let merge = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"]))
+        .unwrap()
+        .trim()
+        .to_string();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2", "--dry-run"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let matches: Vec<&str> = stdout
+        .lines()
+        .filter(|l| l.starts_with("n>1 match commit: "))
+        .collect();
+    assert_eq!(matches, [format!("n>1 match commit: {merge}")]);
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:a.txt"]), b"x");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:b.txt"]), b"y2");
+    let parents = String::from_utf8(git_stdout(
+        &repo_dir,
+        &["rev-list", "--parents", "-n", "1", "HEAD"],
+    ))
+    .unwrap();
+    assert_eq!(parents.split_whitespace().count(), 3);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_handles_tabs_and_newlines_in_file_names() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-odd-names");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+
+    let names = ["tab\there.txt", "new\nline.txt"];
+    for name in names {
+        fs::write(repo_dir.join(name), b"x").unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+
+    for name in names {
+        fs::write(repo_dir.join(name), b"x\n").unwrap();
+    }
+    run_git(&repo_dir, &["commit", "-am", "add newlines"]);
+    fs::write(repo_dir.join("other.txt"), b"o").unwrap();
+    run_git(&repo_dir, &["add", "other.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "other"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("tab\there.txt"), "{stdout}");
+    for name in names {
+        assert_eq!(
+            git_stdout(&repo_dir, &["show", &format!("HEAD~1:{name}")]),
+            b"x",
+            "{name:?}"
+        );
+    }
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn fixeof_n_config_is_the_default_depth() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-config-n");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"a").unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b\n").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("a.txt"), b"a\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "add newline"]);
+    let added = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"])).unwrap();
+    fs::write(repo_dir.join("b.txt"), b"b2\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "unrelated"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let run = |args: &[&str]| {
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    // Only HEAD is checked by default, and HEAD is fine.
+    assert_eq!(run(&["--dry-run"]), "");
+
+    run_git(&repo_dir, &["config", "fixeof.n", "2"]);
+    let stdout = run(&["--dry-run"]);
+    assert!(
+        stdout.contains(&format!("n>1 match commit: {}", added.trim())),
+        "{stdout}"
+    );
+
+    // An explicit --n still wins.
+    assert_eq!(run(&["--dry-run", "--n", "1"]), "");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn range_rewrites_only_the_commits_in_it() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-range");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    let names = ["a.txt", "b.txt", "c.txt"];
+    for name in names {
+        fs::write(repo_dir.join(name), b"x").unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    for name in names {
+        fs::write(repo_dir.join(name), b"y\n").unwrap();
+        run_git(&repo_dir, &["commit", "-am", &format!("newline in {name}")]);
+    }
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--range", "HEAD~3..HEAD~1", "--n", "2"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(64));
+
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--range", "HEAD~3..HEAD~1"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~2:a.txt"]), b"y");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:b.txt"]), b"y");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:c.txt"]), b"y\n");
+    let subjects = String::from_utf8(git_stdout(&repo_dir, &["log", "--format=%s"])).unwrap();
+    assert_eq!(
+        subjects,
+        "newline in c.txt\nnewline in b.txt\nnewline in a.txt\nbase\n"
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n3_since_skips_older_commits_and_composes_with_author_filter() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n3-since");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    let names = ["old.txt", "bob.txt", "new.txt"];
+    for name in names {
+        fs::write(repo_dir.join(name), b"x").unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    let mut envs = HashMap::new();
+    envs.insert("GIT_AUTHOR_DATE", "2020-01-01T12:00:00Z");
+    envs.insert("GIT_COMMITTER_DATE", "2020-01-01T12:00:00Z");
+    run_git_env(&repo_dir, &["commit", "-m", "base"], &envs);
+
+    // `--since` looks at the committer date, so both dates are set.
+    let commits = [
+        ("old.txt", "alice", "2020-02-01T12:00:00Z"),
+        ("bob.txt", "bob", "2024-02-01T12:00:00Z"),
+        ("new.txt", "alice", "2024-03-01T12:00:00Z"),
+    ];
+    for (name, who, date) in commits {
+        fs::write(repo_dir.join(name), b"y\n").unwrap();
+        let email = format!("{who}@example.com");
+        let mut envs = HashMap::new();
+        envs.insert("GIT_AUTHOR_EMAIL", email.as_str());
+        envs.insert("GIT_AUTHOR_DATE", date);
+        envs.insert("GIT_COMMITTER_DATE", date);
+        run_git_env(&repo_dir, &["commit", "-am", name], &envs);
+    }
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let status = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args([
+            "--n",
+            "3",
+            "--since",
+            "2024-01-01",
+            "--author-email",
+            "alice@",
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~2:old.txt"]), b"y\n");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:bob.txt"]), b"y\n");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:new.txt"]), b"y");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n3_engine_rebase_amends_only_the_matching_commit() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n3-engine-rebase");
+    setup_engine_repo(&repo_dir);
+    let rev = |spec: &str| String::from_utf8(git_stdout(&repo_dir, &["rev-parse", spec])).unwrap();
+    let first = rev("HEAD~2");
+    let middle = rev("HEAD~1");
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "3", "--engine", "rebase"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        format!("rewrote {}: b.txt\n", middle.trim())
+    );
+
+    // The commit before the match is untouched; the match is amended and
+    // the commit after it is replayed on top, keeping the fix.
+    assert_eq!(rev("HEAD~2"), first);
+    assert_ne!(rev("HEAD~1"), middle);
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:b.txt"]), b"y");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:b.txt"]), b"y");
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:c.txt"]), b"y");
+    let subjects = String::from_utf8(git_stdout(&repo_dir, &["log", "--format=%s"])).unwrap();
+    assert_eq!(subjects, "edit c\nnewline in b\nedit a\nbase\n");
+    assert!(!repo_dir.join(".git/rebase-merge").exists());
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+/// Three commits on top of a base; only the middle one adds an EOF newline.
+fn setup_engine_repo(repo_dir: &Path) {
+    fs::create_dir_all(repo_dir).unwrap();
+    run_git(repo_dir, &["init"]);
+    run_git(repo_dir, &["config", "user.name", "Test User"]);
+    run_git(repo_dir, &["config", "user.email", "test@example.com"]);
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(repo_dir.join(name), b"x").unwrap();
+    }
+    run_git(repo_dir, &["add", "."]);
+    run_git(repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("a.txt"), b"y").unwrap();
+    run_git(repo_dir, &["commit", "-am", "edit a"]);
+    fs::write(repo_dir.join("b.txt"), b"y\n").unwrap();
+    run_git(repo_dir, &["commit", "-am", "newline in b"]);
+    fs::write(repo_dir.join("c.txt"), b"y").unwrap();
+    run_git(repo_dir, &["commit", "-am", "edit c"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn engine_filter_repo_without_git_filter_repo_is_a_clear_error() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-engine-filter-repo-missing");
+    setup_engine_repo(&repo_dir);
+    let head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+
+    // A PATH with nothing but git on it.
+    let bin_dir = unique_temp_dir("git-fix-eof-newline-engine-filter-repo-path");
+    fs::create_dir_all(&bin_dir).unwrap();
+    let git = String::from_utf8(
+        Command::new("sh")
+            .args(["-c", "command -v git"])
+            .output()
+            .unwrap()
+            .stdout,
+    )
+    .unwrap();
+    std::os::unix::fs::symlink(git.trim(), bin_dir.join("git")).unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .env("PATH", &bin_dir)
+        .args(["--n", "3", "--engine", "filter-repo"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("needs git-filter-repo"), "{stderr}");
+    assert!(stderr.contains("install it"), "{stderr}");
+    assert_eq!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+    fs::remove_dir_all(&bin_dir).unwrap();
+}
+
+#[test]
+#[ignore = "needs git-filter-repo"]
+fn engine_filter_repo_matches_the_default_engine_on_fixed_commits() {
+    let installed = Command::new("git")
+        .args(["filter-repo", "--version"])
+        .output()
+        .is_ok_and(|out| out.status.success());
+    assert!(installed, "git-filter-repo is not installed");
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let mut histories = Vec::new();
+    for engine in ["filter-branch", "rebase", "filter-repo"] {
+        let repo_dir = unique_temp_dir(&format!("git-fix-eof-newline-engine-parity-{engine}"));
+        setup_engine_repo(&repo_dir);
+        let middle = git_stdout(&repo_dir, &["rev-parse", "HEAD~1"]);
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(["--n", "3", "--engine", engine])
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{engine}: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        let stdout = String::from_utf8(out.stdout).unwrap();
+        let rewrote = format!(
+            "rewrote {}: b.txt\n",
+            String::from_utf8(middle).unwrap().trim()
+        );
+        assert!(stdout.ends_with(&rewrote), "{engine}: {stdout}");
+        assert!(git_stdout(&repo_dir, &["status", "--porcelain"]).is_empty());
+        let log = git_stdout(&repo_dir, &["log", "--format=%T %s"]);
+        let head_b = git_stdout(&repo_dir, &["show", "HEAD:b.txt"]);
+        histories.push((String::from_utf8(log).unwrap(), head_b));
+        fs::remove_dir_all(&repo_dir).unwrap();
+    }
+    let [filter_branch, rebase, filter_repo] = &histories[..] else {
+        unreachable!()
+    };
+    assert_eq!(filter_repo, rebase);
+    // The fixed commit and everything before it agree with filter-branch;
+    // only there does the newline come back in the commit after the fix.
+    let older = |log: &str| log.lines().skip(1).collect::<Vec<_>>().join("\n");
+    assert_eq!(older(&filter_repo.0), older(&filter_branch.0));
+    assert_eq!(filter_repo.1, b"y");
+    assert_eq!(filter_branch.1, b"y\n");
+}
+
+#[test]
+fn n2_large_blob_with_unchanged_eof_is_only_read_at_its_ends() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-blob-ends");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    run_git(&repo_dir, &["commit", "--allow-empty", "-m", "base"]);
+    let mut big = vec![b'x'; 4 << 20];
+    fs::write(repo_dir.join("big.txt"), &big).unwrap();
+    run_git(&repo_dir, &["add", "big.txt"]);
+    run_git(&repo_dir, &["commit", "-m", "add big"]);
+    // Changed in the middle; still no newline at the end.
+    big[1 << 20] = b'y';
+    fs::write(repo_dir.join("big.txt"), &big).unwrap();
+    run_git(&repo_dir, &["commit", "-am", "edit big"]);
+
+    let log_file = repo_dir.join("run.log");
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2", "--check", "--log-file"])
+        .arg(&log_file)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let log = fs::read_to_string(&log_file).unwrap();
+    // Both sides of the edit, neither kept whole.
+    assert!(log.contains("blob reads: 0 whole, 2 ends only"), "{log}");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_commit_touching_200_files() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-200-files");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("README"), b"base\n").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    for i in 0..200 {
+        fs::write(repo_dir.join(format!("f{i:03}.txt")), format!("{i}")).unwrap();
+    }
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "add files"]);
+    for i in 0..200 {
+        fs::write(repo_dir.join(format!("f{i:03}.txt")), format!("{i}\n")).unwrap();
+    }
+    // Keeps the commit from becoming empty (and pruned) once fixed.
+    fs::write(repo_dir.join("README"), b"edited\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "add newlines"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let started = std::time::Instant::now();
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2", "--dry-run"])
+        .output()
+        .unwrap();
+    eprintln!("scanned 200 changed files in {:?}", started.elapsed());
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let head = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD"])).unwrap();
+    assert!(
+        stdout.contains(&format!("n>1 match commit: {}", head.trim())),
+        "{stdout}"
+    );
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "2"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    for i in [0, 99, 199] {
+        let spec = format!("HEAD:f{i:03}.txt");
+        assert_eq!(
+            git_stdout(&repo_dir, &["show", &spec]),
+            format!("{i}").as_bytes()
+        );
+    }
+    let changed = git_stdout(&repo_dir, &["diff", "--name-only", "HEAD~1", "HEAD"]);
+    assert_eq!(changed, b"README\n");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n20_parallel_scan_finds_the_same_commits_as_a_serial_one() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n20-jobs");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("base.txt"), b"base").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    // Every third commit adds a newline to its file.
+    for i in 0..20 {
+        let name = format!("f{i:02}.txt");
+        fs::write(repo_dir.join(&name), b"x").unwrap();
+        run_git(&repo_dir, &["add", &name]);
+        run_git(&repo_dir, &["commit", "-m", &format!("add {name}")]);
+        let content: &[u8] = if i % 3 == 0 { b"y\n" } else { b"y" };
+        fs::write(repo_dir.join(&name), content).unwrap();
+        run_git(&repo_dir, &["commit", "-am", &format!("edit {name}")]);
+    }
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let scan = |jobs: &str| {
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(["--n", "40", "--dry-run", "--jobs", jobs])
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let serial = scan("1");
+    assert_eq!(serial.matches("n>1 match commit: ").count(), 7, "{serial}");
+    assert_eq!(scan("8"), serial);
+    assert_eq!(scan("3"), serial);
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+/// The tree-filter and rebase `exec` lines embed the executable's path in
+/// a command for Git for Windows' `sh`.
+#[cfg(windows)]
+#[test]
+fn windows_multi_commit_fix_with_each_engine() {
+    for engine in ["filter-branch", "rebase"] {
+        let repo_dir = unique_temp_dir(&format!("git-fix-eof-newline-windows-{engine}"));
+        setup_engine_repo(&repo_dir);
+        run_git(&repo_dir, &["config", "core.autocrlf", "false"]);
+
+        let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+        let out = Command::new(bin)
+            .current_dir(&repo_dir)
+            .args(["--n", "3", "--engine", engine])
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{engine}: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:b.txt"]), b"y");
+        assert_eq!(git_stdout(&repo_dir, &["show", "HEAD:c.txt"]), b"y");
+
+        fs::remove_dir_all(&repo_dir).unwrap();
+    }
+}
+
+#[test]
+fn n3_backup_ref_points_at_the_original_head() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n3-backup-ref");
+    setup_engine_repo(&repo_dir);
+    let head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "3", "--backup-ref", "before-fix"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        stdout.contains("saved original HEAD as refs/fix-eof-backup/before-fix\n"),
+        "{stdout}"
+    );
+    assert_ne!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+    assert_eq!(
+        git_stdout(&repo_dir, &["rev-parse", "refs/fix-eof-backup/before-fix"]),
+        head
+    );
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n3_autostash_restores_local_changes_after_the_rewrite() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n3-autostash");
+    setup_engine_repo(&repo_dir);
+    let head = git_stdout(&repo_dir, &["rev-parse", "HEAD"]);
+
+    // A staged edit, an unstaged edit and an untracked file.
+    fs::write(repo_dir.join("a.txt"), b"staged\n").unwrap();
+    run_git(&repo_dir, &["add", "a.txt"]);
+    fs::write(repo_dir.join("c.txt"), b"unstaged").unwrap();
+    fs::write(repo_dir.join("new.txt"), b"untracked").unwrap();
+    let status = git_stdout(&repo_dir, &["status", "--porcelain"]);
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "3"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(70));
+
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args(["--n", "3", "--autostash"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("stashed local changes as "), "{stdout}");
+    assert!(stdout.contains("restored local changes"), "{stdout}");
+
+    assert_ne!(git_stdout(&repo_dir, &["rev-parse", "HEAD"]), head);
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:b.txt"]), b"y");
+    assert_eq!(git_stdout(&repo_dir, &["status", "--porcelain"]), status);
+    assert_eq!(git_stdout(&repo_dir, &["show", ":a.txt"]), b"staged\n");
+    assert_eq!(fs::read(repo_dir.join("c.txt")).unwrap(), b"unstaged");
+    assert_eq!(fs::read(repo_dir.join("new.txt")).unwrap(), b"untracked");
+    assert_eq!(git_stdout(&repo_dir, &["stash", "list"]), b"");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
+
+#[test]
+fn n2_format_json_reports_come_before_the_summary() {
+    let repo_dir = unique_temp_dir("git-fix-eof-newline-n2-json-reports");
+    fs::create_dir_all(&repo_dir).unwrap();
+
+    run_git(&repo_dir, &["init"]);
+    run_git(&repo_dir, &["config", "user.name", "Test User"]);
+    run_git(&repo_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(repo_dir.join("a.txt"), b"x").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "base"]);
+    fs::write(repo_dir.join("a.txt"), b"x\n").unwrap();
+    run_git(&repo_dir, &["commit", "-am", "adds a newline"]);
+    fs::write(repo_dir.join("b.txt"), b"y").unwrap();
+    run_git(&repo_dir, &["add", "."]);
+    run_git(&repo_dir, &["commit", "-m", "unrelated"]);
+    let fixed = String::from_utf8(git_stdout(&repo_dir, &["rev-parse", "HEAD~1"])).unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&repo_dir)
+        .args([
+            "--n",
+            "2",
+            "--format",
+            "json",
+            "--report-bytes-per-commit",
+            "--group-by-author",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let objects: Vec<_> = stdout.lines().map(parse_json_object).collect();
+    let field = |v: &str| Some(v.to_string());
+    assert_eq!(objects.len(), 4, "{stdout}");
+    assert_eq!(objects[0]["path"], field("a.txt"));
+    assert_eq!(objects[0]["action"], field("strip"));
+    assert_eq!(objects[1]["commit"], field(fixed.trim()));
+    assert_eq!(objects[1]["bytes_removed"], field("1"));
+    assert_eq!(objects[2]["author_email"], field("test@example.com"));
+    assert_eq!(objects[2]["commits"], field("1"));
+    assert_eq!(objects[3]["changed"], field("1"));
+    assert_eq!(git_stdout(&repo_dir, &["show", "HEAD~1:a.txt"]), b"x");
+
+    fs::remove_dir_all(&repo_dir).unwrap();
+}
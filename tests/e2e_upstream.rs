@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn unique_temp_dir(prefix: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let pid = std::process::id();
+    std::env::temp_dir().join(format!("{prefix}-{pid}-{nanos}"))
+}
+
+fn run_git(repo_dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .current_dir(repo_dir)
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[test]
+fn compare_with_origin_reports_newlines_added_since_upstream() {
+    let root = unique_temp_dir("git-fix-eof-newline-upstream");
+    let origin_dir = root.join("origin");
+    let clone_dir = root.join("clone");
+    fs::create_dir_all(&origin_dir).unwrap();
+
+    run_git(&origin_dir, &["init"]);
+    run_git(&origin_dir, &["config", "user.name", "Test User"]);
+    run_git(&origin_dir, &["config", "user.email", "test@example.com"]);
+    fs::write(origin_dir.join("a.txt"), b"hello").unwrap();
+    fs::write(origin_dir.join("b.txt"), b"world").unwrap();
+    run_git(&origin_dir, &["add", "a.txt", "b.txt"]);
+    run_git(&origin_dir, &["commit", "-m", "base"]);
+
+    run_git(
+        &root,
+        &[
+            "clone",
+            origin_dir.to_str().unwrap(),
+            clone_dir.to_str().unwrap(),
+        ],
+    );
+    run_git(&clone_dir, &["config", "user.name", "Test User"]);
+    run_git(&clone_dir, &["config", "user.email", "test@example.com"]);
+
+    // Committed locally, not pushed.
+    fs::write(clone_dir.join("a.txt"), b"hello\n").unwrap();
+    run_git(&clone_dir, &["commit", "-am", "local change"]);
+    // Changed, but still without a trailing newline.
+    fs::write(clone_dir.join("b.txt"), b"world!").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_git-fix-eof-newline");
+    let out = Command::new(bin)
+        .current_dir(&clone_dir)
+        .arg("--compare-with-origin")
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(stdout, "upstream drift: a.txt\n");
+    assert_eq!(fs::read(clone_dir.join("a.txt")).unwrap(), b"hello\n");
+
+    fs::remove_dir_all(&root).unwrap();
+}